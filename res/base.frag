@@ -1,10 +1,153 @@
 #version 330 core
 
-flat in vec3 color;
+flat in vec4 color;
+flat in vec4 borderColor;
+in vec2 local;
+flat in vec2 extent;
+flat in vec4 radii;
+flat in vec4 params;
+in vec2 gradient;
+flat in vec2 ramp;
+
+uniform sampler2D ramps;
+uniform sampler2D image;
 
 out vec4 FragColor;
 
+// The color of the shape, from the vertices or a gradient
+vec4 paint;
+
+#define SOLID 0
+#define ROUNDED_RECT 1
+#define ELLIPSE 2
+#define ARC 3
+#define PIE 4
+#define MESH 5
+#define IMAGE 6
+
+#define LINEAR 1
+#define RADIAL 2
+#define CONIC 3
+
+#define REPEAT 1
+#define REFLECT 2
+
+#define TAU 6.283185307179586
+#define RAMP_WIDTH 256
+
+// Signed distance to a rectangle with the corner radii clockwise from the top left
+float roundedRect(vec2 p, vec2 halfSize, vec4 r)
+{
+    float radius = p.x > 0.0 ? (p.y > 0.0 ? r.z : r.y) : (p.y > 0.0 ? r.w : r.x);
+    vec2 q = abs(p) - halfSize + radius;
+    return min(max(q.x, q.y), 0.0) + length(max(q, 0.0)) - radius;
+}
+
+// Approximate signed distance to an ellipse, exact for circles
+float ellipse(vec2 p, vec2 r)
+{
+    float k1 = length(p / r);
+    float k2 = length(p / (r * r));
+    if (k2 == 0.0) {
+        return -min(r.x, r.y);
+    }
+    return k1 * (k1 - 1.0) / k2;
+}
+
+// Signed distance to the rays enclosing the angles around the bisector,
+// aperture is the sine and cosine of half of the angle between them
+float wedge(vec2 p, vec2 bisector, vec2 aperture)
+{
+    vec2 q = vec2(abs(p.y * bisector.x - p.x * bisector.y), dot(p, bisector));
+    vec2 d = q - aperture * max(dot(q, aperture), 0.0);
+    return length(d) * sign(aperture.y * q.x - aperture.x * q.y);
+}
+
+// A line of the given width along the inside of an edge
+float stroke(float distance, float width)
+{
+    return max(distance, -(distance + width));
+}
+
+// Color of a gradient at the interpolated position, from its row of the ramps
+vec4 gradientColor(int kind)
+{
+    float offset = gradient.x;
+    if (kind == RADIAL) {
+        offset = length(gradient);
+    } else if (kind == CONIC) {
+        offset = fract(atan(gradient.y, gradient.x) / TAU);
+    }
+
+    int extend = int(ramp.y);
+    if (extend == REPEAT) {
+        offset = fract(offset);
+    } else if (extend == REFLECT) {
+        offset = 1.0 - abs(mod(offset, 2.0) - 1.0);
+    }
+    offset = clamp(offset, 0.0, 1.0);
+
+    int texel = min(int(offset * float(RAMP_WIDTH)), RAMP_WIDTH - 1);
+    return texelFetch(ramps, ivec2(texel, int(ramp.x)), 0);
+}
+
+// Fill and border of a shape, anti-aliased over one pixel
+vec4 shade(float distance)
+{
+    float borderWidth = params.x;
+
+    vec4 fill = vec4(paint.rgb * paint.a, paint.a);
+    if (borderWidth > 0.0) {
+        vec4 border = vec4(borderColor.rgb * borderColor.a, borderColor.a);
+        fill = mix(border, fill, clamp(0.5 - (distance + borderWidth), 0.0, 1.0));
+    }
+
+    float alpha = fill.a * clamp(0.5 - distance, 0.0, 1.0);
+    if (alpha <= 0.0) {
+        discard;
+    }
+
+    return vec4(fill.rgb / fill.a, alpha);
+}
+
 void main()
 {
-    FragColor = vec4(color, 1.0);
+    int kind = int(params.y);
+    float strokeWidth = params.z;
+    int gradientKind = int(params.w);
+
+    paint = gradientKind > 0 ? gradientColor(gradientKind) : color;
+
+    if (kind == ROUNDED_RECT) {
+        FragColor = shade(roundedRect(local, extent, radii));
+    } else if (kind == ELLIPSE || kind == ARC || kind == PIE) {
+        float distance = ellipse(local, extent);
+        if (kind == ARC) {
+            distance = max(stroke(distance, strokeWidth), wedge(local, radii.xy, radii.zw));
+        } else {
+            if (kind == PIE) {
+                distance = max(distance, wedge(local, radii.xy, radii.zw));
+            }
+            if (strokeWidth > 0.0) {
+                distance = stroke(distance, strokeWidth);
+            }
+        }
+        FragColor = shade(distance);
+    } else if (kind == MESH) {
+        // Distances across a stroke and inside from another edge
+        float halfWidth = extent.x;
+        float across = halfWidth + 0.5 - abs(local.x);
+        float coverage = clamp(min(min(across, local.y), halfWidth * 2.0), 0.0, 1.0);
+        if (coverage <= 0.0) {
+            discard;
+        }
+        FragColor = vec4(paint.rgb, paint.a * coverage);
+    } else if (kind == IMAGE) {
+        // Texels are premultiplied, so filtering does not bleed hidden colors
+        vec4 texel = texture(image, local);
+        vec3 straight = texel.a > 0.0 ? texel.rgb / texel.a : vec3(0.0);
+        FragColor = vec4(straight, texel.a) * paint;
+    } else {
+        FragColor = paint;
+    }
 }
\ No newline at end of file
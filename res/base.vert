@@ -1,11 +1,32 @@
 #version 330 core
 layout (location = 0) in vec2 aPos;
-layout (location = 1) in vec3 aColor;
+layout (location = 1) in vec4 aColor;
+layout (location = 2) in vec4 aBorderColor;
+layout (location = 3) in vec2 aLocal;
+layout (location = 4) in vec2 aExtent;
+layout (location = 5) in vec4 aRadii;
+layout (location = 6) in vec4 aParams;
+layout (location = 7) in vec2 aGradient;
+layout (location = 8) in vec2 aRamp;
 
-flat out vec3 color;
+flat out vec4 color;
+flat out vec4 borderColor;
+out vec2 local;
+flat out vec2 extent;
+flat out vec4 radii;
+flat out vec4 params;
+out vec2 gradient;
+flat out vec2 ramp;
 
 void main()
 {
     color = aColor;
+    borderColor = aBorderColor;
+    local = aLocal;
+    extent = aExtent;
+    radii = aRadii;
+    params = aParams;
+    gradient = aGradient;
+    ramp = aRamp;
     gl_Position = vec4(aPos.x, aPos.y, 0.0, 1.0);
 }
\ No newline at end of file
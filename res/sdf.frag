@@ -0,0 +1,66 @@
+#version 330 core
+
+in vec2 o_TexCoords;
+flat in vec4 o_Bounds;
+flat in vec2 o_UvPerPixel;
+flat in vec4 o_Color;
+flat in float o_Scale;
+flat in vec3 o_Effects;
+flat in vec2 o_ShadowOffset;
+flat in vec4 o_OutlineColor;
+flat in vec4 o_GlowColor;
+flat in vec4 o_ShadowColor;
+
+out vec4 color;
+
+uniform sampler2D field;
+// Field pixels encoded on either side of the outline
+uniform float spread;
+// The layer drawn over all glyphs: shadow, glow, outline or fill
+uniform int pass;
+
+// Screen pixels from `uv` to the outline, positive inside of the glyph
+float fieldDistance(vec2 uv) {
+    if (uv.x < o_Bounds.x || uv.y < o_Bounds.y || uv.x > o_Bounds.z || uv.y > o_Bounds.w) {
+        return -spread * o_Scale;
+    }
+
+    return (texture(field, uv).r - 0.5) * 2. * spread * o_Scale;
+}
+
+// Coverage of a pixel whose center is `inside` pixels inside of an edge,
+// which is blurred over `softness` pixels
+float coverage(float inside, float softness) {
+    return clamp(inside / max(softness, 1.) + 0.5, 0., 1.);
+}
+
+vec4 premultiplied(vec4 color, float alpha) {
+    return vec4(color.rgb, 1.) * color.a * alpha;
+}
+
+void main() {
+    float outlineWidth = o_Effects.x;
+    float glowRadius = o_Effects.y;
+    float shadowBlur = o_Effects.z;
+
+    float inside = fieldDistance(o_TexCoords);
+    color = vec4(0.);
+
+    if (pass == 0) {
+        if (o_ShadowColor.a > 0.) {
+            float shadow = fieldDistance(o_TexCoords - o_ShadowOffset * o_UvPerPixel);
+            color = premultiplied(o_ShadowColor, coverage(shadow, shadowBlur));
+        }
+    } else if (pass == 1) {
+        if (glowRadius > 0.) {
+            float glow = clamp(1. + inside / glowRadius, 0., 1.);
+            color = premultiplied(o_GlowColor, glow * glow);
+        }
+    } else if (pass == 2) {
+        if (outlineWidth > 0.) {
+            color = premultiplied(o_OutlineColor, coverage(inside + outlineWidth, 1.));
+        }
+    } else {
+        color = premultiplied(o_Color, coverage(inside, 1.));
+    }
+}
@@ -0,0 +1,46 @@
+#version 330 core
+
+layout(location = 0) in vec4 rect;
+layout(location = 1) in vec4 uv;
+layout(location = 2) in vec4 textColor;
+layout(location = 3) in float scale;
+// Outline width, glow radius and shadow blur
+layout(location = 4) in vec3 effects;
+layout(location = 5) in vec2 shadowOffset;
+layout(location = 6) in vec4 outlineColor;
+layout(location = 7) in vec4 glowColor;
+layout(location = 8) in vec4 shadowColor;
+
+out vec2 o_TexCoords;
+flat out vec4 o_Bounds;
+flat out vec2 o_UvPerPixel;
+flat out vec4 o_Color;
+flat out float o_Scale;
+flat out vec3 o_Effects;
+flat out vec2 o_ShadowOffset;
+flat out vec4 o_OutlineColor;
+flat out vec4 o_GlowColor;
+flat out vec4 o_ShadowColor;
+
+uniform mat4 projection;
+
+void main() {
+    vec2 position = vec2((gl_VertexID == 0 || gl_VertexID == 1) ? 1. : 0.,
+                         (gl_VertexID == 0 || gl_VertexID == 3) ? 0. : 1.);
+
+    // Grown to reach the shadow
+    vec2 grow = abs(shadowOffset);
+    vec2 corner = rect.xy - grow + (rect.zw + 2. * grow) * position;
+    gl_Position = projection * vec4(corner, 0.0, 1.0);
+
+    o_UvPerPixel = uv.zw / rect.zw;
+    o_TexCoords = uv.xy + (corner - rect.xy) * o_UvPerPixel;
+    o_Bounds = vec4(uv.xy, uv.xy + uv.zw);
+    o_Color = textColor;
+    o_Scale = scale;
+    o_Effects = effects;
+    o_ShadowOffset = shadowOffset;
+    o_OutlineColor = outlineColor;
+    o_GlowColor = glowColor;
+    o_ShadowColor = shadowColor;
+}
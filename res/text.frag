@@ -2,6 +2,7 @@
 
 in vec2 o_TexCoords;
 flat in vec4 o_Color;
+flat in int o_Flags;
 
 layout(location = 0, index = 0) out vec4 color;
 layout(location = 0, index = 1) out vec4 alphaMask;
@@ -11,9 +12,9 @@ uniform sampler2D mask;
 #define COLORED 2
 
 void main() {
-    if ((int(o_Color.a) & COLORED) != 0) {
+    if ((o_Flags & COLORED) != 0) {
         vec4 glyphColor = texture(mask, o_TexCoords);
-        alphaMask = vec4(glyphColor.a);
+        alphaMask = vec4(glyphColor.a * o_Color.a);
 
         if (glyphColor.a != 0) {
             glyphColor.rgb = vec3(glyphColor.rgb / glyphColor.a);
@@ -22,7 +23,7 @@ void main() {
         color = vec4(glyphColor.rgb, 1.0);
     } else {
         vec3 textColor = texture(mask, o_TexCoords).rgb;
-        alphaMask = vec4(textColor, textColor.r);
+        alphaMask = vec4(textColor, textColor.r) * o_Color.a;
         color = vec4(o_Color.rgb, 1.0);
     }
 }
\ No newline at end of file
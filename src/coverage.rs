@@ -0,0 +1,29 @@
+//! Measuring the area covered by white drawn on black, for tests of shapes
+//! whose exact pixels don't matter.
+
+use crate::renderer::backend::Backend;
+use crate::renderer::cpu::CpuBackend;
+
+/// Draws with `draw` into a black 128x128 `CpuBackend` and sums the coverage
+/// of the red channel.
+pub fn area<F: FnOnce(&mut CpuBackend)>(draw: F) -> f32 {
+    let mut backend = CpuBackend::new("DejaVu Sans", 16., 128, 128, 1.).unwrap();
+    draw(&mut backend);
+    backend.draw();
+
+    backend
+        .pixels()
+        .chunks_exact(4)
+        .map(|pixel| f32::from(pixel[0]) / 255.)
+        .sum()
+}
+
+/// Checks that an area is within 1% of `expected`.
+pub fn assert_area(area: f32, expected: f32) {
+    assert!(
+        (area - expected).abs() / expected < 0.01,
+        "{} is not {}",
+        area,
+        expected
+    );
+}
@@ -0,0 +1,176 @@
+//! Golden-image testing.
+//!
+//! Scenes are rendered offscreen and compared against the PNGs in
+//! `tests/golden`. Mismatches write `<name>.actual.png` and `<name>.diff.png`
+//! to `target/golden`. Run with `UPDATE_GOLDEN=1` to regenerate the references
+//! and with `GOLDEN_TOLERANCE=<n>` to override the per-channel tolerance.
+
+use crate::gl;
+use crate::renderer::framebuffer::{write_png, Framebuffer};
+use crate::{HeadlessContext, Vec2f};
+
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Default maximum difference per color channel.
+pub const TOLERANCE: u8 = 2;
+
+// The GL function pointers are global, so only one test renders at a time
+static GL_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Creates a headless context, runs `draw` into a cleared framebuffer of the
+/// given size and returns the result.
+pub fn render<F: FnOnce(Vec2f)>(width: u32, height: u32, draw: F) -> Image {
+    let _guard = GL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let _context = HeadlessContext::new().expect("unable to create a headless context");
+
+    let framebuffer =
+        Framebuffer::new(width as i32, height as i32).expect("unable to create a framebuffer");
+    framebuffer.bind();
+
+    unsafe {
+        gl::ClearColor(0., 0., 0., 1.);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+
+    draw(Vec2f {
+        x: width as f32,
+        y: height as f32,
+    });
+
+    let pixels = framebuffer.read_pixels();
+    framebuffer.unbind();
+
+    Image {
+        width,
+        height,
+        pixels,
+    }
+}
+
+/// Compares `image` against `tests/golden/<name>.png`, panicking with the
+/// number of differing pixels if any channel is off by more than `tolerance`.
+pub fn assert_golden(name: &str, image: &Image, tolerance: u8) {
+    let reference_path = root().join("tests/golden").join(format!("{}.png", name));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        write_png(&reference_path, image.width, image.height, &image.pixels).unwrap();
+        return;
+    }
+
+    let tolerance = env::var("GOLDEN_TOLERANCE")
+        .ok()
+        .and_then(|t| t.parse().ok())
+        .unwrap_or(tolerance);
+
+    let reference = read_png(&reference_path).unwrap_or_else(|| {
+        panic!(
+            "missing reference {}, run with UPDATE_GOLDEN=1 to create it",
+            reference_path.display()
+        )
+    });
+
+    if (reference.width, reference.height) != (image.width, image.height) {
+        write_failure(name, image, None);
+        panic!(
+            "{}: size {}x{} differs from reference {}x{}",
+            name, image.width, image.height, reference.width, reference.height
+        );
+    }
+
+    let (mismatched, diff) = compare(&reference, image, tolerance);
+    if mismatched > 0 {
+        let out = write_failure(name, image, Some(&diff));
+        panic!(
+            "{}: {} pixels differ by more than {}, see {}",
+            name,
+            mismatched,
+            tolerance,
+            out.display()
+        );
+    }
+}
+
+/// Returns the number of mismatched pixels and a diff image, in which
+/// matching pixels are dimmed and mismatches are red.
+pub fn compare(reference: &Image, image: &Image, tolerance: u8) -> (usize, Image) {
+    let mut mismatched = 0;
+    let mut diff = Vec::with_capacity(image.pixels.len());
+
+    for (expected, actual) in reference
+        .pixels
+        .chunks_exact(4)
+        .zip(image.pixels.chunks_exact(4))
+    {
+        let delta = expected
+            .iter()
+            .zip(actual)
+            .map(|(a, b)| (i16::from(*a) - i16::from(*b)).unsigned_abs())
+            .max()
+            .unwrap_or(0);
+
+        if delta > u16::from(tolerance) {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            diff.extend_from_slice(&[actual[0] / 4, actual[1] / 4, actual[2] / 4, 255]);
+        }
+    }
+
+    let diff = Image {
+        width: image.width,
+        height: image.height,
+        pixels: diff,
+    };
+
+    (mismatched, diff)
+}
+
+fn read_png(path: &PathBuf) -> Option<Image> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().ok()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).ok()?;
+
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return None;
+    }
+
+    pixels.truncate(info.buffer_size());
+
+    Some(Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn write_failure(name: &str, image: &Image, diff: Option<&Image>) -> PathBuf {
+    let dir = root().join("target/golden");
+    fs::create_dir_all(&dir).unwrap();
+
+    let actual = dir.join(format!("{}.actual.png", name));
+    write_png(&actual, image.width, image.height, &image.pixels).unwrap();
+
+    match diff {
+        Some(diff) => {
+            let path = dir.join(format!("{}.diff.png", name));
+            write_png(&path, diff.width, diff.height, &diff.pixels).unwrap();
+            path
+        }
+        None => actual,
+    }
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
@@ -0,0 +1,154 @@
+use crate::gl;
+
+use khronos_egl as egl;
+use std::fmt::{self, Display, Formatter};
+
+// EGL_MESA_platform_surfaceless
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+type Egl = egl::DynamicInstance<egl::EGL1_4>;
+
+#[derive(Debug)]
+pub enum HeadlessError {
+    Load(String),
+    NoDisplay,
+    NoConfig,
+    Egl(egl::Error),
+}
+
+impl std::error::Error for HeadlessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HeadlessError::Egl(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for HeadlessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadlessError::Load(err) => write!(f, "Unable to load libEGL: {}", err),
+            HeadlessError::NoDisplay => write!(f, "No EGL display available"),
+            HeadlessError::NoConfig => write!(f, "No matching EGL config"),
+            HeadlessError::Egl(err) => write!(f, "EGL call failed: {}", err),
+        }
+    }
+}
+
+impl From<egl::Error> for HeadlessError {
+    fn from(err: egl::Error) -> Self {
+        HeadlessError::Egl(err)
+    }
+}
+
+/// An OpenGL 3.3 core context without any window.
+///
+/// Mesa's surfaceless platform is tried first (this also covers llvmpipe),
+/// otherwise the default display is used with a 1x1 pbuffer surface. Either
+/// way nothing is presented, so everything has to be drawn into a
+/// [`Framebuffer`](crate::renderer::framebuffer::Framebuffer).
+pub struct HeadlessContext {
+    egl: Egl,
+    display: egl::Display,
+    surface: Option<egl::Surface>,
+    context: egl::Context,
+}
+
+impl HeadlessContext {
+    pub fn new() -> Result<Self, HeadlessError> {
+        let egl =
+            unsafe { Egl::load_required() }.map_err(|e| HeadlessError::Load(e.to_string()))?;
+
+        let surfaceless = egl
+            .upcast::<egl::EGL1_5>()
+            .and_then(|egl| {
+                egl.get_platform_display(
+                    PLATFORM_SURFACELESS_MESA,
+                    egl::DEFAULT_DISPLAY,
+                    &[egl::ATTRIB_NONE],
+                )
+                .ok()
+            })
+            .filter(|display| egl.initialize(*display).is_ok());
+
+        let (display, use_pbuffer) = match surfaceless {
+            Some(display) => (display, false),
+            None => {
+                let display = egl
+                    .get_display(egl::DEFAULT_DISPLAY)
+                    .ok_or(HeadlessError::NoDisplay)?;
+                egl.initialize(display)?;
+                (display, true)
+            }
+        };
+
+        egl.bind_api(egl::OPENGL_API)?;
+
+        let config_attributes = [
+            egl::SURFACE_TYPE,
+            egl::PBUFFER_BIT,
+            egl::RENDERABLE_TYPE,
+            egl::OPENGL_BIT,
+            egl::RED_SIZE,
+            8,
+            egl::GREEN_SIZE,
+            8,
+            egl::BLUE_SIZE,
+            8,
+            egl::ALPHA_SIZE,
+            8,
+            egl::NONE,
+        ];
+        let config = egl
+            .choose_first_config(display, &config_attributes)?
+            .ok_or(HeadlessError::NoConfig)?;
+
+        let context_attributes = [
+            egl::CONTEXT_MAJOR_VERSION,
+            3,
+            egl::CONTEXT_MINOR_VERSION,
+            3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK,
+            egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ];
+        let context = egl.create_context(display, config, None, &context_attributes)?;
+
+        let surface = if use_pbuffer {
+            let surface = egl.create_pbuffer_surface(
+                display,
+                config,
+                &[egl::WIDTH, 1, egl::HEIGHT, 1, egl::NONE],
+            )?;
+            Some(surface)
+        } else {
+            None
+        };
+
+        egl.make_current(display, surface, surface, Some(context))?;
+
+        gl::load_with(|s| match egl.get_proc_address(s) {
+            Some(f) => f as *const _,
+            None => std::ptr::null(),
+        });
+
+        Ok(Self {
+            egl,
+            display,
+            surface,
+            context,
+        })
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        if let Some(surface) = self.surface {
+            let _ = self.egl.destroy_surface(self.display, surface);
+        }
+        let _ = self.egl.terminate(self.display);
+    }
+}
@@ -4,16 +4,97 @@ mod gl {
 }
 
 mod vectors {
-    #[derive(Clone, Copy, Debug)]
+    use std::ops::{Add, Mul, Neg, Sub};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
     pub struct Vec2f {
         pub x: f32,
         pub y: f32,
     }
+
+    impl Vec2f {
+        pub const fn new(x: f32, y: f32) -> Self {
+            Self { x, y }
+        }
+
+        pub fn dot(self, other: Self) -> f32 {
+            self.x * other.x + self.y * other.y
+        }
+
+        /// The z component of the cross product, positive if `other` turns
+        /// clockwise on the screen.
+        pub fn cross(self, other: Self) -> f32 {
+            self.x * other.y - self.y * other.x
+        }
+
+        pub fn length(self) -> f32 {
+            self.dot(self).sqrt()
+        }
+
+        /// Rotated by 90 degrees clockwise on the screen.
+        pub fn perp(self) -> Self {
+            Self::new(-self.y, self.x)
+        }
+    }
+
+    impl Add for Vec2f {
+        type Output = Self;
+
+        fn add(self, other: Self) -> Self {
+            Self::new(self.x + other.x, self.y + other.y)
+        }
+    }
+
+    impl Sub for Vec2f {
+        type Output = Self;
+
+        fn sub(self, other: Self) -> Self {
+            Self::new(self.x - other.x, self.y - other.y)
+        }
+    }
+
+    impl Mul<f32> for Vec2f {
+        type Output = Self;
+
+        fn mul(self, factor: f32) -> Self {
+            Self::new(self.x * factor, self.y * factor)
+        }
+    }
+
+    impl Neg for Vec2f {
+        type Output = Self;
+
+        fn neg(self) -> Self {
+            Self::new(-self.x, -self.y)
+        }
+    }
 }
 
+#[cfg(test)]
+mod coverage;
+#[cfg(test)]
+mod golden;
+mod headless;
 mod renderer;
-pub use renderer::font::TextRenderer;
-pub use renderer::{RenderRect, Renderer, Rgb};
+pub use headless::HeadlessContext;
+pub use renderer::atlas::{
+    Allocation, AtlasError, Occupancy, Packer, RowPacker, SkylinePacker, Sprite, SpriteAtlas,
+    SpriteBatch, SpriteInstance,
+};
+pub use renderer::backend::{Backend, GlBackend};
+pub use renderer::cpu::CpuBackend;
+pub use renderer::decoration::{Decoration, DecorationMetrics, LineStyle};
+pub use renderer::font::{GlyphMode, MemoryUsage, TextRenderer};
+pub use renderer::framebuffer::Framebuffer;
+pub use renderer::gradient::{ColorStop, Extend, Gradient, GradientKind, GradientShape};
+pub use renderer::image::{Filter, Image, ImageDraw, ImageError, ImageFormat, ImageId};
+pub use renderer::layout::{Align, Direction, Layout};
+pub use renderer::path::{FillRule, Path, PathBuilder};
+pub use renderer::sdf::{Glow, Outline, Shadow, TextEffects};
+pub use renderer::shape::{Arc, Border, CornerRadii, Ellipse, Pie, RoundedRect, Style};
+pub use renderer::shaping::FontStyle;
+pub use renderer::stroke::{LineCap, LineJoin, Stroke};
+pub use renderer::{Rect, RenderRect, Renderer, Rgb, Rgba};
 pub use vectors::Vec2f;
 
 use glutin::dpi::PhysicalSize;
@@ -22,9 +103,104 @@ use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::window::WindowBuilder;
 use glutin::ContextBuilder;
 
+use std::error::Error;
+
+/// Tried in order for characters "Roboto" has no glyph for.
+const FALLBACK_FONTS: &[&str] = &["Noto Sans CJK SC", "Noto Color Emoji", "DejaVu Sans"];
+
+fn clear() {
+    unsafe {
+        gl::ClearColor(0., 0., 0., 1.);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+}
+
+fn add_fallbacks(backend: &mut dyn Backend) -> Result<(), Box<dyn Error>> {
+    for family in FALLBACK_FONTS {
+        backend.add_fallback(family)?;
+    }
+
+    Ok(())
+}
+
+fn draw_frame(backend: &mut dyn Backend, size: Vec2f) {
+    // Some basic Text
+    let text = "汉语/漢語, Hànyǔ or 中文, 😍🔞😎👍🏽🇩🇪👨\u{200D}👩\u{200D}👧";
+
+    let (width, height) = (size.x as i16, size.y as i16);
+    let (font_length, font_height) = (
+        backend.get_length(text, FontStyle::REGULAR),
+        backend.get_height(),
+    );
+
+    let x = width / 2 - font_length / 2;
+    let y = height / 2 - font_height / 2;
+
+    backend.draw_string(
+        text,
+        x,
+        y,
+        Rgb::from_hex(0xFFFFFF).into(),
+        FontStyle::REGULAR,
+    );
+
+    backend.draw();
+}
+
+/// Renders a single frame without creating a window and writes it to `path`.
+fn run_headless(size: Vec2f, path: &str) -> Result<(), Box<dyn Error>> {
+    let _context = HeadlessContext::new()?;
+
+    let framebuffer = Framebuffer::new(size.x as i32, size.y as i32)?;
+    let mut backend = GlBackend::new("Roboto", 20., size, 1.)?;
+    add_fallbacks(&mut backend)?;
+
+    framebuffer.bind();
+    clear();
+    draw_frame(&mut backend, size);
+    framebuffer.save_png(path)?;
+    framebuffer.unbind();
+
+    Ok(())
+}
+
+/// Same as `run_headless`, but without touching OpenGL at all.
+fn run_cpu(size: Vec2f, path: &str) -> Result<(), Box<dyn Error>> {
+    let mut backend = CpuBackend::new("Roboto", 20., size.x as usize, size.y as usize, 1.)?;
+    add_fallbacks(&mut backend)?;
+
+    draw_frame(&mut backend, size);
+    backend.save_png(path)?;
+
+    Ok(())
+}
+
 fn main() {
     let mut size = Vec2f { x: 1600., y: 1200. };
 
+    // `--headless <file.png>` renders one frame offscreen and exits,
+    // with `--cpu` the software renderer is used instead of OpenGL
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--headless") {
+        let path = args
+            .get(index + 1)
+            .filter(|arg| !arg.starts_with("--"))
+            .map_or("frame.png", String::as_str);
+
+        let result = if args.iter().any(|arg| arg == "--cpu") {
+            run_cpu(size, path)
+        } else {
+            run_headless(size, path)
+        };
+
+        if let Err(err) = result {
+            eprintln!("Headless rendering failed: {}", err);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     // On windows the physical size is a bit too big
     if std::env::consts::OS == "windows" {
         size.x /= 2.;
@@ -34,7 +210,7 @@ fn main() {
     let el = EventLoop::new();
     let wb = WindowBuilder::new()
         .with_title("Renderer")
-        .with_resizable(false)
+        .with_resizable(true)
         .with_inner_size(PhysicalSize::new(size.x as u16, size.y as u16));
     let windowed_context = ContextBuilder::new().build_windowed(wb, &el).unwrap();
     let windowed_context = unsafe { windowed_context.make_current().unwrap() };
@@ -44,9 +220,12 @@ fn main() {
     #[cfg(any(not(feature = "x11"), target_os = "macos", windows))]
     let is_x11 = false;
     #[cfg(all(feature = "x11", not(any(target_os = "macos", windows))))]
-    let is_x11 = event_loop.is_x11();
+    let is_x11 = {
+        use glutin::platform::unix::EventLoopWindowTargetExtUnix;
+        el.is_x11()
+    };
 
-    let estimated_dpr = if cfg!(any(target_os = "macos", windows)) || is_x11 {
+    let mut dpr = if cfg!(any(target_os = "macos", windows)) || is_x11 {
         el.available_monitors()
             .next()
             .map(|m| m.scale_factor())
@@ -55,39 +234,46 @@ fn main() {
         1.
     };
 
-    let mut renderer = Renderer::new(size).unwrap();
-    let mut font = TextRenderer::new("Roboto", 20., size, estimated_dpr).unwrap();
+    let mut backend = GlBackend::new("Roboto", 20., size, dpr).unwrap();
+    add_fallbacks(&mut backend).unwrap();
 
     el.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
 
         match event {
-            Event::LoopDestroyed => return,
+            Event::LoopDestroyed => (),
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(physical_size) => windowed_context.resize(physical_size),
+                WindowEvent::Resized(physical_size) => {
+                    windowed_context.resize(physical_size);
+
+                    size = Vec2f {
+                        x: physical_size.width as f32,
+                        y: physical_size.height as f32,
+                    };
+                    backend.resize(size, dpr);
+                    windowed_context.window().request_redraw();
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    windowed_context.resize(*new_inner_size);
+
+                    dpr = scale_factor;
+                    size = Vec2f {
+                        x: new_inner_size.width as f32,
+                        y: new_inner_size.height as f32,
+                    };
+                    backend.resize(size, dpr);
+                    windowed_context.window().request_redraw();
+                }
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                 _ => (),
             },
             Event::RedrawRequested(_) => {
-                unsafe {
-                    gl::ClearColor(0., 0., 0., 1.);
-                    gl::Clear(gl::COLOR_BUFFER_BIT);
-
-                    gl::Viewport(0, 0, size.x as i32, size.y as i32);
-
-                    // Some basic Text
-                    let text = "汉语/漢語, Hànyǔ or 中文, 😍🔞😎";
+                clear();
+                draw_frame(&mut backend, size);
 
-                    let (width, height) = (size.x as i16, size.y as i16);
-                    let (font_length, font_height) = (font.get_length(text), font.get_height());
-
-                    let x = width / 2 - font_length / 2;
-                    let y = height / 2 - font_height / 2;
-
-                    font.draw_string(text, x, y, 0xFFFFFF);
-
-                    renderer.draw();
-                }
                 windowed_context.swap_buffers().unwrap();
             }
             _ => (),
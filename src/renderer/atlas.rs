@@ -0,0 +1,981 @@
+use crate::renderer::backend::Backend;
+use crate::renderer::image::{Filter, Image, ImageError, ImageId};
+use crate::renderer::{Rect, Rgba};
+
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtlasError {
+    /// There is no room left for the rectangle.
+    Full,
+    /// The rectangle is larger than the atlas.
+    TooLarge,
+    /// The image has not `width * height * 4` bytes of pixels.
+    InvalidImage,
+}
+
+impl std::error::Error for AtlasError {}
+
+impl Display for AtlasError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AtlasError::Full => write!(f, "The atlas is full"),
+            AtlasError::TooLarge => write!(f, "The image is larger than the atlas"),
+            AtlasError::InvalidImage => write!(f, "The pixels do not match the image size"),
+        }
+    }
+}
+
+/// Where a rectangle was placed by a [`Packer`], inside of its padding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Allocation {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// How much of the area of a packer is in use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Occupancy {
+    pub allocations: usize,
+    /// Pixels of the allocations, without their padding.
+    pub used: i64,
+    pub total: i64,
+}
+
+impl Occupancy {
+    /// The used part of the area, from 0 to 1.
+    pub fn ratio(&self) -> f32 {
+        if self.total == 0 {
+            return 0.;
+        }
+        self.used as f32 / self.total as f32
+    }
+}
+
+/// Finds room for rectangles in an area.
+///
+/// Every rectangle is surrounded by the padding of the packer, which no
+/// other rectangle overlaps. Empty rectangles take up no room.
+pub trait Packer {
+    fn insert(&mut self, width: i32, height: i32) -> Result<Allocation, AtlasError>;
+
+    /// Makes the room of an allocation available again.
+    fn free(&mut self, allocation: Allocation);
+
+    /// Frees every allocation.
+    fn clear(&mut self);
+
+    fn occupancy(&self) -> Occupancy;
+}
+
+/// An area of a packer, with the padding of its allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+/// Counts what packers have handed out.
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    width: i32,
+    height: i32,
+    padding: i32,
+    allocations: usize,
+    used: i64,
+}
+
+impl Usage {
+    fn new(width: i32, height: i32, padding: i32) -> Self {
+        Self {
+            width,
+            height,
+            padding,
+            allocations: 0,
+            used: 0,
+        }
+    }
+
+    /// The size of a rectangle with its padding, if it fits at all.
+    fn padded(&self, width: i32, height: i32) -> Result<(i32, i32), AtlasError> {
+        let (width, height) = (width + self.padding * 2, height + self.padding * 2);
+        if width > self.width || height > self.height {
+            return Err(AtlasError::TooLarge);
+        }
+        Ok((width, height))
+    }
+
+    fn allocate(&mut self, region: Region) -> Allocation {
+        let allocation = Allocation {
+            x: region.x + self.padding,
+            y: region.y + self.padding,
+            width: region.width - self.padding * 2,
+            height: region.height - self.padding * 2,
+        };
+        self.allocations += 1;
+        self.used += i64::from(allocation.width) * i64::from(allocation.height);
+        allocation
+    }
+
+    /// Takes back an allocation and returns its region, unless it is empty.
+    fn release(&mut self, allocation: Allocation) -> Option<Region> {
+        if allocation.width == 0 || allocation.height == 0 {
+            return None;
+        }
+
+        self.allocations -= 1;
+        self.used -= i64::from(allocation.width) * i64::from(allocation.height);
+        Some(Region {
+            x: allocation.x - self.padding,
+            y: allocation.y - self.padding,
+            width: allocation.width + self.padding * 2,
+            height: allocation.height + self.padding * 2,
+        })
+    }
+
+    fn occupancy(&self) -> Occupancy {
+        Occupancy {
+            allocations: self.allocations,
+            used: self.used,
+            total: i64::from(self.width) * i64::from(self.height),
+        }
+    }
+}
+
+/// Places rectangles left to right in rows, a row is as tall as its tallest
+/// rectangle.
+///
+/// Rows of mixed heights waste the room above their shorter rectangles, and
+/// freed room is only reused once every rectangle is freed.
+#[derive(Debug, Clone)]
+pub struct RowPacker {
+    usage: Usage,
+    row_extent: i32,
+    row_baseline: i32,
+    row_tallest: i32,
+}
+
+impl RowPacker {
+    pub fn new(width: i32, height: i32, padding: i32) -> Self {
+        Self {
+            usage: Usage::new(width, height, padding),
+            row_extent: 0,
+            row_baseline: 0,
+            row_tallest: 0,
+        }
+    }
+
+    fn room_in_row(&self, width: i32, height: i32) -> bool {
+        let enough_width = self.row_extent + width <= self.usage.width;
+        let enough_height = height <= self.usage.height - self.row_baseline;
+
+        enough_width && enough_height
+    }
+
+    fn advance_row(&mut self) -> Result<(), AtlasError> {
+        let advance_to = self.row_baseline + self.row_tallest;
+        if self.usage.height - advance_to <= 0 {
+            return Err(AtlasError::Full);
+        }
+
+        self.row_baseline = advance_to;
+        self.row_extent = 0;
+        self.row_tallest = 0;
+
+        Ok(())
+    }
+}
+
+impl Packer for RowPacker {
+    fn insert(&mut self, width: i32, height: i32) -> Result<Allocation, AtlasError> {
+        if width == 0 || height == 0 {
+            return Ok(Allocation::default());
+        }
+        let (width, height) = self.usage.padded(width, height)?;
+
+        if !self.room_in_row(width, height) {
+            self.advance_row()?;
+        }
+
+        if !self.room_in_row(width, height) {
+            return Err(AtlasError::Full);
+        }
+
+        let region = Region {
+            x: self.row_extent,
+            y: self.row_baseline,
+            width,
+            height,
+        };
+        self.row_extent += width;
+        self.row_tallest = self.row_tallest.max(height);
+
+        Ok(self.usage.allocate(region))
+    }
+
+    fn free(&mut self, allocation: Allocation) {
+        if self.usage.release(allocation).is_some() && self.usage.allocations == 0 {
+            self.clear();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.usage = Usage::new(self.usage.width, self.usage.height, self.usage.padding);
+        self.row_extent = 0;
+        self.row_baseline = 0;
+        self.row_tallest = 0;
+    }
+
+    fn occupancy(&self) -> Occupancy {
+        self.usage.occupancy()
+    }
+}
+
+/// The top edge of the allocations above a span of columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+/// Places every rectangle as low as possible on the skyline of the ones
+/// placed before, like Tetris pieces.
+///
+/// The gaps left under a rectangle and freed allocations are kept in a list
+/// of free regions, which later rectangles are placed in first. Freed room
+/// is joined with the free regions next to it, and given back to the skyline
+/// when nothing is placed above it.
+#[derive(Debug, Clone)]
+pub struct SkylinePacker {
+    usage: Usage,
+    /// Sorted from left to right, covering the whole width.
+    skyline: Vec<Segment>,
+    free: Vec<Region>,
+}
+
+impl SkylinePacker {
+    pub fn new(width: i32, height: i32, padding: i32) -> Self {
+        Self {
+            usage: Usage::new(width, height, padding),
+            skyline: vec![Segment { x: 0, y: 0, width }],
+            free: Vec::new(),
+        }
+    }
+
+    /// Splits the best fitting free region, the rest of it stays free.
+    fn insert_free(&mut self, width: i32, height: i32) -> Option<Region> {
+        let (index, _) = self
+            .free
+            .iter()
+            .enumerate()
+            .filter(|(_, free)| free.width >= width && free.height >= height)
+            .min_by_key(|(_, free)| i64::from(free.width) * i64::from(free.height))?;
+
+        let free = self.free.swap_remove(index);
+        let right = Region {
+            x: free.x + width,
+            y: free.y,
+            width: free.width - width,
+            height,
+        };
+        let below = Region {
+            x: free.x,
+            y: free.y + height,
+            width: free.width,
+            height: free.height - height,
+        };
+        for rest in [right, below] {
+            if rest.width > 0 && rest.height > 0 {
+                self.free.push(rest);
+            }
+        }
+
+        Some(Region {
+            x: free.x,
+            y: free.y,
+            width,
+            height,
+        })
+    }
+
+    /// The lowest position on the skyline, preferring the least room wasted
+    /// underneath. Returns the first segment and the top of the rectangle.
+    fn fit_skyline(&self, width: i32, height: i32) -> Option<(usize, i32)> {
+        let mut best: Option<((i32, i64), usize, i32)> = None;
+
+        for (first, segment) in self.skyline.iter().enumerate() {
+            let right = segment.x + width;
+            if right > self.usage.width {
+                break;
+            }
+
+            let covered = self
+                .skyline
+                .iter()
+                .skip(first)
+                .take_while(|covered| covered.x < right);
+            let top = covered.clone().map(|covered| covered.y).max().unwrap_or(0);
+            if top + height > self.usage.height {
+                continue;
+            }
+
+            let waste: i64 = covered
+                .map(|covered| {
+                    let overlap = (covered.x + covered.width).min(right) - covered.x;
+                    i64::from(overlap) * i64::from(top - covered.y)
+                })
+                .sum();
+            let score = (top + height, waste);
+            if best.is_none_or(|(best, ..)| score < best) {
+                best = Some((score, first, top));
+            }
+        }
+
+        best.map(|(_, first, top)| (first, top))
+    }
+
+    /// Raises the skyline over a rectangle, the gaps below become free.
+    fn insert_skyline(&mut self, first: usize, top: i32, width: i32, height: i32) -> Region {
+        let x = self.skyline[first].x;
+        let right = x + width;
+
+        let mut last = first;
+        while last < self.skyline.len() && self.skyline[last].x < right {
+            let segment = self.skyline[last];
+            let overlap = (segment.x + segment.width).min(right) - segment.x;
+            if top > segment.y {
+                self.free.push(Region {
+                    x: segment.x,
+                    y: segment.y,
+                    width: overlap,
+                    height: top - segment.y,
+                });
+            }
+            last += 1;
+        }
+
+        // The last covered segment may stick out on the right
+        let cut = self.skyline[last - 1];
+        let mut replacement = vec![Segment {
+            x,
+            y: top + height,
+            width,
+        }];
+        if cut.x + cut.width > right {
+            replacement.push(Segment {
+                x: right,
+                y: cut.y,
+                width: cut.x + cut.width - right,
+            });
+        }
+        self.skyline.splice(first..last, replacement);
+
+        self.join_segments();
+
+        Region {
+            x,
+            y: top,
+            width,
+            height,
+        }
+    }
+
+    /// Neighbours of the same height are one segment.
+    fn join_segments(&mut self) {
+        self.skyline.dedup_by(|next, previous| {
+            if next.y == previous.y {
+                previous.width += next.width;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Whether the skyline is right below `region` in all of its columns.
+    fn on_skyline(&self, region: &Region) -> bool {
+        let right = region.x + region.width;
+        self.skyline
+            .iter()
+            .filter(|segment| segment.x < right && segment.x + segment.width > region.x)
+            .all(|segment| segment.y == region.y + region.height)
+    }
+
+    /// Lowers the skyline to the top of `region`, which must be on it.
+    fn lower_skyline(&mut self, region: &Region) {
+        let right = region.x + region.width;
+        let mut skyline = Vec::with_capacity(self.skyline.len() + 2);
+
+        for &segment in &self.skyline {
+            let end = segment.x + segment.width;
+            if end <= region.x || segment.x >= right {
+                skyline.push(segment);
+                continue;
+            }
+
+            if segment.x < region.x {
+                skyline.push(Segment {
+                    width: region.x - segment.x,
+                    ..segment
+                });
+            }
+            if segment.x <= region.x {
+                skyline.push(Segment {
+                    x: region.x,
+                    y: region.y,
+                    width: region.width,
+                });
+            }
+            if end > right {
+                skyline.push(Segment {
+                    x: right,
+                    width: end - right,
+                    ..segment
+                });
+            }
+        }
+
+        self.skyline = skyline;
+        self.join_segments();
+    }
+
+    /// Makes freed room available again. It is joined with every free region
+    /// it shares a whole edge with, and regions on the skyline lower it
+    /// instead of being kept.
+    fn release(&mut self, mut region: Region) {
+        loop {
+            if self.on_skyline(&region) {
+                self.lower_skyline(&region);
+
+                // Lowering may put other free regions on the skyline
+                match self.free.iter().position(|free| self.on_skyline(free)) {
+                    Some(index) => region = self.free.swap_remove(index),
+                    None => return,
+                }
+                continue;
+            }
+
+            let joined = self
+                .free
+                .iter()
+                .enumerate()
+                .find_map(|(index, free)| Some((index, join(free, &region)?)));
+            match joined {
+                Some((index, joined)) => {
+                    self.free.swap_remove(index);
+                    region = joined;
+                }
+                None => {
+                    self.free.push(region);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// The region covering two regions that share a whole edge.
+fn join(a: &Region, b: &Region) -> Option<Region> {
+    let (first, second) = if (a.x, a.y) <= (b.x, b.y) {
+        (a, b)
+    } else {
+        (b, a)
+    };
+
+    let beside =
+        first.y == second.y && first.height == second.height && first.x + first.width == second.x;
+    let above =
+        first.x == second.x && first.width == second.width && first.y + first.height == second.y;
+
+    if beside {
+        Some(Region {
+            width: first.width + second.width,
+            ..*first
+        })
+    } else if above {
+        Some(Region {
+            height: first.height + second.height,
+            ..*first
+        })
+    } else {
+        None
+    }
+}
+
+impl Packer for SkylinePacker {
+    fn insert(&mut self, width: i32, height: i32) -> Result<Allocation, AtlasError> {
+        if width == 0 || height == 0 {
+            return Ok(Allocation::default());
+        }
+        let (width, height) = self.usage.padded(width, height)?;
+
+        let region = match self.insert_free(width, height) {
+            Some(region) => region,
+            None => {
+                let (first, top) = self.fit_skyline(width, height).ok_or(AtlasError::Full)?;
+                self.insert_skyline(first, top, width, height)
+            }
+        };
+
+        Ok(self.usage.allocate(region))
+    }
+
+    fn free(&mut self, allocation: Allocation) {
+        if let Some(region) = self.usage.release(allocation) {
+            if self.usage.allocations == 0 {
+                self.clear();
+            } else {
+                self.release(region);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self::new(self.usage.width, self.usage.height, self.usage.padding);
+    }
+
+    fn occupancy(&self) -> Occupancy {
+        self.usage.occupancy()
+    }
+}
+
+/// An image packed into a [`SpriteAtlas`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    /// Where the image is in the atlas, in pixels.
+    pub source: Rect,
+    allocation: Allocation,
+}
+
+/// Many small images packed into one, so they can be drawn from a single
+/// texture.
+///
+/// The padding around every sprite repeats its edge pixels, so filtering
+/// never blends in the neighbouring sprites.
+#[derive(Debug)]
+pub struct SpriteAtlas {
+    packer: SkylinePacker,
+    padding: i32,
+    image: Image,
+    /// The image loaded by a backend, until the atlas changes.
+    loaded: Option<ImageId>,
+    changed: bool,
+}
+
+impl SpriteAtlas {
+    pub fn new(width: u32, height: u32, padding: u32) -> Self {
+        Self {
+            packer: SkylinePacker::new(width as i32, height as i32, padding as i32),
+            padding: padding as i32,
+            image: Image {
+                width,
+                height,
+                pixels: vec![0; width as usize * height as usize * 4],
+            },
+            loaded: None,
+            changed: true,
+        }
+    }
+
+    /// The packed sprites, transparent where there are none.
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Copies `image` into the atlas. Empty images take up no room.
+    pub fn insert(&mut self, image: &Image) -> Result<Sprite, AtlasError> {
+        if image.pixels.len() != image.width as usize * image.height as usize * 4 {
+            return Err(AtlasError::InvalidImage);
+        }
+
+        let (width, height) = (image.width as i32, image.height as i32);
+        if width == 0 || height == 0 {
+            return Ok(Sprite {
+                source: Rect {
+                    x: 0.,
+                    y: 0.,
+                    width: width as f32,
+                    height: height as f32,
+                },
+                allocation: Allocation::default(),
+            });
+        }
+
+        let allocation = self.packer.insert(width, height)?;
+        let (x, y) = (allocation.x, allocation.y);
+        self.changed = true;
+
+        let padding = self.padding;
+        let stride = self.image.width as usize * 4;
+        for row in -padding..height + padding {
+            for column in -padding..width + padding {
+                let source =
+                    (row.clamp(0, height - 1) * width + column.clamp(0, width - 1)) as usize;
+                let target = (y + row) as usize * stride + (x + column) as usize * 4;
+                self.image.pixels[target..target + 4]
+                    .copy_from_slice(&image.pixels[source * 4..source * 4 + 4]);
+            }
+        }
+
+        Ok(Sprite {
+            source: Rect {
+                x: x as f32,
+                y: y as f32,
+                width: width as f32,
+                height: height as f32,
+            },
+            allocation,
+        })
+    }
+
+    /// Frees the room of a sprite, which must not be drawn anymore.
+    pub fn remove(&mut self, sprite: Sprite) {
+        let Allocation {
+            x,
+            y,
+            width,
+            height,
+        } = sprite.allocation;
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.packer.free(sprite.allocation);
+        self.changed = true;
+
+        let padding = self.padding;
+        let stride = self.image.width as usize * 4;
+        for row in y - padding..y + height + padding {
+            let start = row as usize * stride + (x - padding) as usize * 4;
+            let end = start + (width + padding * 2) as usize * 4;
+            self.image.pixels[start..end]
+                .iter_mut()
+                .for_each(|byte| *byte = 0);
+        }
+    }
+
+    pub fn occupancy(&self) -> Occupancy {
+        self.packer.occupancy()
+    }
+
+    /// Removes every sprite.
+    pub fn clear(&mut self) {
+        self.packer.clear();
+        self.image.pixels.iter_mut().for_each(|byte| *byte = 0);
+        self.changed = true;
+    }
+
+    /// Loads the atlas into `backend`, again if sprites were added since.
+    pub fn upload(&mut self, backend: &mut dyn Backend) -> Result<ImageId, ImageError> {
+        match self.loaded {
+            Some(id) if !self.changed => return Ok(id),
+            Some(id) => backend.remove_image(id),
+            None => {}
+        }
+
+        let id = backend.load_image(&self.image)?;
+        self.loaded = Some(id);
+        self.changed = false;
+
+        Ok(id)
+    }
+}
+
+/// One sprite of a [`SpriteBatch`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteInstance {
+    pub source: Rect,
+    pub destination: Rect,
+    /// Multiplied with the color of every pixel.
+    pub tint: Rgba,
+}
+
+/// Sprites of one atlas, which are drawn in a single draw call.
+#[derive(Debug, Clone)]
+pub struct SpriteBatch {
+    pub image: ImageId,
+    pub filter: Filter,
+    pub sprites: Vec<SpriteInstance>,
+}
+
+impl SpriteBatch {
+    pub fn new(image: ImageId, filter: Filter) -> Self {
+        Self {
+            image,
+            filter,
+            sprites: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, sprite: Sprite, destination: Rect, tint: Rgba) {
+        self.sprites.push(SpriteInstance {
+            source: sprite.source,
+            destination,
+            tint,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: i32, y: i32, width: i32, height: i32) -> Result<Allocation, AtlasError> {
+        Ok(Allocation {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    #[test]
+    fn rows() {
+        let mut packer = RowPacker::new(10, 8, 0);
+
+        assert_eq!(packer.insert(4, 3), at(0, 0, 4, 3));
+        assert_eq!(packer.insert(6, 2), at(4, 0, 6, 2));
+        // The next row starts below the tallest rectangle
+        assert_eq!(packer.insert(1, 1), at(0, 3, 1, 1));
+        assert_eq!(packer.insert(9, 5), at(1, 3, 9, 5));
+        assert_eq!(packer.insert(1, 1), Err(AtlasError::Full));
+        assert_eq!(packer.insert(11, 1), Err(AtlasError::TooLarge));
+
+        packer.clear();
+        assert_eq!(packer.insert(10, 8), at(0, 0, 10, 8));
+    }
+
+    #[test]
+    fn padding() {
+        let mut packer = RowPacker::new(10, 10, 1);
+
+        assert_eq!(packer.insert(3, 3), at(1, 1, 3, 3));
+        assert_eq!(packer.insert(3, 3), at(6, 1, 3, 3));
+        assert_eq!(packer.insert(3, 3), at(1, 6, 3, 3));
+        assert_eq!(packer.insert(9, 1), Err(AtlasError::TooLarge));
+        // Empty rectangles take up no room
+        assert_eq!(packer.insert(0, 3), at(0, 0, 0, 0));
+        assert_eq!(packer.occupancy().allocations, 3);
+    }
+
+    #[test]
+    fn skyline() {
+        let mut packer = SkylinePacker::new(10, 8, 0);
+
+        assert_eq!(packer.insert(4, 3), at(0, 0, 4, 3));
+        assert_eq!(packer.insert(6, 2), at(4, 0, 6, 2));
+        // On the lowest part of the skyline, not in a new row
+        assert_eq!(packer.insert(5, 2), at(4, 2, 5, 2));
+        assert_eq!(packer.insert(1, 2), at(9, 2, 1, 2));
+        assert_eq!(packer.insert(10, 4), at(0, 4, 10, 4));
+        // The gap left under the last rectangle is free
+        assert_eq!(packer.insert(4, 1), at(0, 3, 4, 1));
+        assert_eq!(packer.insert(1, 1), Err(AtlasError::Full));
+        assert_eq!(packer.insert(1, 9), Err(AtlasError::TooLarge));
+    }
+
+    #[test]
+    fn free() {
+        let mut packer = SkylinePacker::new(10, 10, 1);
+        let first = packer.insert(2, 2).unwrap();
+        packer.insert(4, 4).unwrap();
+        assert_eq!(
+            packer.occupancy(),
+            Occupancy {
+                allocations: 2,
+                used: 20,
+                total: 100
+            }
+        );
+
+        // Freed room with nothing above it lowers the skyline again
+        packer.free(first);
+        assert!(packer.free.is_empty());
+        assert_eq!(packer.insert(1, 1), at(1, 1, 1, 1));
+        assert_eq!(packer.insert(1, 1), at(1, 4, 1, 1));
+        assert_eq!(packer.occupancy().used, 18);
+
+        // Neighbouring free regions are joined, and so is room freed below
+        // the skyline once it is lowered onto it
+        let mut packer = SkylinePacker::new(12, 8, 0);
+        let row: Vec<Allocation> = (0..3).map(|_| packer.insert(4, 2).unwrap()).collect();
+        let above = packer.insert(12, 2).unwrap();
+        packer.free(row[0]);
+        packer.free(row[1]);
+        assert_eq!(
+            packer.free,
+            [Region {
+                x: 0,
+                y: 0,
+                width: 8,
+                height: 2
+            }]
+        );
+        packer.free(above);
+        assert!(packer.free.is_empty());
+        assert_eq!(
+            packer.skyline,
+            [
+                Segment {
+                    x: 0,
+                    y: 0,
+                    width: 8
+                },
+                Segment {
+                    x: 8,
+                    y: 2,
+                    width: 4
+                }
+            ]
+        );
+
+        // Freeing everything starts over
+        let mut packer = SkylinePacker::new(8, 8, 1);
+        let first = packer.insert(6, 6).unwrap();
+        assert_eq!(packer.insert(6, 6), Err(AtlasError::Full));
+        packer.free(first);
+        packer.free(Allocation::default());
+        assert_eq!(packer.occupancy().ratio(), 0.);
+        assert_eq!(packer.insert(6, 6), at(1, 1, 6, 6));
+    }
+
+    /// Boxes of CJK glyphs at a few font sizes, which are close to square
+    /// but differ a little in height from one character to the next.
+    fn cjk_workload() -> Vec<(i32, i32)> {
+        let mut seed: u32 = 0x2545_F491;
+        let mut random = move |range: i32| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 16) as i32 % range
+        };
+
+        (0..4000)
+            .map(|_| {
+                let size = [12, 14, 16, 20, 24, 32][random(6) as usize];
+                let width = size - random(size / 6 + 1);
+                let height = size - random(size / 3 + 1);
+                (width, height)
+            })
+            .collect()
+    }
+
+    /// Fills atlases one after another like the glyph atlases of
+    /// `TextRenderer`.
+    fn atlases<P: Packer>(workload: &[(i32, i32)], new: impl Fn() -> P) -> Vec<Occupancy> {
+        let mut atlases = vec![new()];
+        for &(width, height) in workload {
+            let atlas = atlases.last_mut().unwrap();
+            if atlas.insert(width, height) == Err(AtlasError::Full) {
+                let mut atlas = new();
+                atlas.insert(width, height).unwrap();
+                atlases.push(atlas);
+            }
+        }
+
+        atlases.iter().map(Packer::occupancy).collect()
+    }
+
+    #[test]
+    fn cjk_atlases() {
+        let workload = cjk_workload();
+        let rows = atlases(&workload, || RowPacker::new(256, 256, 1));
+        let skyline = atlases(&workload, || SkylinePacker::new(256, 256, 1));
+
+        let full = |atlases: &[Occupancy]| {
+            let full = &atlases[..atlases.len() - 1];
+            full.iter().map(Occupancy::ratio).sum::<f32>() / full.len() as f32
+        };
+        assert!(skyline.len() * 10 <= rows.len() * 9);
+        assert!(full(&skyline) > full(&rows));
+    }
+
+    #[test]
+    fn eviction_churn() {
+        let mut seed: u32 = 7;
+        let mut random = move |range: i32| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 16) as i32 % range
+        };
+
+        // The oldest rectangles are freed until the next one fits, like
+        // glyphs evicted from a `TextRenderer`, but one is always in use
+        let mut packer = SkylinePacker::new(256, 256, 1);
+        packer.insert(10, 10).unwrap();
+        let mut live = std::collections::VecDeque::new();
+        for _ in 0..20_000 {
+            let (width, height) = (8 + random(24), 8 + random(24));
+            loop {
+                if let Ok(allocation) = packer.insert(width, height) {
+                    live.push_back(allocation);
+                    break;
+                }
+                packer.free(live.pop_front().unwrap());
+            }
+            assert!(packer.free.len() < 400);
+        }
+
+        assert!(live.len() > 20);
+    }
+
+    #[test]
+    fn sprites() {
+        let mut atlas = SpriteAtlas::new(8, 4, 1);
+        let red = Image {
+            width: 1,
+            height: 2,
+            pixels: vec![255, 0, 0, 255, 128, 0, 0, 255],
+        };
+        let green = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![0, 255, 0, 255, 0, 128, 0, 128],
+        };
+
+        let sprite = atlas.insert(&red).unwrap();
+        assert_eq!(
+            sprite.source,
+            Rect {
+                x: 1.,
+                y: 1.,
+                width: 1.,
+                height: 2.
+            }
+        );
+        assert_eq!(atlas.insert(&green).unwrap().source.x, 4.);
+        assert_eq!(atlas.insert(&green), Err(AtlasError::Full));
+
+        // Edges repeat into the padding
+        let red_channel = |x: usize, y: usize| atlas.image().pixels[(y * 8 + x) * 4];
+        let column: Vec<u8> = (0..4).map(|y| red_channel(0, y)).collect();
+        assert_eq!(column, [255, 255, 128, 128]);
+        let row: Vec<u8> = (3..7)
+            .map(|x| atlas.image().pixels[(8 + x) * 4 + 3])
+            .collect();
+        assert_eq!(row, [255, 255, 128, 128]);
+        assert_eq!(atlas.image().pixels[7 * 4 + 3], 0);
+
+        // Removed sprites are cleared and their room is reused
+        atlas.remove(sprite);
+        assert_eq!(atlas.image().pixels[8 * 4], 0);
+        assert_eq!(atlas.occupancy().allocations, 1);
+        assert_eq!(atlas.insert(&red), Ok(sprite));
+
+        // Empty images are not copied, short pixels are rejected
+        let empty = Image {
+            width: 3,
+            height: 0,
+            pixels: Vec::new(),
+        };
+        assert_eq!(atlas.insert(&empty).unwrap().source.width, 3.);
+        assert_eq!(atlas.occupancy().allocations, 2);
+        let short = Image {
+            width: 2,
+            height: 2,
+            pixels: vec![0; 12],
+        };
+        assert_eq!(atlas.insert(&short), Err(AtlasError::InvalidImage));
+    }
+}
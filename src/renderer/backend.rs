@@ -0,0 +1,359 @@
+use crate::renderer::atlas::SpriteBatch;
+use crate::renderer::command::{Command, CommandQueue};
+use crate::renderer::decoration::{self, Decoration};
+use crate::renderer::font::{GlyphMode, TextRenderer};
+use crate::renderer::gradient::{Gradient, GradientShape};
+use crate::renderer::image::{Image, ImageDraw, ImageError, ImageId, Texture};
+use crate::renderer::layout::{Align, Layout};
+use crate::renderer::path::{FillRule, Path};
+use crate::renderer::sdf::TextEffects;
+use crate::renderer::shape::{Arc, Ellipse, Pie, RoundedRect};
+use crate::renderer::shaping::FontStyle;
+use crate::renderer::stroke::{self, Stroke};
+use crate::renderer::{RenderRect, Renderer, RendererError, Rgba};
+use crate::vectors::Vec2f;
+
+use crossfont::Error as RasterizerError;
+use fnv::FnvHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+/// The drawing operations shared by the OpenGL and the CPU renderer.
+///
+/// Shapes and text are queued and drawn by the next call to
+/// [`Backend::draw`], in the order they were issued.
+pub trait Backend {
+    fn rectangle(&mut self, rect: &RenderRect);
+
+    fn rounded_rect(&mut self, rect: &RoundedRect);
+
+    fn ellipse(&mut self, ellipse: &Ellipse);
+
+    /// Draws a section of the outline of an ellipse.
+    fn arc(&mut self, arc: &Arc);
+
+    /// Draws a slice of an ellipse.
+    fn pie(&mut self, pie: &Pie);
+
+    fn line(&mut self, from: Vec2f, to: Vec2f, stroke: &Stroke) {
+        self.polyline(&[from, to], false, stroke);
+    }
+
+    /// Draws a line through `points`, back to the first point if `closed`.
+    fn polyline(&mut self, points: &[Vec2f], closed: bool, stroke: &Stroke);
+
+    /// Fills the area inside `path`.
+    fn fill_path(&mut self, path: &Path, rule: FillRule, color: Rgba);
+
+    /// Fills a shape with `gradient` instead of its color.
+    fn gradient(&mut self, shape: &GradientShape, gradient: &Gradient);
+
+    /// Keeps `image` to be drawn until it is removed.
+    fn load_image(&mut self, image: &Image) -> Result<ImageId, ImageError>;
+
+    /// Frees a loaded image, draws of it that are still queued are skipped.
+    fn remove_image(&mut self, image: ImageId);
+
+    fn draw_image(&mut self, image: ImageId, draw: &ImageDraw) -> Result<(), ImageError>;
+
+    /// Draws sprites of a loaded atlas.
+    fn draw_sprites(&mut self, batch: &SpriteBatch) -> Result<(), ImageError>;
+
+    /// Draws the outline of every subpath of `path`.
+    fn stroke_path(&mut self, path: &Path, stroke: &Stroke) {
+        for subpath in &path.subpaths {
+            self.polyline(&subpath.points, subpath.closed, stroke);
+        }
+    }
+
+    /// Draws all queued commands.
+    fn draw(&mut self);
+
+    /// Adapts to a new screen size in pixels and scale factor.
+    fn resize(&mut self, size: Vec2f, scale_factor: f64);
+
+    /// Appends a font to the fallback chain, characters are drawn with the
+    /// first font of the chain that has a glyph for them.
+    fn add_fallback(&mut self, family: &str) -> Result<(), RasterizerError>;
+
+    /// Wraps `string` into lines no wider than `max_width`.
+    fn layout(&mut self, string: &str, max_width: f32, align: Align, style: FontStyle) -> Layout {
+        let font_size = self.font_size();
+        self.layout_sized(string, font_size, max_width, align, style)
+    }
+
+    /// Like [`Backend::layout`] at `font_size` points, text of every size can
+    /// be drawn by the same backend.
+    fn layout_sized(
+        &mut self,
+        string: &str,
+        font_size: f32,
+        max_width: f32,
+        align: Align,
+        style: FontStyle,
+    ) -> Layout;
+
+    /// The size text is laid out at unless another one is given, in points.
+    fn font_size(&self) -> f32;
+
+    /// Changes the size text is laid out at by default, to zoom text.
+    fn set_font_size(&mut self, font_size: f32);
+
+    fn glyph_mode(&self) -> GlyphMode;
+
+    /// Changes how the glyphs queued next are rasterized and drawn.
+    fn set_glyph_mode(&mut self, mode: GlyphMode);
+
+    /// Queues a layout with the top of its first line at `y`.
+    fn draw_layout(&mut self, layout: &Layout, x: i16, y: i16, color: Rgba) {
+        self.draw_layout_with_effects(layout, x, y, color, &TextEffects::default());
+    }
+
+    /// Queues a layout with `effects` around its glyphs. Effects are drawn
+    /// from distance fields, so glyphs with effects are drawn from distance
+    /// fields in every mode.
+    fn draw_layout_with_effects(
+        &mut self,
+        layout: &Layout,
+        x: i16,
+        y: i16,
+        color: Rgba,
+        effects: &TextEffects,
+    );
+
+    /// Queues a layout with lines along its text, which are drawn right after
+    /// the glyphs.
+    fn draw_decorated(
+        &mut self,
+        layout: &Layout,
+        x: i16,
+        y: i16,
+        color: Rgba,
+        decoration: &Decoration,
+    ) {
+        self.draw_layout(layout, x, y, color);
+        for line in decoration::decoration_lines(layout, x, y, color, decoration) {
+            self.polyline(&line.points, false, &line.stroke);
+        }
+    }
+
+    /// Draws `string`, lines only end at line breaks like `'\n'`.
+    fn draw_string(&mut self, string: &str, x: i16, y: i16, color: Rgba, style: FontStyle) {
+        let layout = self.layout(string, f32::INFINITY, Align::Left, style);
+        self.draw_layout(&layout, x, y, color);
+    }
+
+    /// Width of the widest line of `string`.
+    fn get_length(&mut self, string: &str, style: FontStyle) -> i16 {
+        self.layout(string, f32::INFINITY, Align::Left, style)
+            .width
+            .round() as i16
+    }
+
+    fn get_height(&self) -> i16;
+}
+
+/// [`Renderer`] and [`TextRenderer`] on the current OpenGL context.
+pub struct GlBackend {
+    pub renderer: Renderer,
+    pub text: TextRenderer,
+    queue: CommandQueue,
+    images: HashMap<ImageId, Texture, BuildHasherDefault<FnvHasher>>,
+    next_image: u32,
+}
+
+impl GlBackend {
+    pub fn new(
+        font: &str,
+        font_size: f32,
+        screen_size: Vec2f,
+        dpr: f64,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            renderer: Renderer::new(screen_size)?,
+            text: TextRenderer::new(font, font_size, screen_size, dpr)?,
+            queue: CommandQueue::default(),
+            images: HashMap::default(),
+            next_image: 0,
+        })
+    }
+}
+
+impl Backend for GlBackend {
+    fn rectangle(&mut self, rect: &RenderRect) {
+        self.queue.push(Command::Rect(rect.clone()));
+    }
+
+    fn rounded_rect(&mut self, rect: &RoundedRect) {
+        self.queue.push(Command::RoundedRect(rect.clone()));
+    }
+
+    fn ellipse(&mut self, ellipse: &Ellipse) {
+        if let Some(curve) = ellipse.curve() {
+            self.queue.push(Command::Curve(curve));
+        }
+    }
+
+    fn arc(&mut self, arc: &Arc) {
+        if let Some(curve) = arc.curve() {
+            self.queue.push(Command::Curve(curve));
+        }
+    }
+
+    fn pie(&mut self, pie: &Pie) {
+        if let Some(curve) = pie.curve() {
+            self.queue.push(Command::Curve(curve));
+        }
+    }
+
+    fn polyline(&mut self, points: &[Vec2f], closed: bool, stroke: &Stroke) {
+        let mesh = stroke::tessellate(points, closed, stroke);
+        self.queue.push(Command::Mesh(mesh));
+    }
+
+    fn fill_path(&mut self, path: &Path, rule: FillRule, color: Rgba) {
+        self.queue.push(Command::Fill {
+            path: path.clone(),
+            rule,
+            color,
+        });
+    }
+
+    fn gradient(&mut self, shape: &GradientShape, gradient: &Gradient) {
+        self.queue.push(Command::Gradient {
+            shape: shape.clone(),
+            gradient: gradient.clone(),
+        });
+    }
+
+    fn load_image(&mut self, image: &Image) -> Result<ImageId, ImageError> {
+        let texture = Texture::new(image)?;
+        let id = ImageId(self.next_image);
+        self.next_image += 1;
+        self.images.insert(id, texture);
+
+        Ok(id)
+    }
+
+    fn remove_image(&mut self, image: ImageId) {
+        self.images.remove(&image);
+    }
+
+    fn draw_image(&mut self, image: ImageId, draw: &ImageDraw) -> Result<(), ImageError> {
+        if !self.images.contains_key(&image) {
+            return Err(ImageError::NotLoaded(image));
+        }
+
+        self.queue.push(Command::Image { image, draw: *draw });
+        Ok(())
+    }
+
+    fn draw_sprites(&mut self, batch: &SpriteBatch) -> Result<(), ImageError> {
+        if !self.images.contains_key(&batch.image) {
+            return Err(ImageError::NotLoaded(batch.image));
+        }
+
+        self.queue.push(Command::Sprites(batch.clone()));
+        Ok(())
+    }
+
+    /// Every run of neighbouring shapes or texts is drawn as one batch.
+    fn draw(&mut self) {
+        for batch in self.queue.take_batches() {
+            for command in batch {
+                match command {
+                    Command::Rect(rect) => self.renderer.rectangle(&rect),
+                    Command::RoundedRect(rect) => self.renderer.rounded_rect(&rect),
+                    Command::Curve(curve) => self.renderer.curve(&curve),
+                    Command::Mesh(mesh) => self.renderer.mesh(&mesh),
+                    Command::Fill { path, rule, color } => {
+                        self.renderer.fill_path(&path, rule, color)
+                    }
+                    Command::Gradient { shape, gradient } => {
+                        self.renderer.gradient(&shape, &gradient)
+                    }
+                    Command::Image { image, draw } => {
+                        if let Some(texture) = self.images.get(&image) {
+                            self.renderer.image(texture, &draw);
+                        }
+                    }
+                    Command::Sprites(batch) => {
+                        if let Some(texture) = self.images.get(&batch.image) {
+                            self.renderer.sprites(texture, &batch);
+                        }
+                    }
+                    Command::Text {
+                        layout,
+                        x,
+                        y,
+                        color,
+                        mode,
+                        effects,
+                    } => self.text.draw_layout_in(layout, x, y, color, mode, effects),
+                }
+            }
+
+            self.renderer.draw();
+            self.text.flush();
+        }
+    }
+
+    fn resize(&mut self, size: Vec2f, scale_factor: f64) {
+        self.renderer.resize(size);
+        self.text.resize(size, scale_factor);
+    }
+
+    fn add_fallback(&mut self, family: &str) -> Result<(), RasterizerError> {
+        self.text.add_fallback(family)
+    }
+
+    fn layout_sized(
+        &mut self,
+        string: &str,
+        font_size: f32,
+        max_width: f32,
+        align: Align,
+        style: FontStyle,
+    ) -> Layout {
+        self.text
+            .layout_sized(string, font_size, max_width, align, style)
+    }
+
+    fn font_size(&self) -> f32 {
+        self.text.font_size()
+    }
+
+    fn set_font_size(&mut self, font_size: f32) {
+        self.text.set_font_size(font_size);
+    }
+
+    fn glyph_mode(&self) -> GlyphMode {
+        self.text.glyph_mode()
+    }
+
+    fn set_glyph_mode(&mut self, mode: GlyphMode) {
+        self.text.set_glyph_mode(mode);
+    }
+
+    fn draw_layout_with_effects(
+        &mut self,
+        layout: &Layout,
+        x: i16,
+        y: i16,
+        color: Rgba,
+        effects: &TextEffects,
+    ) {
+        self.queue.push(Command::Text {
+            layout: layout.clone(),
+            x,
+            y,
+            color,
+            mode: self.text.glyph_mode(),
+            effects: *effects,
+        });
+    }
+
+    fn get_height(&self) -> i16 {
+        self.text.get_height()
+    }
+}
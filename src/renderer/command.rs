@@ -0,0 +1,158 @@
+use crate::renderer::atlas::SpriteBatch;
+use crate::renderer::font::GlyphMode;
+use crate::renderer::gradient::{Gradient, GradientShape};
+use crate::renderer::image::{ImageDraw, ImageId};
+use crate::renderer::layout::Layout;
+use crate::renderer::mesh::Mesh;
+use crate::renderer::path::{FillRule, Path};
+use crate::renderer::sdf::TextEffects;
+use crate::renderer::shape::{Curve, RoundedRect};
+use crate::renderer::{RenderRect, Rgba};
+
+use std::mem;
+
+/// A queued draw call.
+#[derive(Debug, Clone)]
+pub enum Command {
+    Rect(RenderRect),
+    RoundedRect(RoundedRect),
+    Curve(Curve),
+    Mesh(Mesh),
+    Fill {
+        path: Path,
+        rule: FillRule,
+        color: Rgba,
+    },
+    Gradient {
+        shape: GradientShape,
+        gradient: Gradient,
+    },
+    Image {
+        image: ImageId,
+        draw: ImageDraw,
+    },
+    Sprites(SpriteBatch),
+    Text {
+        layout: Layout,
+        x: i16,
+        y: i16,
+        color: Rgba,
+        mode: GlyphMode,
+        effects: TextEffects,
+    },
+}
+
+/// The GPU state a command is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pipeline {
+    /// The vertices of `Renderer`.
+    Shapes,
+    /// The glyph instances of `TextRenderer`.
+    Text,
+}
+
+impl Command {
+    pub fn pipeline(&self) -> Pipeline {
+        match self {
+            Command::Rect(_)
+            | Command::RoundedRect(_)
+            | Command::Curve(_)
+            | Command::Mesh(_)
+            | Command::Fill { .. }
+            | Command::Gradient { .. }
+            | Command::Image { .. }
+            | Command::Sprites(_) => Pipeline::Shapes,
+            Command::Text { .. } => Pipeline::Text,
+        }
+    }
+}
+
+/// Draw calls in the order they were issued.
+///
+/// Neighbouring commands of the same pipeline share their GPU state, so they
+/// can be submitted as one batch.
+#[derive(Debug, Default)]
+pub struct CommandQueue {
+    commands: Vec<Command>,
+}
+
+impl CommandQueue {
+    pub fn push(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    /// Takes the queued commands, split into runs of the same pipeline.
+    pub fn take_batches(&mut self) -> Vec<Vec<Command>> {
+        let mut batches: Vec<Vec<Command>> = Vec::new();
+
+        for command in mem::take(&mut self.commands) {
+            match batches.last_mut() {
+                Some(batch) if batch[0].pipeline() == command.pipeline() => batch.push(command),
+                _ => batches.push(vec![command]),
+            }
+        }
+
+        batches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::shape::CornerRadii;
+    use crate::renderer::Rgb;
+
+    #[test]
+    fn batches_keep_order() {
+        let rect = Command::Rect(RenderRect {
+            x: 0.,
+            y: 0.,
+            width: 1.,
+            height: 1.,
+            color: Rgb::from_hex(0x000000).into(),
+        });
+        let rounded = Command::RoundedRect(RoundedRect {
+            x: 0.,
+            y: 0.,
+            width: 1.,
+            height: 1.,
+            radii: CornerRadii::uniform(0.5),
+            color: Rgb::from_hex(0x000000).into(),
+            border: None,
+        });
+        let text = Command::Text {
+            layout: Layout::default(),
+            x: 0,
+            y: 0,
+            color: Rgb::from_hex(0x000000).into(),
+            mode: GlyphMode::Bitmap,
+            effects: TextEffects::default(),
+        };
+
+        let mut queue = CommandQueue::default();
+        for command in [&rect, &rounded, &text, &rect, &text, &text] {
+            queue.push(command.clone());
+        }
+
+        let batches: Vec<Vec<bool>> = queue
+            .take_batches()
+            .iter()
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|command| command.pipeline() == Pipeline::Shapes)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            batches,
+            [
+                vec![true, true],
+                vec![false],
+                vec![true],
+                vec![false, false]
+            ]
+        );
+        assert!(queue.take_batches().is_empty());
+    }
+}
@@ -0,0 +1,1558 @@
+use crate::renderer::atlas::SpriteBatch;
+use crate::renderer::backend::Backend;
+use crate::renderer::command::{Command, CommandQueue};
+use crate::renderer::font::{cell_height, GlyphMode};
+use crate::renderer::framebuffer::write_png;
+use crate::renderer::gradient::{Gradient, GradientShape};
+use crate::renderer::image::{Image, ImageDraw, ImageError, ImageId};
+use crate::renderer::layout::{Align, Layout};
+use crate::renderer::mesh::{self, Mesh, MeshVertex};
+use crate::renderer::path::{FillRule, Path};
+use crate::renderer::sdf::{self, Pass, TextEffects, FIELD_SIZE};
+use crate::renderer::shape::{
+    rounded_rect_distance, shade, Arc, Border, Curve, Ellipse, Pie, RoundedRect,
+};
+use crate::renderer::shaping::{FontStyle, ShapedKey};
+use crate::renderer::stroke::{self, Stroke};
+use crate::renderer::text::TextState;
+use crate::renderer::{Rect, RenderRect, RendererError, Rgba};
+use crate::vectors::Vec2f;
+
+use fnv::FnvHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::ops::Range;
+use std::path;
+
+use crossfont::{BitmapBuffer, Error as RasterizerError, RasterizedGlyph, Size};
+
+/// Draws into an RGBA pixel buffer without OpenGL.
+///
+/// Rectangles and glyphs are placed and blended the same way as by
+/// `Renderer` and `TextRenderer`, so both produce (almost) the same pixels.
+pub struct CpuBackend {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    queue: CommandQueue,
+    text: TextState,
+    cache: HashMap<ShapedKey, RasterizedGlyph, BuildHasherDefault<FnvHasher>>,
+    /// Distance fields at `FIELD_SIZE`, scaled to every size.
+    fields: HashMap<ShapedKey, RasterizedGlyph, BuildHasherDefault<FnvHasher>>,
+    /// Glyphs drawn from their fields in passes once the text is batched.
+    queued_fields: Vec<QueuedField>,
+    mode: GlyphMode,
+    /// Loaded images with premultiplied alpha, like the textures on the GPU.
+    images: HashMap<ImageId, Image, BuildHasherDefault<FnvHasher>>,
+    next_image: u32,
+}
+
+impl CpuBackend {
+    pub fn new(
+        font: &str,
+        font_size: f32,
+        width: usize,
+        height: usize,
+        dpr: f64,
+    ) -> Result<Self, RendererError> {
+        Ok(Self {
+            width,
+            height,
+            pixels: black(width, height),
+            queue: CommandQueue::default(),
+            text: TextState::new(font, font_size, dpr)?,
+            cache: HashMap::default(),
+            fields: HashMap::default(),
+            queued_fields: Vec::new(),
+            mode: GlyphMode::Bitmap,
+            images: HashMap::default(),
+            next_image: 0,
+        })
+    }
+
+    /// The RGBA pixels, top row first.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn clear(&mut self, r: u8, g: u8, b: u8) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+
+    pub fn save_png<P: AsRef<path::Path>>(&self, path: P) -> Result<(), png::EncodingError> {
+        write_png(path, self.width as u32, self.height as u32, &self.pixels)
+    }
+
+    fn get_glyph(&mut self, key: ShapedKey) -> &RasterizedGlyph {
+        if !self.cache.contains_key(&key) {
+            let rasterized = match self.text.shaper.rasterize(key) {
+                Ok(rasterized) => rasterized,
+                Err(RasterizerError::MissingGlyph(rasterized)) => rasterized,
+                Err(_) => Default::default(),
+            };
+            self.cache.insert(key, rasterized);
+        }
+
+        &self.cache[&key]
+    }
+
+    fn get_field(&mut self, key: ShapedKey) -> &RasterizedGlyph {
+        if !self.fields.contains_key(&key) {
+            let field = match sdf::rasterize(&mut self.text.shaper, key) {
+                Ok(field) | Err(RasterizerError::MissingGlyph(field)) => field,
+                Err(_) => Default::default(),
+            };
+            self.fields.insert(key, field);
+        }
+
+        &self.fields[&key]
+    }
+
+    fn fill_rect(&mut self, rect: &RenderRect, paint: Paint) {
+        for y in centers(rect.y, rect.height, self.height) {
+            for x in centers(rect.x, rect.width, self.width) {
+                let color = paint.at(x as f32 + 0.5, y as f32 + 0.5);
+                self.blend_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Mirrors the `IMAGE` shape of `base.frag` for an image with
+    /// premultiplied alpha.
+    fn fill_image(&mut self, image: &Image, draw: &ImageDraw) {
+        let source = draw.source_rect(image.width, image.height);
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = draw.destination;
+        let tint =
+            [draw.tint.r, draw.tint.g, draw.tint.b, draw.tint.a].map(|c| f32::from(c) / 255.);
+
+        for py in centers(y, height, self.height) {
+            for px in centers(x, width, self.width) {
+                let u = source.x + source.width * ((px as f32 + 0.5 - x) / width);
+                let v = source.y + source.height * ((py as f32 + 0.5 - y) / height);
+                let texel =
+                    image.sample(u / image.width as f32, v / image.height as f32, draw.filter);
+
+                let alpha = texel[3];
+                let channel = |i: usize| {
+                    let straight = if alpha > 0. { texel[i] / alpha } else { 0. };
+                    (straight * tint[i] * 255.).round().min(255.) as u8
+                };
+                let color = Rgba {
+                    r: channel(0),
+                    g: channel(1),
+                    b: channel(2),
+                    a: (alpha * tint[3] * 255.).round() as u8,
+                };
+                self.blend_pixel(px, py, color);
+            }
+        }
+    }
+
+    fn fill_rounded_rect(&mut self, rect: &RoundedRect, paint: Paint) {
+        let radii = rect.radii.clamp(rect.width, rect.height);
+        let (half_width, half_height) = (rect.width / 2., rect.height / 2.);
+
+        self.fill_distance(
+            [rect.x, rect.y, rect.width, rect.height],
+            paint,
+            rect.border,
+            |x, y| rounded_rect_distance(x, y, half_width, half_height, radii),
+        );
+    }
+
+    fn fill_curve(&mut self, curve: &Curve) {
+        let bounds = [
+            curve.x - curve.radius_x,
+            curve.y - curve.radius_y,
+            curve.radius_x * 2.,
+            curve.radius_y * 2.,
+        ];
+
+        let paint = Paint::Solid(curve.color);
+        self.fill_distance(bounds, paint, None, |x, y| curve.distance(x, y));
+    }
+
+    fn fill_mesh(&mut self, mesh: &Mesh, paint: Paint) {
+        for triangle in mesh.vertices.chunks_exact(3) {
+            self.fill_triangle(triangle, |vertex| {
+                let coverage = mesh::coverage(vertex.across, vertex.edge, mesh.half_width);
+                let color = paint.at(vertex.x, vertex.y);
+                Rgba {
+                    a: (f32::from(color.a) * coverage).round() as u8,
+                    ..color
+                }
+            });
+        }
+    }
+
+    /// Shades the pixel centers inside a triangle with its vertices
+    /// interpolated at them.
+    ///
+    /// Positions are snapped to the 1/256 pixel grid of the GL rasterizer and
+    /// centers on an edge belong to the triangle on its top or left, so
+    /// neighbouring triangles never blend the same pixel twice.
+    fn fill_triangle(&mut self, triangle: &[MeshVertex], shade: impl Fn(MeshVertex) -> Rgba) {
+        const SUBPIXELS: f32 = 256.;
+        let snap = |vertex: &MeshVertex| {
+            (
+                (vertex.x * SUBPIXELS).round() as i64,
+                (vertex.y * SUBPIXELS).round() as i64,
+            )
+        };
+
+        let mut corners = [snap(&triangle[0]), snap(&triangle[1]), snap(&triangle[2])];
+        let mut vertices = [triangle[0], triangle[1], triangle[2]];
+        let orient = |a: (i64, i64), b: (i64, i64), c: (i64, i64)| {
+            (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+        };
+
+        let area = orient(corners[0], corners[1], corners[2]);
+        if area == 0 {
+            return;
+        }
+        // Clockwise on the screen, so inside is positive for every edge
+        if area < 0 {
+            corners.swap(1, 2);
+            vertices.swap(1, 2);
+        }
+        let area = area.abs() as f32;
+
+        // Edges facing up or left own the pixel centers on them
+        let owns = |from: (i64, i64), to: (i64, i64)| {
+            let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+            (dy == 0 && dx > 0) || dy < 0
+        };
+        let edges = [(1, 2), (2, 0), (0, 1)];
+
+        let bounds = |axis: fn(&(i64, i64)) -> i64, max: usize| {
+            let low = corners.iter().map(axis).min().unwrap() as f32 / SUBPIXELS;
+            let high = corners.iter().map(axis).max().unwrap() as f32 / SUBPIXELS;
+            let from = (low - 0.5).floor().max(0.) as usize;
+            let to = ((high + 0.5).ceil().max(0.) as usize).min(max);
+            from..to
+        };
+
+        for y in bounds(|corner| corner.1, self.height) {
+            for x in bounds(|corner| corner.0, self.width) {
+                let center = (x as i64 * 256 + 128, y as i64 * 256 + 128);
+
+                let mut weights = [0.; 3];
+                let mut inside = true;
+                for (i, (from, to)) in edges.iter().enumerate() {
+                    let weight = orient(corners[*from], corners[*to], center);
+                    inside &= weight > 0 || (weight == 0 && owns(corners[*from], corners[*to]));
+                    weights[i] = weight as f32 / area;
+                }
+                if !inside {
+                    continue;
+                }
+
+                let interpolate = |value: fn(&MeshVertex) -> f32| {
+                    (0..3).map(|i| value(&vertices[i]) * weights[i]).sum()
+                };
+                let vertex = MeshVertex {
+                    x: x as f32 + 0.5,
+                    y: y as f32 + 0.5,
+                    across: interpolate(|vertex| vertex.across),
+                    edge: interpolate(|vertex| vertex.edge),
+                };
+
+                self.blend_pixel(x, y, shade(vertex));
+            }
+        }
+    }
+
+    /// Shades the pixels around `bounds` by their signed distance to the edge
+    /// of a shape, `distance` takes positions relative to the center.
+    fn fill_distance(
+        &mut self,
+        [x, y, width, height]: [f32; 4],
+        paint: Paint,
+        border: Option<Border>,
+        distance: impl Fn(f32, f32) -> f32,
+    ) {
+        let (center_x, center_y) = (x + width / 2., y + height / 2.);
+
+        // One more pixel around the shape for its anti-aliased edge
+        let span = |start: f32, length: f32, max: usize| {
+            let from = (start - 1.).floor().max(0.) as usize;
+            let to = ((start + length + 1.).ceil().max(0.) as usize).min(max);
+            from..to
+        };
+
+        for py in span(y, height, self.height) {
+            for px in span(x, width, self.width) {
+                let (x, y) = (px as f32 + 0.5, py as f32 + 0.5);
+                let color = paint.at(x, y);
+                self.blend_pixel(
+                    px,
+                    py,
+                    shade(distance(x - center_x, y - center_y), color, border),
+                );
+            }
+        }
+    }
+
+    /// Blends a straight alpha color over a pixel, like `Renderer` does.
+    fn blend_pixel(&mut self, x: usize, y: usize, color: Rgba) {
+        let alpha = f32::from(color.a) / 255.;
+        let index = (y * self.width + x) * 4;
+        let pixel = &mut self.pixels[index..index + 4];
+
+        for (pixel, color) in pixel.iter_mut().zip([color.r, color.g, color.b, 255]) {
+            *pixel = mix(*pixel, color, alpha);
+        }
+    }
+
+    fn blend_layout(&mut self, layout: &Layout, t_x: i16, t_y: i16, color: Rgba) {
+        for line in &layout.lines {
+            let line_top = f32::from(t_y + line.y.round() as i16);
+
+            for positioned in &line.glyphs {
+                let baseline = line_top + cell_height(positioned.key.size);
+                let glyph = self.get_glyph(positioned.key).clone();
+
+                let x = i32::from(t_x) + positioned.x.round() as i32 + glyph.left;
+                let y = (baseline - positioned.y.round()) as i32 - glyph.top;
+                self.blend_glyph(&glyph, x, y, color);
+            }
+        }
+    }
+
+    /// Queues the glyphs of a layout to be drawn from their distance fields,
+    /// placed like by `TextRenderer`.
+    fn queue_layout_fields(
+        &mut self,
+        layout: &Layout,
+        t_x: i16,
+        t_y: i16,
+        color: Rgba,
+        effects: &TextEffects,
+    ) {
+        let field_size = Size::new(FIELD_SIZE);
+
+        for line in &layout.lines {
+            let line_top = f32::from(t_y + line.y.round() as i16);
+
+            for positioned in &line.glyphs {
+                let key = ShapedKey {
+                    size: field_size,
+                    ..positioned.key
+                };
+                let field = self.get_field(key).clone();
+                if field.width == 0 || field.height == 0 {
+                    continue;
+                }
+
+                let scale = positioned.key.size.as_f32_pts() / FIELD_SIZE;
+                let x = f32::from(t_x) + positioned.x + field.left as f32 * scale;
+                let baseline = line_top + cell_height(positioned.key.size) - positioned.y;
+                let y = baseline - field.top as f32 * scale;
+                self.queued_fields.push(QueuedField {
+                    field,
+                    x,
+                    y,
+                    scale,
+                    color,
+                    effects: *effects,
+                });
+            }
+        }
+    }
+
+    /// Draws the queued fields like `FieldPipeline`, every layer over all
+    /// of them before the next one.
+    fn blend_queued_fields(&mut self) {
+        let queued = std::mem::take(&mut self.queued_fields);
+        for pass in Pass::ALL {
+            for glyph in queued.iter().filter(|glyph| pass.draws(&glyph.effects)) {
+                self.blend_field(glyph, pass);
+            }
+        }
+    }
+
+    /// Mirrors `sdf.vert` and `sdf.frag` for one pass over a queued field.
+    fn blend_field(&mut self, glyph: &QueuedField, pass: Pass) {
+        let QueuedField {
+            ref field,
+            x,
+            y,
+            scale,
+            color,
+            ref effects,
+        } = *glyph;
+        let (width, height) = (field.width as f32 * scale, field.height as f32 * scale);
+        // Grown to reach the shadow
+        let offset = effects
+            .shadow
+            .map_or(Vec2f::new(0., 0.), |shadow| shadow.offset);
+        let (grow_x, grow_y) = (offset.x.abs(), offset.y.abs());
+
+        let rows = centers(y - grow_y, height + grow_y * 2., self.height);
+        for py in rows {
+            for px in centers(x - grow_x, width + grow_x * 2., self.width) {
+                let field_x = (px as f32 + 0.5 - x) / scale;
+                let field_y = (py as f32 + 0.5 - y) / scale;
+                let inside = sdf::field_distance(field, field_x, field_y) * scale;
+                let shadow = sdf::field_distance(
+                    field,
+                    field_x - offset.x / scale,
+                    field_y - offset.y / scale,
+                ) * scale;
+
+                let shaded = sdf::shade(pass, inside, shadow, color, effects);
+                let alpha = shaded[3];
+                if alpha <= 0. {
+                    continue;
+                }
+                let channel = |i: usize| (shaded[i] / alpha * 255.).round().min(255.) as u8;
+                let color = Rgba {
+                    r: channel(0),
+                    g: channel(1),
+                    b: channel(2),
+                    a: (alpha * 255.).round() as u8,
+                };
+                self.blend_pixel(px, py, color);
+            }
+        }
+    }
+
+    fn blend_glyph(&mut self, glyph: &RasterizedGlyph, x: i32, y: i32, color: Rgba) {
+        let opacity = f32::from(color.a) / 255.;
+        let color = [color.r, color.g, color.b];
+        let (bytes_per_pixel, buffer) = match &glyph.buffer {
+            BitmapBuffer::Rgb(buffer) => (3, buffer),
+            BitmapBuffer::Rgba(buffer) => (4, buffer),
+        };
+
+        for row in 0..glyph.height {
+            let py = y + row;
+            if py < 0 || py >= self.height as i32 {
+                continue;
+            }
+
+            for column in 0..glyph.width {
+                let px = x + column;
+                if px < 0 || px >= self.width as i32 {
+                    continue;
+                }
+
+                let source = ((row * glyph.width + column) * bytes_per_pixel) as usize;
+                let texel = &buffer[source..source + bytes_per_pixel as usize];
+                let index = (py as usize * self.width + px as usize) * 4;
+                let pixel = &mut self.pixels[index..index + 4];
+
+                // Mirrors the dual source blending of `text.frag`
+                let coverage = |value: u8| f32::from(value) / 255. * opacity;
+                if bytes_per_pixel == 3 {
+                    for channel in 0..3 {
+                        let factor = coverage(texel[channel]);
+                        pixel[channel] = mix(pixel[channel], color[channel], factor);
+                    }
+                    pixel[3] = mix(pixel[3], 255, coverage(texel[0]));
+                } else {
+                    let alpha = texel[3];
+                    for channel in 0..3 {
+                        let straight = if alpha == 0 {
+                            texel[channel]
+                        } else {
+                            (u32::from(texel[channel]) * 255 / u32::from(alpha)).min(255) as u8
+                        };
+                        pixel[channel] = mix(pixel[channel], straight, coverage(alpha));
+                    }
+                    pixel[3] = mix(pixel[3], 255, coverage(alpha));
+                }
+            }
+        }
+    }
+}
+
+/// A glyph drawn from its distance field, scaled by `scale` with its top
+/// left corner at `x` and `y`.
+struct QueuedField {
+    field: RasterizedGlyph,
+    x: f32,
+    y: f32,
+    scale: f32,
+    color: Rgba,
+    effects: TextEffects,
+}
+
+/// The color of the pixels of a shape.
+#[derive(Clone, Copy)]
+enum Paint<'a> {
+    Solid(Rgba),
+    /// A gradient and its ramp.
+    Gradient(&'a Gradient, &'a [Rgba]),
+}
+
+impl Paint<'_> {
+    /// The color of the pixel whose center is at `x` and `y`.
+    fn at(self, x: f32, y: f32) -> Rgba {
+        match self {
+            Paint::Solid(color) => color,
+            Paint::Gradient(gradient, ramp) => gradient.sample(ramp, Vec2f::new(x, y)),
+        }
+    }
+}
+
+/// The pixels whose centers are inside a span of a rectangle, just like in GL.
+fn centers(start: f32, length: f32, max: usize) -> Range<usize> {
+    let from = (start - 0.5).ceil().max(0.) as usize;
+    let to = ((start + length - 0.5).ceil().max(0.) as usize).min(max);
+    from..to
+}
+
+/// Opaque black RGBA pixels.
+fn black(width: usize, height: usize) -> Vec<u8> {
+    let mut pixels = vec![0; width * height * 4];
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = 255;
+    }
+
+    pixels
+}
+
+/// `dst * (1 - factor) + src * factor`, with a factor from 0 to 1.
+fn mix(dst: u8, src: u8, factor: f32) -> u8 {
+    (f32::from(dst) * (1. - factor) + f32::from(src) * factor).round() as u8
+}
+
+impl Backend for CpuBackend {
+    fn rectangle(&mut self, rect: &RenderRect) {
+        self.queue.push(Command::Rect(rect.clone()));
+    }
+
+    fn rounded_rect(&mut self, rect: &RoundedRect) {
+        self.queue.push(Command::RoundedRect(rect.clone()));
+    }
+
+    fn ellipse(&mut self, ellipse: &Ellipse) {
+        if let Some(curve) = ellipse.curve() {
+            self.queue.push(Command::Curve(curve));
+        }
+    }
+
+    fn arc(&mut self, arc: &Arc) {
+        if let Some(curve) = arc.curve() {
+            self.queue.push(Command::Curve(curve));
+        }
+    }
+
+    fn pie(&mut self, pie: &Pie) {
+        if let Some(curve) = pie.curve() {
+            self.queue.push(Command::Curve(curve));
+        }
+    }
+
+    fn polyline(&mut self, points: &[Vec2f], closed: bool, stroke: &Stroke) {
+        let mesh = stroke::tessellate(points, closed, stroke);
+        self.queue.push(Command::Mesh(mesh));
+    }
+
+    fn fill_path(&mut self, path: &Path, rule: FillRule, color: Rgba) {
+        self.queue.push(Command::Fill {
+            path: path.clone(),
+            rule,
+            color,
+        });
+    }
+
+    fn gradient(&mut self, shape: &GradientShape, gradient: &Gradient) {
+        self.queue.push(Command::Gradient {
+            shape: shape.clone(),
+            gradient: gradient.clone(),
+        });
+    }
+
+    fn load_image(&mut self, image: &Image) -> Result<ImageId, ImageError> {
+        image.check_size()?;
+
+        let id = ImageId(self.next_image);
+        self.next_image += 1;
+        self.images.insert(id, image.premultiplied());
+
+        Ok(id)
+    }
+
+    fn remove_image(&mut self, image: ImageId) {
+        self.images.remove(&image);
+    }
+
+    fn draw_image(&mut self, image: ImageId, draw: &ImageDraw) -> Result<(), ImageError> {
+        if !self.images.contains_key(&image) {
+            return Err(ImageError::NotLoaded(image));
+        }
+
+        self.queue.push(Command::Image { image, draw: *draw });
+        Ok(())
+    }
+
+    fn draw_sprites(&mut self, batch: &SpriteBatch) -> Result<(), ImageError> {
+        if !self.images.contains_key(&batch.image) {
+            return Err(ImageError::NotLoaded(batch.image));
+        }
+
+        self.queue.push(Command::Sprites(batch.clone()));
+        Ok(())
+    }
+
+    fn draw(&mut self) {
+        for batch in self.queue.take_batches() {
+            for command in batch {
+                match command {
+                    Command::Rect(rect) => self.fill_rect(&rect, Paint::Solid(rect.color)),
+                    Command::RoundedRect(rect) => {
+                        self.fill_rounded_rect(&rect, Paint::Solid(rect.color))
+                    }
+                    Command::Curve(curve) => self.fill_curve(&curve),
+                    Command::Mesh(mesh) => self.fill_mesh(&mesh, Paint::Solid(mesh.color)),
+                    Command::Fill { path, rule, color } => {
+                        let (width, height) = (self.width as f32, self.height as f32);
+                        let mesh = path.fill(rule, color, width, height);
+                        self.fill_mesh(&mesh, Paint::Solid(color));
+                    }
+                    Command::Gradient { shape, gradient } => {
+                        let ramp = gradient.ramp();
+                        let paint = Paint::Gradient(&gradient, &ramp);
+                        match shape {
+                            GradientShape::Rect(rect) => self.fill_rect(&rect, paint),
+                            GradientShape::RoundedRect(rect) => {
+                                self.fill_rounded_rect(&rect, paint)
+                            }
+                            GradientShape::Path { path, rule } => {
+                                let (width, height) = (self.width as f32, self.height as f32);
+                                let mesh = path.fill(rule, Rgba::default(), width, height);
+                                self.fill_mesh(&mesh, paint);
+                            }
+                        }
+                    }
+                    Command::Image { image: id, draw } => {
+                        // Taken out while the pixels are borrowed mutably
+                        if let Some(image) = self.images.remove(&id) {
+                            self.fill_image(&image, &draw);
+                            self.images.insert(id, image);
+                        }
+                    }
+                    Command::Sprites(batch) => {
+                        if let Some(image) = self.images.remove(&batch.image) {
+                            for sprite in &batch.sprites {
+                                let draw = ImageDraw {
+                                    source: Some(sprite.source),
+                                    destination: sprite.destination,
+                                    tint: sprite.tint,
+                                    filter: batch.filter,
+                                };
+                                self.fill_image(&image, &draw);
+                            }
+                            self.images.insert(batch.image, image);
+                        }
+                    }
+                    Command::Text {
+                        layout,
+                        x,
+                        y,
+                        color,
+                        mode,
+                        effects,
+                    } => {
+                        if mode == GlyphMode::DistanceField || effects != TextEffects::default() {
+                            self.queue_layout_fields(&layout, x, y, color, &effects);
+                        } else {
+                            // Fields are drawn before bitmaps are, like by
+                            // `TextRenderer`
+                            self.blend_queued_fields();
+                            self.blend_layout(&layout, x, y, color);
+                        }
+                    }
+                }
+            }
+
+            self.blend_queued_fields();
+        }
+    }
+
+    /// Resizing clears the pixels to black.
+    fn resize(&mut self, size: Vec2f, scale_factor: f64) {
+        self.width = size.x as usize;
+        self.height = size.y as usize;
+        self.pixels = black(self.width, self.height);
+
+        if self.text.update_dpr(scale_factor) {
+            self.cache.clear();
+            self.fields.clear();
+        }
+    }
+
+    fn add_fallback(&mut self, family: &str) -> Result<(), RasterizerError> {
+        self.text.add_fallback(family)
+    }
+
+    fn layout_sized(
+        &mut self,
+        string: &str,
+        font_size: f32,
+        max_width: f32,
+        align: Align,
+        style: FontStyle,
+    ) -> Layout {
+        self.text
+            .layout_sized(string, font_size, max_width, align, style)
+    }
+
+    fn font_size(&self) -> f32 {
+        self.text.font_size()
+    }
+
+    fn set_font_size(&mut self, font_size: f32) {
+        self.text.set_font_size(font_size);
+    }
+
+    fn glyph_mode(&self) -> GlyphMode {
+        self.mode
+    }
+
+    fn set_glyph_mode(&mut self, mode: GlyphMode) {
+        self.mode = mode;
+    }
+
+    fn draw_layout_with_effects(
+        &mut self,
+        layout: &Layout,
+        x: i16,
+        y: i16,
+        color: Rgba,
+        effects: &TextEffects,
+    ) {
+        self.queue.push(Command::Text {
+            layout: layout.clone(),
+            x,
+            y,
+            color,
+            mode: self.mode,
+            effects: *effects,
+        });
+    }
+
+    fn get_height(&self) -> i16 {
+        self.text.line_height()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{self, Image};
+    use crate::renderer::atlas::{Sprite, SpriteAtlas};
+    use crate::renderer::backend::GlBackend;
+    use crate::renderer::decoration::{Decoration, LineStyle};
+    use crate::renderer::gradient::{ColorStop, Extend, GradientKind};
+    use crate::renderer::image::{self, Filter};
+    use crate::renderer::path::{PathBuilder, SubPath};
+    use crate::renderer::sdf::{Glow, Outline, Shadow};
+    use crate::renderer::shape::{CornerRadii, Style};
+    use crate::renderer::stroke::{LineCap, LineJoin};
+    use crate::renderer::Rgb;
+
+    /// Text and rectangles covering each other in the order they are drawn.
+    fn scene(backend: &mut dyn Backend) {
+        backend.draw_string(
+            "Hello, World!",
+            8,
+            14,
+            Rgb::from_hex(0xFFFFFF).into(),
+            FontStyle::REGULAR,
+        );
+        backend.rectangle(&RenderRect {
+            x: 10.,
+            y: 44.,
+            width: 100.,
+            height: 6.,
+            color: Rgb { r: 255, g: 0, b: 0 }.into(),
+        });
+        backend.draw();
+        backend.rectangle(&RenderRect {
+            x: 136.,
+            y: 36.,
+            width: 40.,
+            height: 20.,
+            color: Rgb { r: 0, g: 0, b: 255 }.into(),
+        });
+        backend.draw_string(
+            "0xC0FFEE",
+            140,
+            14,
+            Rgb::from_hex(0xC0FFEE).into(),
+            FontStyle::BOLD,
+        );
+
+        // A translucent highlight and faded text
+        backend.rectangle(&RenderRect {
+            x: 4.,
+            y: 4.,
+            width: 60.,
+            height: 48.,
+            color: Rgb::from_hex(0xFFFF00).alpha(96),
+        });
+        let faded = Rgb::from_hex(0xFFFFFF).alpha(128);
+        backend.draw_string("fade", 200, 20, faded, FontStyle::REGULAR);
+        backend.draw();
+    }
+
+    #[test]
+    fn rectangle_coverage() {
+        let mut backend = CpuBackend::new("DejaVu Sans", 16., 4, 4, 1.).unwrap();
+        backend.rectangle(&RenderRect {
+            x: 0.6,
+            y: 1.,
+            width: 2.,
+            height: 2.,
+            color: Rgb { r: 255, g: 0, b: 0 }.into(),
+        });
+        backend.draw();
+
+        let covered: Vec<bool> = backend
+            .pixels()
+            .chunks_exact(4)
+            .map(|p| p[0] == 255)
+            .collect();
+        #[rustfmt::skip]
+        assert_eq!(covered, [
+            false, false, false, false,
+            false, true,  true,  false,
+            false, true,  true,  false,
+            false, false, false, false,
+        ]);
+    }
+
+    /// Anti-aliased shapes over each other.
+    fn shapes(backend: &mut dyn Backend) {
+        backend.rounded_rect(&RoundedRect {
+            x: 8.,
+            y: 8.,
+            width: 72.,
+            height: 48.,
+            radii: CornerRadii::uniform(12.),
+            color: Rgb::from_hex(0x3060C0).into(),
+            border: None,
+        });
+        backend.rounded_rect(&RoundedRect {
+            x: 48.5,
+            y: 24.25,
+            width: 64.,
+            height: 32.,
+            radii: CornerRadii {
+                top_left: 0.,
+                top_right: 16.,
+                bottom_right: 4.,
+                bottom_left: 40.,
+            },
+            color: Rgb::from_hex(0xFFFFFF).alpha(160),
+            border: Some(Border {
+                width: 3.,
+                color: Rgb::from_hex(0xE04020).into(),
+            }),
+        });
+
+        let green = Rgb::from_hex(0x40C060).into();
+        backend.ellipse(&Ellipse::circle(144.5, 32., 20., green, Style::Fill));
+        backend.ellipse(&Ellipse {
+            x: 144.,
+            y: 32.,
+            radius_x: 28.,
+            radius_y: 14.,
+            color: Rgb::from_hex(0xFFFFFF).alpha(192),
+            style: Style::Stroke(2.5),
+        });
+        backend.pie(&Pie {
+            x: 208.,
+            y: 32.,
+            radius_x: 24.,
+            radius_y: 24.,
+            start: -2.,
+            sweep: 4.,
+            color: Rgb::from_hex(0xE0C040).into(),
+            style: Style::Fill,
+        });
+        backend.pie(&Pie {
+            x: 212.,
+            y: 32.,
+            radius_x: 20.,
+            radius_y: 26.,
+            start: 2.5,
+            sweep: -1.5,
+            color: Rgb::from_hex(0x40C0E0).into(),
+            style: Style::Stroke(2.),
+        });
+        backend.arc(&Arc {
+            x: 208.,
+            y: 32.,
+            radius_x: 30.,
+            radius_y: 30.,
+            start: 0.5,
+            sweep: -4.,
+            width: 3.,
+            color: Rgb::from_hex(0xC040E0).into(),
+        });
+        backend.draw();
+    }
+
+    /// Strokes with every join, cap and a dash pattern.
+    fn lines(backend: &mut dyn Backend) {
+        let zigzag: Vec<Vec2f> = (0..5)
+            .map(|i| Vec2f::new(12. + i as f32 * 20., if i % 2 == 0 { 16. } else { 40. }))
+            .collect();
+        let joins = [LineJoin::Miter, LineJoin::Round, LineJoin::Bevel];
+        let caps = [LineCap::Butt, LineCap::Round, LineCap::Square];
+        for (i, (join, cap)) in joins.iter().zip(caps).enumerate() {
+            let mut stroke = Stroke::new(7., Rgb::from_hex(0x40A0E0).into());
+            stroke.join = *join;
+            stroke.cap = cap;
+
+            let offset = Vec2f::new(i as f32 * 0.3, i as f32 * 26.);
+            let points: Vec<Vec2f> = zigzag.iter().map(|point| *point + offset).collect();
+            backend.polyline(&points, false, &stroke);
+        }
+
+        let mut dashed = Stroke::new(4., Rgb::from_hex(0xFFC040).alpha(176));
+        dashed.dashes = vec![14., 6., 2., 6.];
+        dashed.dash_offset = 5.;
+        dashed.cap = LineCap::Round;
+        let star: Vec<Vec2f> = (0..5)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::PI * 4. / 5.;
+                Vec2f::new(180. + angle.sin() * 40., 52. - angle.cos() * 40.)
+            })
+            .collect();
+        backend.polyline(&star, true, &dashed);
+
+        let thin = Stroke::new(0.6, Rgb::from_hex(0xFFFFFF).into());
+        backend.line(Vec2f::new(130., 90.), Vec2f::new(250., 6.), &thin);
+        backend.line(
+            Vec2f::new(130.5, 6.),
+            Vec2f::new(250.5, 90.),
+            &Stroke::new(1.5, Rgb::from_hex(0xFF4060).into()),
+        );
+        backend.draw();
+    }
+
+    fn paths(backend: &mut dyn Backend) {
+        // A heart of curves, partly translucent over a stroked outline
+        let heart = PathBuilder::new()
+            .move_to(Vec2f::new(40., 80.))
+            .cubic_to(
+                Vec2f::new(0., 50.),
+                Vec2f::new(10., 10.),
+                Vec2f::new(40., 28.),
+            )
+            .cubic_to(
+                Vec2f::new(70., 10.),
+                Vec2f::new(80., 50.),
+                Vec2f::new(40., 80.),
+            )
+            .close()
+            .build();
+        let mut outline = Stroke::new(5., Rgb::from_hex(0xFFFFFF).into());
+        outline.join = LineJoin::Round;
+        backend.stroke_path(&heart, &outline);
+        backend.fill_path(
+            &heart,
+            FillRule::NonZero,
+            Rgb::from_hex(0xE04060).alpha(200),
+        );
+
+        // The same star with both fill rules
+        let mut star = PathBuilder::new();
+        for i in 0..5 {
+            let angle = i as f32 * std::f32::consts::PI * 4. / 5.;
+            star.line_to(Vec2f::new(
+                110. + angle.sin() * 30.,
+                50. - angle.cos() * 30.,
+            ));
+        }
+        let star = star.close().build();
+        backend.fill_path(&star, FillRule::NonZero, Rgb::from_hex(0xFFC040).into());
+        let offset = Vec2f::new(64.3, 0.);
+        let shifted = Path {
+            subpaths: star
+                .subpaths
+                .iter()
+                .map(|subpath| SubPath {
+                    points: subpath.points.iter().map(|point| *point + offset).collect(),
+                    closed: true,
+                })
+                .collect(),
+        };
+        backend.fill_path(&shifted, FillRule::EvenOdd, Rgb::from_hex(0x40A0E0).into());
+
+        // A quadratic wave and an elliptical arc with a hole, off the edge
+        let wave = PathBuilder::new()
+            .move_to(Vec2f::new(8., 90.))
+            .quadratic_to(Vec2f::new(40., 70.), Vec2f::new(72., 90.))
+            .quadratic_to(Vec2f::new(104., 110.), Vec2f::new(136., 90.))
+            .build();
+        backend.stroke_path(&wave, &Stroke::new(3., Rgb::from_hex(0x60E080).into()));
+
+        let mut ring = PathBuilder::new();
+        ring.move_to(Vec2f::new(216., 40.))
+            .arc_to(
+                Vec2f::new(40., 28.),
+                0.4,
+                false,
+                true,
+                Vec2f::new(296., 40.),
+            )
+            .arc_to(
+                Vec2f::new(40., 28.),
+                0.4,
+                false,
+                true,
+                Vec2f::new(216., 40.),
+            )
+            .close()
+            .move_to(Vec2f::new(236., 40.))
+            .arc_to(
+                Vec2f::new(20., 12.),
+                0.4,
+                false,
+                false,
+                Vec2f::new(276., 40.),
+            )
+            .arc_to(
+                Vec2f::new(20., 12.),
+                0.4,
+                false,
+                false,
+                Vec2f::new(236., 40.),
+            )
+            .close();
+        backend.fill_path(
+            &ring.build(),
+            FillRule::NonZero,
+            Rgb::from_hex(0xA070E0).into(),
+        );
+        backend.draw();
+    }
+
+    fn gradients(backend: &mut dyn Backend) {
+        let color = |hex: u32| Rgb::from_hex(hex).into();
+        let rainbow = vec![
+            ColorStop::new(0., color(0xE04060)),
+            ColorStop::new(0.3, color(0xFFC040)),
+            ColorStop::new(0.6, color(0x60E080)),
+            ColorStop::new(1., color(0x40A0E0)),
+        ];
+        let rect = |x: f32, y: f32, width: f32, height: f32| RenderRect {
+            x,
+            y,
+            width,
+            height,
+            color: color(0x000000),
+        };
+
+        // Linear gradients with every extend mode
+        let extends = [Extend::Pad, Extend::Repeat, Extend::Reflect];
+        for (i, extend) in extends.iter().enumerate() {
+            let y = 4. + i as f32 * 24.;
+            let kind = GradientKind::Linear {
+                start: Vec2f::new(24., y),
+                end: Vec2f::new(48., y + 12.),
+            };
+            let mut gradient = Gradient::new(kind, rainbow.clone());
+            gradient.extend = *extend;
+            backend.gradient(&GradientShape::Rect(rect(4., y, 80., 20.)), &gradient);
+        }
+
+        // A repeated radial gradient fading out, in a rounded rectangle
+        let mut rings = Gradient::new(
+            GradientKind::Radial {
+                center: Vec2f::new(124., 38.),
+                radius: 12.,
+            },
+            vec![
+                ColorStop::new(0., color(0xFFFFFF)),
+                ColorStop::new(1., Rgb::from_hex(0x4060E0).alpha(0)),
+            ],
+        );
+        rings.extend = Extend::Repeat;
+        let rounded = RoundedRect {
+            x: 92.5,
+            y: 6.5,
+            width: 64.,
+            height: 64.,
+            radii: CornerRadii::uniform(14.),
+            color: color(0x000000),
+            border: Some(Border {
+                width: 3.,
+                color: color(0xFFC040),
+            }),
+        };
+        backend.rounded_rect(&RoundedRect {
+            color: color(0x808080),
+            border: None,
+            ..rounded
+        });
+        backend.gradient(&GradientShape::RoundedRect(rounded), &rings);
+
+        // A conic gradient in a star with a sharp stop
+        let mut star = PathBuilder::new();
+        for i in 0..5 {
+            let angle = i as f32 * std::f32::consts::PI * 4. / 5.;
+            star.line_to(Vec2f::new(
+                206. + angle.sin() * 36.,
+                40. - angle.cos() * 36.,
+            ));
+        }
+        let mut wheel = rainbow;
+        wheel.push(ColorStop::new(1., color(0xFFFFFF)));
+        wheel.insert(2, ColorStop::new(0.3, Rgb::from_hex(0x000000).alpha(128)));
+        let gradient = Gradient::new(
+            GradientKind::Conic {
+                center: Vec2f::new(206., 40.),
+                angle: -0.5,
+            },
+            wheel,
+        );
+        let path = GradientShape::Path {
+            path: star.close().build(),
+            rule: FillRule::EvenOdd,
+        };
+        backend.gradient(&path, &gradient);
+        backend.draw();
+    }
+
+    fn images(backend: &mut dyn Backend) {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/images/quadrants.jpg");
+        let quadrants = backend
+            .load_image(&image::Image::open(path).unwrap())
+            .unwrap();
+
+        // A translucent checkerboard with a transparent corner
+        let mut pixels = Vec::new();
+        for y in 0..4 {
+            for x in 0..4 {
+                let color = if (x + y) % 2 == 0 { 0xFFC040 } else { 0x40A0E0 };
+                let alpha = if (x, y) == (3, 3) { 0 } else { 255 - x * 40 };
+                pixels.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+                pixels.push(alpha as u8);
+            }
+        }
+        let checkers = image::Image {
+            width: 4,
+            height: 4,
+            pixels,
+        };
+        let checkers = backend.load_image(&checkers).unwrap();
+
+        let short = image::Image {
+            width: 4,
+            height: 4,
+            pixels: vec![0; 60],
+        };
+        assert!(matches!(
+            backend.load_image(&short),
+            Err(ImageError::InvalidSize { len: 60, .. })
+        ));
+
+        backend.rectangle(&RenderRect {
+            x: 0.,
+            y: 40.,
+            width: 256.,
+            height: 8.,
+            color: Rgb::from_hex(0x808080).into(),
+        });
+        let rect = |x: f32, y: f32, width: f32, height: f32| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        for (i, filter) in [Filter::Nearest, Filter::Linear].iter().enumerate() {
+            let offset = i as f32 * 128.;
+            let mut draw = ImageDraw::new(rect(4. + offset, 4., 48., 48.));
+            draw.filter = *filter;
+            backend.draw_image(quadrants, &draw).unwrap();
+
+            draw.destination = rect(56.3 + offset, 4.6, 64., 64.);
+            backend.draw_image(checkers, &draw).unwrap();
+
+            // A corner of the image, tinted and squeezed
+            draw.source = Some(rect(4., 4., 12., 12.));
+            draw.destination = rect(4. + offset, 56., 24., 16.);
+            draw.tint = Rgb::from_hex(0x80FF80).alpha(192);
+            backend.draw_image(quadrants, &draw).unwrap();
+        }
+
+        backend.draw();
+
+        backend.remove_image(checkers);
+        assert!(matches!(
+            backend.draw_image(checkers, &ImageDraw::new(rect(0., 0., 8., 8.))),
+            Err(ImageError::NotLoaded(_))
+        ));
+    }
+
+    fn sprites(backend: &mut dyn Backend) {
+        // Icons drawn by a distance, with soft edges
+        let icon = |color: u32, shape: fn(f32, f32) -> f32| {
+            let mut pixels = Vec::new();
+            for y in 0..10 {
+                for x in 0..10 {
+                    let (px, py) = (x as f32 - 4.5, y as f32 - 4.5);
+                    let alpha = ((4.5 - shape(px, py)) * 255.).clamp(0., 255.);
+                    pixels.extend_from_slice(&[
+                        (color >> 16) as u8,
+                        (color >> 8) as u8,
+                        color as u8,
+                    ]);
+                    pixels.push(alpha as u8);
+                }
+            }
+            image::Image {
+                width: 10,
+                height: 10,
+                pixels,
+            }
+        };
+        let icons = [
+            icon(0xE04060, |x, y| (x * x + y * y).sqrt()),
+            icon(0x60E080, |x, y| x.abs().max(y.abs())),
+            icon(0x40A0E0, |x, y| x.abs() + y.abs()),
+            icon(0xFFC040, |x, y| {
+                (x.abs().min(y.abs()) * 3.).max(x.abs().max(y.abs()))
+            }),
+        ];
+
+        let mut atlas = SpriteAtlas::new(32, 32, 1);
+        let sprites: Vec<Sprite> = icons
+            .iter()
+            .map(|icon| atlas.insert(icon).unwrap())
+            .collect();
+        let id = atlas.upload(backend).unwrap();
+
+        let mut batch = SpriteBatch::new(id, Filter::Linear);
+        for row in 0..6 {
+            for column in 0..24 {
+                let destination = Rect {
+                    x: 2. + column as f32 * 10.5,
+                    y: 2. + row as f32 * 10.,
+                    width: 10.,
+                    height: 10.,
+                };
+                let alpha = 255 - column as u8 * 8;
+                let tint = Rgb::from_hex(0xFFFFFF).alpha(alpha);
+                batch.push(sprites[(row + column) % 4], destination, tint);
+            }
+        }
+
+        // Scaled up, the padding keeps the edges clean
+        for (i, sprite) in sprites.iter().enumerate() {
+            let destination = Rect {
+                x: 4. + i as f32 * 34.,
+                y: 64.,
+                width: 30.,
+                height: 30.,
+            };
+            batch.push(*sprite, destination, Rgb::from_hex(0xFFFFFF).into());
+        }
+        backend.draw_sprites(&batch).unwrap();
+        backend.draw();
+    }
+
+    /// A heading, body text and a caption laid out at their own sizes, then
+    /// the body zoomed in.
+    fn sizes(backend: &mut dyn Backend) {
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        let heading =
+            backend.layout_sized("Heading", 28., f32::INFINITY, Align::Left, FontStyle::BOLD);
+        backend.draw_layout(&heading, 8, 0, white);
+        backend.draw_string("Body text", 8, 42, white, FontStyle::REGULAR);
+        let caption = backend.layout_sized(
+            "small caption",
+            9.,
+            f32::INFINITY,
+            Align::Left,
+            FontStyle::ITALIC,
+        );
+        backend.draw_layout(&caption, 8, 71, Rgb::from_hex(0xC0C0C0).into());
+
+        backend.set_font_size(backend.font_size() * 1.5);
+        backend.draw_string("Body text", 8, 74, white, FontStyle::REGULAR);
+        backend.draw();
+    }
+
+    fn decorations(backend: &mut dyn Backend) {
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        let styles = [
+            LineStyle::Solid,
+            LineStyle::Double,
+            LineStyle::Dotted,
+            LineStyle::Dashed,
+            LineStyle::Wavy,
+        ];
+
+        for (i, style) in styles.iter().enumerate() {
+            let layout =
+                backend.layout("Decorated", f32::INFINITY, Align::Left, FontStyle::REGULAR);
+            let decoration = Decoration {
+                underline: Some(*style),
+                ..Decoration::default()
+            };
+            backend.draw_decorated(&layout, 8, 28 * i as i16 + 2, white, &decoration);
+        }
+
+        let layout = backend.layout("Struck", f32::INFINITY, Align::Left, FontStyle::BOLD);
+        let decoration = Decoration {
+            strikethrough: Some(LineStyle::Solid),
+            overline: Some(LineStyle::Double),
+            color: Some(Rgb::from_hex(0xFF4040).into()),
+            ..Decoration::default()
+        };
+        backend.draw_decorated(&layout, 144, 2, white, &decoration);
+
+        let layout =
+            backend.layout_sized("Wavy", 32., f32::INFINITY, Align::Left, FontStyle::REGULAR);
+        let decoration = Decoration {
+            underline: Some(LineStyle::Wavy),
+            ..Decoration::default()
+        };
+        backend.draw_decorated(&layout, 144, 53, white, &decoration);
+
+        // Drawn over the text and its underline
+        backend.rectangle(&RenderRect {
+            x: 96.,
+            y: 128.,
+            width: 40.,
+            height: 16.,
+            color: Rgb::from_hex(0x4080FF).into(),
+        });
+        backend.draw();
+    }
+
+    fn bidi(backend: &mut dyn Backend) {
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        let text = "Hello עולם!\nשלום world 2024\nمرحبا بالعالم (123)";
+        let layout = backend.layout(text, 240., Align::Start, FontStyle::REGULAR);
+        backend.draw_layout(&layout, 8, 2, white);
+        backend.draw();
+    }
+
+    /// The mode of every text is kept in the queue when it is changed.
+    fn glyph_modes(backend: &mut dyn Backend) {
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        backend.draw_string("Bitmap", 8, 4, white, FontStyle::REGULAR);
+
+        backend.set_glyph_mode(GlyphMode::DistanceField);
+        // Distance fields are scaled by a half and not at all
+        let field =
+            backend.layout_sized("Field", 18., f32::INFINITY, Align::Left, FontStyle::REGULAR);
+        backend.draw_layout(&field, 8, 29, white);
+        let large = backend.layout_sized("Large", 36., f32::INFINITY, Align::Left, FontStyle::BOLD);
+        backend.draw_layout(&large, 96, 0, Rgb::from_hex(0xFFD070).into());
+
+        backend.set_glyph_mode(GlyphMode::Bitmap);
+        assert_eq!(backend.glyph_mode(), GlyphMode::Bitmap);
+        backend.draw_string("Bitmap", 8, 60, white, FontStyle::REGULAR);
+        backend.draw();
+    }
+
+    /// Effects of text drawn as bitmaps, behind the text drawn next.
+    fn text_effects(backend: &mut dyn Backend) {
+        backend.rectangle(&RenderRect {
+            x: 0.,
+            y: 0.,
+            width: 256.,
+            height: 112.,
+            color: Rgb::from_hex(0xE0E0D0).into(),
+        });
+
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        let outline = TextEffects {
+            outline: Some(Outline {
+                width: 2.,
+                color: Rgb::from_hex(0x202020).into(),
+            }),
+            ..TextEffects::default()
+        };
+        let all = TextEffects {
+            glow: Some(Glow {
+                radius: 4.,
+                color: Rgb::from_hex(0x2060C0).into(),
+            }),
+            shadow: Some(Shadow {
+                offset: Vec2f::new(2., 3.),
+                blur: 2.,
+                color: Rgb::from_hex(0x000000).alpha(192),
+            }),
+            ..outline
+        };
+
+        let large =
+            backend.layout_sized("Effects", 36., f32::INFINITY, Align::Left, FontStyle::BOLD);
+        backend.draw_layout_with_effects(&large, 8, 0, white, &all);
+        let small =
+            backend.layout_sized("Outlined", 18., f32::INFINITY, Align::Left, FontStyle::BOLD);
+        backend.draw_layout_with_effects(&small, 8, 71, white, &outline);
+        backend.draw_string(
+            "Plain",
+            160,
+            74,
+            Rgb::from_hex(0x202020).into(),
+            FontStyle::REGULAR,
+        );
+        backend.draw();
+    }
+
+    fn texture_units(backend: &mut dyn Backend) {
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        backend.draw_string("Before", 8, 2, white, FontStyle::REGULAR);
+
+        // A batch of shapes with an image and a gradient between the texts,
+        // the second text reuses the atlas of the first
+        let pixels = [0xE0, 0x40, 0x60, 0xFF].repeat(4);
+        let image = backend
+            .load_image(&image::Image {
+                width: 2,
+                height: 2,
+                pixels,
+            })
+            .unwrap();
+        let destination = Rect {
+            x: 88.,
+            y: 8.,
+            width: 32.,
+            height: 32.,
+        };
+        backend
+            .draw_image(image, &ImageDraw::new(destination))
+            .unwrap();
+        let gradient = Gradient::new(
+            GradientKind::Linear {
+                start: Vec2f::new(128., 0.),
+                end: Vec2f::new(160., 0.),
+            },
+            vec![
+                ColorStop::new(0., Rgb::from_hex(0x40A0E0).into()),
+                ColorStop::new(1., Rgb::from_hex(0x60E080).into()),
+            ],
+        );
+        let rect = RenderRect {
+            x: 128.,
+            y: 8.,
+            width: 32.,
+            height: 32.,
+            color: white,
+        };
+        backend.gradient(&GradientShape::Rect(rect), &gradient);
+
+        backend.draw_string("Before", 168, 2, white, FontStyle::REGULAR);
+        backend.draw();
+    }
+
+    /// Draws `scene` with both backends and checks them against each other
+    /// and the golden image of the GPU.
+    fn assert_matches_gl(name: &str, width: u32, height: u32, scene: fn(&mut dyn Backend)) {
+        let gl = golden::render(width, height, |size| {
+            let mut backend = GlBackend::new("DejaVu Sans", 16., size, 1.).unwrap();
+            scene(&mut backend);
+        });
+
+        let (cpu_width, cpu_height) = (width as usize, height as usize);
+        let mut cpu = CpuBackend::new("DejaVu Sans", 16., cpu_width, cpu_height, 1.).unwrap();
+        scene(&mut cpu);
+        let cpu = Image {
+            width,
+            height,
+            pixels: cpu.pixels().to_vec(),
+        };
+
+        golden::assert_golden(name, &gl, golden::TOLERANCE);
+
+        let (mismatched, _) = golden::compare(&gl, &cpu, golden::TOLERANCE);
+        assert_eq!(mismatched, 0);
+    }
+
+    #[test]
+    fn matches_gl() {
+        assert_matches_gl("draw_order", 256, 64, scene);
+    }
+
+    #[test]
+    fn shapes_match_gl() {
+        assert_matches_gl("shapes", 256, 64, shapes);
+    }
+
+    #[test]
+    fn lines_match_gl() {
+        assert_matches_gl("lines", 256, 96, lines);
+    }
+
+    #[test]
+    fn paths_match_gl() {
+        assert_matches_gl("paths", 256, 112, paths);
+    }
+
+    #[test]
+    fn gradients_match_gl() {
+        assert_matches_gl("gradients", 256, 80, gradients);
+    }
+
+    #[test]
+    fn images_match_gl() {
+        assert_matches_gl("images", 256, 76, images);
+    }
+
+    #[test]
+    fn sprites_match_gl() {
+        assert_matches_gl("sprites", 256, 96, sprites);
+    }
+
+    #[test]
+    fn sizes_match_gl() {
+        assert_matches_gl("sizes", 256, 128, sizes);
+    }
+
+    #[test]
+    fn decorations_match_gl() {
+        assert_matches_gl("decorations", 256, 160, decorations);
+    }
+
+    #[test]
+    fn glyph_modes_match_gl() {
+        assert_matches_gl("glyph_modes", 256, 96, glyph_modes);
+    }
+
+    #[test]
+    fn text_effects_match_gl() {
+        assert_matches_gl("text_effects_backends", 256, 112, text_effects);
+    }
+
+    #[test]
+    fn texture_units_match_gl() {
+        assert_matches_gl("texture_units", 256, 48, texture_units);
+    }
+
+    #[test]
+    fn bidi_match_gl() {
+        assert_matches_gl("bidi", 256, 96, bidi);
+    }
+
+    #[test]
+    fn resize() {
+        let mut backend = CpuBackend::new("DejaVu Sans", 16., 4, 4, 1.).unwrap();
+        let length = backend.get_length("Hello", FontStyle::REGULAR);
+        let height = backend.get_height();
+
+        backend.resize(Vec2f { x: 8., y: 6. }, 2.);
+        assert_eq!(backend.pixels().len(), 8 * 6 * 4);
+        assert!((backend.get_length("Hello", FontStyle::REGULAR) - length * 2).abs() <= 2);
+        assert!((backend.get_height() - height * 2).abs() <= 2);
+    }
+}
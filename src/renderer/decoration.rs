@@ -0,0 +1,267 @@
+use crate::renderer::font::cell_height;
+use crate::renderer::layout::Layout;
+use crate::renderer::stroke::Stroke;
+use crate::renderer::Rgba;
+use crate::vectors::Vec2f;
+
+use crossfont::{Metrics, Size};
+use std::f32::consts::TAU;
+
+/// How a decoration line is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineStyle {
+    Solid,
+    /// Two lines, one thickness apart.
+    Double,
+    /// Square dots, one thickness apart.
+    Dotted,
+    Dashed,
+    /// A sine wave as high as two thicknesses.
+    Wavy,
+}
+
+/// Lines along the text of a layout, drawn by [`Backend::draw_decorated`].
+///
+/// [`Backend::draw_decorated`]: crate::renderer::backend::Backend::draw_decorated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Decoration {
+    pub underline: Option<LineStyle>,
+    pub strikethrough: Option<LineStyle>,
+    pub overline: Option<LineStyle>,
+    /// Color of the lines, the color of the text if `None`.
+    pub color: Option<Rgba>,
+}
+
+/// Where the decorations of a font size are drawn, in pixels below the top of
+/// a line.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DecorationMetrics {
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+    pub strikeout_position: f32,
+    pub strikeout_thickness: f32,
+    /// The top of the ascent, overlines are as thick as underlines.
+    pub overline_position: f32,
+}
+
+impl DecorationMetrics {
+    /// Converts the positions of `metrics`, which are above the baseline, for
+    /// glyphs of `size`.
+    pub fn new(metrics: &Metrics, size: Size) -> Self {
+        let baseline = cell_height(size);
+        let ascent = metrics.line_height as f32 + metrics.descent;
+
+        Self {
+            underline_position: baseline - metrics.underline_position,
+            underline_thickness: metrics.underline_thickness,
+            strikeout_position: baseline - metrics.strikeout_position,
+            strikeout_thickness: metrics.strikeout_thickness,
+            overline_position: baseline - ascent + metrics.underline_thickness / 2.,
+        }
+    }
+}
+
+/// A decoration line of a layout, drawn with [`Backend::polyline`].
+///
+/// [`Backend::polyline`]: crate::renderer::backend::Backend::polyline
+#[derive(Debug, Clone)]
+pub struct DecorationLine {
+    pub points: Vec<Vec2f>,
+    pub stroke: Stroke,
+}
+
+/// The lines of `decoration` along every line of `layout`, drawn at `x` and
+/// `y` like [`Backend::draw_layout`].
+///
+/// Lines are as wide as the text without trailing whitespace and centered on
+/// whole pixels, so solid lines stay sharp.
+///
+/// [`Backend::draw_layout`]: crate::renderer::backend::Backend::draw_layout
+pub fn decoration_lines(
+    layout: &Layout,
+    x: i16,
+    y: i16,
+    color: Rgba,
+    decoration: &Decoration,
+) -> Vec<DecorationLine> {
+    let metrics = &layout.decoration_metrics;
+    let color = decoration.color.unwrap_or(color);
+    let kinds = [
+        (
+            decoration.overline,
+            metrics.overline_position,
+            metrics.underline_thickness,
+        ),
+        (
+            decoration.strikethrough,
+            metrics.strikeout_position,
+            metrics.strikeout_thickness,
+        ),
+        (
+            decoration.underline,
+            metrics.underline_position,
+            metrics.underline_thickness,
+        ),
+    ];
+
+    let mut lines = Vec::new();
+    for line in layout.lines.iter().filter(|line| line.width > 0.) {
+        let start = f32::from(x) + line.x;
+        let end = start + line.width;
+        let line_top = f32::from(y) + line.y.round();
+
+        for (style, position, thickness) in kinds {
+            if let Some(style) = style {
+                let thickness = thickness.round().max(1.);
+                let center = (line_top + position - thickness / 2.).round() + thickness / 2.;
+                let stroke = Stroke::new(thickness, color);
+                push_line(&mut lines, style, start, end, center, stroke);
+            }
+        }
+    }
+
+    lines
+}
+
+fn push_line(
+    lines: &mut Vec<DecorationLine>,
+    style: LineStyle,
+    start: f32,
+    end: f32,
+    y: f32,
+    mut stroke: Stroke,
+) {
+    let thickness = stroke.width;
+    let straight = |y| vec![Vec2f::new(start, y), Vec2f::new(end, y)];
+
+    let points = match style {
+        LineStyle::Solid => straight(y),
+        LineStyle::Double => {
+            lines.push(DecorationLine {
+                points: straight(y - thickness),
+                stroke: stroke.clone(),
+            });
+            straight(y + thickness)
+        }
+        LineStyle::Dotted => {
+            stroke.dashes = vec![thickness, thickness];
+            straight(y)
+        }
+        LineStyle::Dashed => {
+            stroke.dashes = vec![thickness * 3., thickness * 2.];
+            straight(y)
+        }
+        LineStyle::Wavy => {
+            let wavelength = thickness * 4.;
+            let step = wavelength / 8.;
+            let count = ((end - start) / step).ceil() as usize;
+
+            (0..=count)
+                .map(|i| {
+                    let x = (start + i as f32 * step).min(end);
+                    let phase = (x - start) / wavelength * TAU;
+                    Vec2f::new(x, y + phase.sin() * thickness)
+                })
+                .collect()
+        }
+    };
+
+    lines.push(DecorationLine { points, stroke });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::layout::{Direction, LayoutLine};
+    use crate::renderer::Rgb;
+
+    fn metrics() -> Metrics {
+        Metrics {
+            average_advance: 8.,
+            line_height: 20.,
+            descent: -4.,
+            underline_position: -2.,
+            underline_thickness: 1.,
+            strikeout_position: 5.,
+            strikeout_thickness: 2.,
+        }
+    }
+
+    fn layout(widths: &[f32]) -> Layout {
+        let lines = widths
+            .iter()
+            .enumerate()
+            .map(|(i, &width)| LayoutLine {
+                glyphs: Vec::new(),
+                range: 0..0,
+                x: 2.,
+                y: i as f32 * 20.,
+                width,
+                direction: Direction::LeftToRight,
+            })
+            .collect();
+
+        Layout {
+            lines,
+            width: 40.,
+            height: widths.len() as f32 * 20.,
+            line_height: 20.,
+            decoration_metrics: DecorationMetrics::new(&metrics(), Size::new(8.)),
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let metrics = DecorationMetrics::new(&metrics(), Size::new(8.));
+        let baseline = cell_height(Size::new(8.));
+
+        assert_eq!(metrics.underline_position, baseline + 2.);
+        assert_eq!(metrics.strikeout_position, baseline - 5.);
+        assert_eq!(metrics.overline_position, baseline - 15.5);
+    }
+
+    #[test]
+    fn styles() {
+        let black: Rgba = Rgb::from_hex(0x000000).into();
+        let red: Rgba = Rgb::from_hex(0xFF0000).into();
+        let layout = layout(&[40., 0., 20.]);
+        let lines = |decoration: Decoration| decoration_lines(&layout, 10, 0, black, &decoration);
+
+        let underline = lines(Decoration {
+            underline: Some(LineStyle::Solid),
+            ..Decoration::default()
+        });
+        // Empty lines are not decorated
+        assert_eq!(underline.len(), 2);
+        assert_eq!(
+            underline[0].points,
+            [Vec2f::new(12., 18.5), Vec2f::new(52., 18.5)]
+        );
+        assert_eq!(underline[1].points[1], Vec2f::new(32., 58.5));
+        assert_eq!(underline[0].stroke.color, black);
+
+        let all = lines(Decoration {
+            underline: Some(LineStyle::Double),
+            strikethrough: Some(LineStyle::Dashed),
+            overline: Some(LineStyle::Dotted),
+            color: Some(red),
+        });
+        assert_eq!(all.len(), 8);
+        assert!(all.iter().all(|line| line.stroke.color == red));
+        assert_eq!(all[0].stroke.dashes, [1., 1.]);
+        assert_eq!(all[1].stroke.dashes, [6., 4.]);
+        assert_eq!(all[1].points[0].y, 11.);
+        assert_eq!(all[2].points[0].y, 17.5);
+        assert_eq!(all[3].points[0].y, 19.5);
+
+        let wavy = lines(Decoration {
+            underline: Some(LineStyle::Wavy),
+            ..Decoration::default()
+        });
+        let points = &wavy[0].points;
+        assert_eq!(points.first().unwrap().x, 12.);
+        assert_eq!(points.last().unwrap().x, 52.);
+        assert!(points.iter().all(|point| (point.y - 18.5).abs() <= 1.));
+        assert!(points.iter().any(|point| point.y < 18.));
+    }
+}
@@ -1,9 +1,16 @@
-use crate::renderer::shader::{Program, Shader, ShaderError};
+use crate::renderer::atlas::{Allocation, AtlasError, Occupancy, Packer, SkylinePacker};
+use crate::renderer::layout::{Align, Layout};
+use crate::renderer::sdf::{self, FieldInstance, FieldPipeline, TextEffects, FIELD_SIZE};
+use crate::renderer::shader::{Program, Shader};
+use crate::renderer::shaping::{FontStyle, GlyphId, ShapedKey};
+use crate::renderer::text::TextState;
+use crate::renderer::{RendererError, Rgb, Rgba};
 
 use crate::gl;
 use gl::types::*;
 
 use fnv::FnvHasher;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::hash::BuildHasherDefault;
 use std::mem::size_of;
@@ -14,16 +21,28 @@ use gl_matrix::mat4;
 
 use crate::vectors::Vec2f;
 
-use crossfont::{
-    BitmapBuffer, Error as RasterizerError, FontDesc, FontKey, GlyphKey, Metrics, Rasterize,
-    RasterizedGlyph, Rasterizer, Size, Slant, Style, Weight,
-};
+use crossfont::{BitmapBuffer, Error as RasterizerError, RasterizedGlyph, Size};
 
 use bitflags::bitflags;
 
 const BATCH_MAX: usize = 0x1_0000;
 const ATLAS_SIZE: i32 = 2048;
 
+/// Transparent pixels around every glyph in an atlas, so linear filtering
+/// never picks up a neighbour.
+const GLYPH_PADDING: i32 = 1;
+
+/// Bytes of the texture of one atlas.
+const ATLAS_BYTES: usize = (ATLAS_SIZE * ATLAS_SIZE * 4) as usize;
+
+/// Atlases used less than this are packed again before more glyphs are
+/// evicted from them.
+const COMPACT_RATIO: f32 = 0.5;
+
+/// Keys without a glyph that are cached, the least recently used half is
+/// dropped when there are more.
+const MISSING_MAX: usize = 4096;
+
 static FRAGMENT: &str = include_str!("../../res/text.frag");
 static VERTEX: &str = include_str!("../../res/text.vert");
 
@@ -49,6 +68,50 @@ pub struct Glyph {
     uv_height: f32,
 }
 
+/// How `TextRenderer` rasterizes and draws glyphs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlyphMode {
+    /// Coverage rasterized for every size with subpixel antialiasing, the
+    /// sharpest at small sizes.
+    Bitmap,
+    /// Distance fields generated once per glyph at `FIELD_SIZE`, which stay
+    /// sharp at any size and can have effects around them.
+    DistanceField,
+}
+
+/// Glyphs of every mode are cached apart.
+type CacheKey = (ShapedKey, GlyphMode);
+
+/// A glyph in the cache of `TextRenderer`.
+#[derive(Debug, Clone, Copy)]
+enum Cached {
+    Loaded {
+        glyph: Glyph,
+        atlas: usize,
+        allocation: Allocation,
+        /// When the glyph was last drawn or measured, on the clock of
+        /// `TextRenderer`.
+        used: u64,
+    },
+    /// The font has no glyph for the key, the glyph of its missing key is
+    /// drawn instead.
+    Missing { used: u64 },
+}
+
+/// Memory held by the glyph cache of a `TextRenderer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub glyphs: usize,
+    /// Cached keys the font has no glyph for, which take no atlas room.
+    pub missing: usize,
+    pub atlases: usize,
+    /// Bytes of all atlas textures.
+    pub texture_bytes: usize,
+    /// Bytes of the atlas textures taken by glyphs.
+    pub glyph_bytes: usize,
+    pub budget: Option<usize>,
+}
+
 #[derive(Debug)]
 #[repr(C)]
 struct InstanceData {
@@ -65,6 +128,7 @@ struct InstanceData {
     r: u8,
     g: u8,
     b: u8,
+    a: u8,
     cell_flags: RenderingGlyphFlags,
 }
 
@@ -74,31 +138,52 @@ pub struct Batch {
     instances: Vec<InstanceData>,
 }
 
+/// A layout drawn by the next [`TextRenderer::flush`].
+#[derive(Debug)]
+struct QueuedText {
+    layout: Layout,
+    x: i16,
+    /// Top of the first line.
+    top: i16,
+    color: Rgba,
+    mode: GlyphMode,
+    effects: TextEffects,
+}
+
+/// Draws text with glyphs cached in atlases.
+///
+/// Draws are only queued, [`TextRenderer::flush`] draws them in the order
+/// they were queued. Text queued before shapes of a `Renderer` stays below
+/// them as long as it is flushed before the shapes are drawn.
 pub struct TextRenderer {
     program: Program,
     vao: GLuint,
     ebo: GLuint,
     vbo_instance: GLuint,
     atlas: Vec<Atlas>,
-    current_atlas: usize,
+    /// Bytes the atlases may take, at least one atlas is kept.
+    budget: Option<usize>,
     active_tex: GLuint,
+    queued: Vec<QueuedText>,
     batch: Batch,
-    size: Size,
-    font_key: FontKey,
-    cache: HashMap<GlyphKey, Glyph, BuildHasherDefault<FnvHasher>>,
-    rasterizer: Rasterizer,
-    metrics: Metrics,
-    spacing: i16,
+    mode: GlyphMode,
+    /// Glyphs drawn from distance fields, with effects around them.
+    field: FieldPipeline,
+    /// Around glyphs drawn without effects of their own.
+    effects: TextEffects,
+    text: TextState,
+    cache: HashMap<CacheKey, Cached, BuildHasherDefault<FnvHasher>>,
+    /// Entries of `cache` that are `Cached::Missing`.
+    missing: usize,
+    /// Counts glyph lookups, to find the least recently used glyphs.
+    clock: u64,
 }
 
 #[derive(Debug)]
 struct Atlas {
     id: GLuint,
-    width: i32,
-    height: i32,
-    row_extent: i32,
-    row_baseline: i32,
-    row_tallest: i32,
+    size: i32,
+    packer: SkylinePacker,
 }
 
 impl Batch {
@@ -110,7 +195,7 @@ impl Batch {
         }
     }
 
-    pub fn add_item(&mut self, x: i16, y: i16, r: u8, g: u8, b: u8, glyph: &Glyph) {
+    pub fn add_item(&mut self, x: i16, y: i16, color: Rgba, glyph: &Glyph) {
         if self.is_empty() {
             self.tex = glyph.tex_id;
         }
@@ -122,9 +207,10 @@ impl Batch {
         self.instances.push(InstanceData {
             x,
             y,
-            r,
-            g,
-            b,
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            a: color.a,
             top: glyph.top,
             left: glyph.left,
             width: glyph.width,
@@ -158,19 +244,34 @@ impl Batch {
     }
 }
 
-fn compute_font_keys(rasterizer: &mut Rasterizer, font: &str, size: Size) -> FontKey {
-    rasterizer
-        .load_font(
-            &FontDesc::new(
-                font,
-                Style::Description {
-                    slant: Slant::Normal,
-                    weight: Weight::Normal,
-                },
-            ),
-            size,
-        )
-        .unwrap()
+/// Glyphs of `size` are placed `cell_height - glyph.top` below the line top.
+pub(crate) fn cell_height(size: Size) -> f32 {
+    size.as_f32_pts() * 2.
+}
+
+/// Maps screen pixels, with the origin at the top left, to clip space.
+fn set_projection(program: &Program, screen_size: Vec2f) {
+    let mut proj_matrix: Mat4 = [0.; 16];
+    mat4::ortho(
+        &mut proj_matrix,
+        0.,
+        screen_size.x,
+        screen_size.y,
+        0.,
+        0.,
+        1000.,
+    );
+
+    unsafe {
+        gl::UseProgram(program.id);
+        gl::UniformMatrix4fv(
+            gl::GetUniformLocation(program.id, b"projection\0".as_ptr() as *const _),
+            1,
+            gl::FALSE,
+            proj_matrix.as_ptr(),
+        );
+        gl::UseProgram(0);
+    }
 }
 
 impl TextRenderer {
@@ -179,38 +280,23 @@ impl TextRenderer {
         font_size: f32,
         screen_size: Vec2f,
         dpr: f64,
-    ) -> Result<TextRenderer, ShaderError> {
+    ) -> Result<TextRenderer, RendererError> {
         let program = Program::new(
             Shader::new(gl::VERTEX_SHADER, VERTEX)?,
             Shader::new(gl::FRAGMENT_SHADER, FRAGMENT)?,
         )?;
 
+        let field = FieldPipeline::new(BATCH_MAX)?;
+
         let mut vao: GLuint = 0;
         let mut ebo: GLuint = 0;
 
-        let mut proj_matrix: Mat4 = [0.; 16];
-        mat4::ortho(
-            &mut proj_matrix,
-            0.,
-            screen_size.x,
-            screen_size.y,
-            0.,
-            0.,
-            1000.,
-        );
-
         let mut vbo_instance: GLuint = 0;
 
-        unsafe {
-            gl::UseProgram(program.id);
-            gl::UniformMatrix4fv(
-                gl::GetUniformLocation(program.id, b"projection\0".as_ptr() as *const _),
-                1,
-                gl::FALSE,
-                proj_matrix.as_ptr(),
-            );
+        set_projection(&program, screen_size);
+        set_projection(&field.program, screen_size);
 
-            gl::UseProgram(0);
+        unsafe {
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC1_COLOR, gl::ONE_MINUS_SRC1_COLOR);
             gl::Enable(gl::MULTISAMPLE);
@@ -268,6 +354,7 @@ impl TextRenderer {
             add_attr!(4, gl::SHORT, i16);
             add_attr!(4, gl::FLOAT, f32);
             add_attr!(4, gl::UNSIGNED_BYTE, u8);
+            add_attr!(1, gl::UNSIGNED_BYTE, u8);
 
             gl::UseProgram(0);
             gl::Disable(gl::BLEND);
@@ -278,11 +365,7 @@ impl TextRenderer {
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         }
 
-        let size = Size::new(font_size);
-
-        let mut rasterizer = Rasterizer::new(dpr as f32, false).unwrap();
-        let font_key = compute_font_keys(&mut rasterizer, font, size);
-        let metrics = rasterizer.metrics(font_key, size).unwrap();
+        let text = TextState::new(font, font_size, dpr)?;
 
         let mut renderer = Self {
             program,
@@ -290,111 +373,386 @@ impl TextRenderer {
             ebo,
             vbo_instance,
             atlas: Vec::new(),
-            current_atlas: 0,
+            budget: None,
             active_tex: 0,
+            queued: Vec::new(),
             batch: Batch::new(),
+            mode: GlyphMode::Bitmap,
+            field,
+            effects: TextEffects::default(),
+            text,
             cache: HashMap::default(),
-            rasterizer,
-            metrics,
-            size,
-            font_key,
-            spacing: size.as_f32_pts() as i16 / 8,
+            missing: 0,
+            clock: 0,
         };
 
         let atlas = Atlas::new(ATLAS_SIZE);
         renderer.atlas.push(atlas);
 
-        for i in 32u8..=126u8 {
-            renderer.get_glyph(GlyphKey {
-                font_key,
-                character: i as char,
-                size,
-            });
+        let ascii: String = (32u8..=126u8).map(char::from).collect();
+        let font_key = renderer.text.font_key(FontStyle::REGULAR);
+        let size = renderer.text.size();
+        for glyph in renderer.text.shaper.shape(&ascii, font_key, size).glyphs {
+            renderer.get_glyph(glyph.key);
         }
 
         Ok(renderer)
     }
 
+    /// Adapts to a new screen size in pixels. When the scale factor changed
+    /// the metrics are updated and glyphs are rasterized again.
+    pub fn resize(&mut self, screen_size: Vec2f, scale_factor: f64) {
+        set_projection(&self.program, screen_size);
+        set_projection(&self.field.program, screen_size);
+
+        unsafe {
+            gl::Viewport(0, 0, screen_size.x as i32, screen_size.y as i32);
+        }
+
+        if !self.text.update_dpr(scale_factor) {
+            return;
+        }
+
+        self.cache.clear();
+        self.missing = 0;
+        for atlas in &mut self.atlas {
+            atlas.clear();
+        }
+    }
+
+    /// Appends a font to the fallback chain for characters the font has no
+    /// glyph for.
+    pub fn add_fallback(&mut self, family: &str) -> Result<(), RasterizerError> {
+        self.text.add_fallback(family)
+    }
+
+    /// The size text is laid out at unless another one is given, in points.
+    pub fn font_size(&self) -> f32 {
+        self.text.font_size()
+    }
+
+    /// Changes the size text is laid out at by default. Glyphs of the old
+    /// size stay cached until they are evicted.
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.text.set_font_size(font_size);
+    }
+
+    pub fn glyph_mode(&self) -> GlyphMode {
+        self.mode
+    }
+
+    /// Changes how the glyphs queued next are drawn.
+    pub fn set_glyph_mode(&mut self, mode: GlyphMode) {
+        self.mode = mode;
+    }
+
+    /// Changes the effects around the next glyphs queued without effects of
+    /// their own.
+    pub fn set_text_effects(&mut self, effects: TextEffects) {
+        self.effects = effects;
+    }
+
+    /// Queues a white `character` with the top of its line at `y`.
     pub fn draw_char(&mut self, character: char, x: i16, y: i16) {
-        let glyph = self.get_glyph(GlyphKey {
-            character,
-            font_key: self.font_key,
-            size: self.size,
-        });
+        let mut buffer = [0; 4];
+        let layout = self.layout(
+            character.encode_utf8(&mut buffer),
+            f32::INFINITY,
+            Align::Left,
+            FontStyle::REGULAR,
+        );
+        let (mode, effects) = (self.mode, self.effects);
+        self.queue(layout, x, y, Rgb::from_hex(0xFFFFFF).into(), mode, effects);
+    }
 
-        self.batch.add_item(x, y, 255, 255, 255, &glyph);
-        self.render_batch();
+    /// Width of the widest line of `string`.
+    pub fn get_length(&mut self, string: &str, style: FontStyle) -> i16 {
+        self.layout(string, f32::INFINITY, Align::Left, style)
+            .width
+            .round() as i16
     }
 
-    pub fn get_length(&mut self, string: &str) -> i16 {
-        let width: i16 = string
-            .chars()
-            .enumerate()
-            .map(|(_, character)| {
-                let mut w = self
-                    .get_glyph(GlyphKey {
-                        character,
-                        font_key: self.font_key,
-                        size: self.size,
-                    })
-                    .width;
-
-                if w == 0 {
-                    w = self.metrics.average_advance as i16 / 2;
-                }
+    /// Distance of lines at the font size.
+    pub fn get_height(&self) -> i16 {
+        self.text.line_height()
+    }
 
-                w + self.spacing
-            })
+    /// Limits the glyph atlases to `budget` bytes, but keeps at least one.
+    /// The least recently used glyphs are evicted when no room is left.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.budget = budget;
+
+        let max = self.max_atlases();
+        if self.atlas.len() <= max {
+            return;
+        }
+
+        let dropped: Vec<CacheKey> = self
+            .cache
+            .iter()
+            .filter(|(_, cached)| matches!(cached, Cached::Loaded { atlas, .. } if *atlas >= max))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in dropped {
+            self.forget(key);
+        }
+        self.atlas.truncate(max);
+        self.active_tex = 0;
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let glyphs = self
+            .cache
+            .values()
+            .filter(|cached| matches!(cached, Cached::Loaded { .. }))
+            .count();
+        let glyph_bytes = self
+            .atlas_occupancy()
+            .iter()
+            .map(|occupancy| occupancy.used as usize * 4)
             .sum();
 
-        width
+        MemoryUsage {
+            glyphs,
+            missing: self.missing,
+            atlases: self.atlas.len(),
+            texture_bytes: self.atlas.len() * ATLAS_BYTES,
+            glyph_bytes,
+            budget: self.budget,
+        }
     }
 
-    pub fn get_height(&self) -> i16 {
-        self.metrics.line_height as i16
+    fn max_atlases(&self) -> usize {
+        match self.budget {
+            Some(budget) => (budget / ATLAS_BYTES).max(1),
+            None => usize::MAX,
+        }
+    }
+
+    /// How full every glyph atlas is, in the order they were created.
+    pub fn atlas_occupancy(&self) -> Vec<Occupancy> {
+        self.atlas
+            .iter()
+            .map(|atlas| atlas.packer.occupancy())
+            .collect()
+    }
+
+    /// Wraps `string` into lines no wider than `max_width`, the lines are
+    /// `Metrics::line_height` apart.
+    pub fn layout(
+        &mut self,
+        string: &str,
+        max_width: f32,
+        align: Align,
+        style: FontStyle,
+    ) -> Layout {
+        self.layout_sized(string, self.font_size(), max_width, align, style)
+    }
+
+    /// Like [`TextRenderer::layout`] at `font_size` points. Glyphs of every
+    /// size share the atlases, so layouts of many sizes can be drawn in one
+    /// batch.
+    pub fn layout_sized(
+        &mut self,
+        string: &str,
+        font_size: f32,
+        max_width: f32,
+        align: Align,
+        style: FontStyle,
+    ) -> Layout {
+        self.text
+            .layout_sized(string, font_size, max_width, align, style)
+    }
+
+    /// Queues `string` with the top of its first line at `t_y`, lines only
+    /// end at line breaks like `'\n'`.
+    pub fn draw_string(&mut self, string: &str, t_x: i16, t_y: i16, color: Rgba, style: FontStyle) {
+        let effects = self.effects;
+        self.draw_string_with_effects(string, t_x, t_y, color, style, &effects);
+    }
+
+    /// Queues `string` with `effects` around it, like
+    /// [`TextRenderer::draw_string`].
+    pub fn draw_string_with_effects(
+        &mut self,
+        string: &str,
+        t_x: i16,
+        t_y: i16,
+        color: Rgba,
+        style: FontStyle,
+        effects: &TextEffects,
+    ) {
+        let layout = self.layout(string, f32::INFINITY, Align::Left, style);
+        self.queue(layout, t_x, t_y, color, self.mode, *effects);
+    }
+
+    /// Queues a layout with the top of its first line at `t_y`.
+    pub fn draw_layout(&mut self, layout: &Layout, t_x: i16, t_y: i16, color: Rgba) {
+        let effects = self.effects;
+        self.draw_layout_with_effects(layout, t_x, t_y, color, &effects);
+    }
+
+    /// Queues a layout with `effects` around it instead of the ones set by
+    /// [`TextRenderer::set_text_effects`]. Effects are drawn from distance
+    /// fields, so glyphs with effects are drawn from distance fields in
+    /// every mode.
+    pub fn draw_layout_with_effects(
+        &mut self,
+        layout: &Layout,
+        t_x: i16,
+        t_y: i16,
+        color: Rgba,
+        effects: &TextEffects,
+    ) {
+        self.queue(layout.clone(), t_x, t_y, color, self.mode, *effects);
+    }
+
+    /// Queues a layout in `mode` with `effects`, instead of the ones set on
+    /// the renderer.
+    pub(crate) fn draw_layout_in(
+        &mut self,
+        layout: Layout,
+        t_x: i16,
+        t_y: i16,
+        color: Rgba,
+        mode: GlyphMode,
+        effects: TextEffects,
+    ) {
+        self.queue(layout, t_x, t_y, color, mode, effects);
+    }
+
+    fn queue(
+        &mut self,
+        layout: Layout,
+        x: i16,
+        top: i16,
+        color: Rgba,
+        mode: GlyphMode,
+        effects: TextEffects,
+    ) {
+        self.queued.push(QueuedText {
+            layout,
+            x,
+            top,
+            color,
+            mode,
+            effects,
+        });
+    }
+
+    /// Draws the queued text.
+    pub fn flush(&mut self) {
+        self.batch_queued();
+        self.render_batch();
     }
 
-    pub fn draw_string(&mut self, string: &str, t_x: i16, t_y: i16, hex: i32) {
-        let mut x = t_x;
-        let mut y = t_y;
+    /// Adds the glyphs of the queued text to the batch. The batch is drawn
+    /// early when it is full, the glyphs use another atlas or glyphs are
+    /// evicted to make room.
+    fn batch_queued(&mut self) {
+        for text in std::mem::take(&mut self.queued) {
+            self.batch_text(&text);
+        }
+    }
+
+    fn batch_text(&mut self, text: &QueuedText) {
+        let fields =
+            text.mode == GlyphMode::DistanceField || text.effects != TextEffects::default();
 
-        // somehow, on windows and linux there is a small offset
-        if std::env::consts::OS == "windows" || std::env::consts::OS == "linux" {
-            if y - self.get_height() >= 0 {
-                y -= self.get_height();
+        // Fields are drawn before bitmaps, a batch only holds one of them to
+        // keep the order of the draws
+        let other_queued = if fields {
+            !self.batch.is_empty()
+        } else {
+            !self.field.instances.is_empty()
+        };
+        if other_queued {
+            self.render_batch();
+        }
+
+        if fields {
+            self.batch_fields(text);
+            return;
+        }
+
+        for line in &text.layout.lines {
+            let y = text.top + line.y.round() as i16;
+
+            for positioned in &line.glyphs {
+                let glyph = self.get_glyph(positioned.key);
+
+                if self.batch.len() == BATCH_MAX
+                    || (!self.batch.is_empty() && self.batch.tex != glyph.tex_id)
+                {
+                    self.render_batch();
+                }
+
+                let x = text.x + positioned.x.round() as i16;
+                let y = y - positioned.y.round() as i16 + cell_height(positioned.key.size) as i16;
+                self.batch.add_item(x, y, text.color, &glyph);
             }
         }
+    }
 
-        let glyphs = string
-            .chars()
-            .enumerate()
-            .map(|(_, character)| {
-                self.get_glyph(GlyphKey {
-                    character,
-                    font_key: self.font_key,
-                    size: self.size,
-                })
-            })
-            .collect::<Vec<_>>();
+    /// Adds the distance fields of the glyphs of a layout, which are scaled
+    /// from `FIELD_SIZE` to the size of every glyph.
+    fn batch_fields(&mut self, text: &QueuedText) {
+        let field_size = Size::new(FIELD_SIZE);
 
-        for glyph in glyphs {
-            let red = ((hex >> 16) & 0xFF) as u8;
-            let green = ((hex >> 8) & 0xFF) as u8;
-            let blue = (hex & 0xFF) as u8;
+        for line in &text.layout.lines {
+            let y = f32::from(text.top + line.y.round() as i16);
 
-            self.batch.add_item(x, y, red, green, blue, &glyph);
+            for positioned in &line.glyphs {
+                let key = ShapedKey {
+                    size: field_size,
+                    ..positioned.key
+                };
+                let glyph = self.cached_glyph((key, GlyphMode::DistanceField));
+                if glyph.width == 0 || glyph.height == 0 {
+                    continue;
+                }
 
-            if glyph.width <= 0 {
-                x += self.metrics.average_advance as i16 / 2;
+                let instances = &self.field.instances;
+                if instances.len() == BATCH_MAX
+                    || (!instances.is_empty() && self.field.tex != glyph.tex_id)
+                {
+                    self.render_batch();
+                }
+                self.field.tex = glyph.tex_id;
+
+                let scale = positioned.key.size.as_f32_pts() / FIELD_SIZE;
+                let x = f32::from(text.x) + positioned.x + f32::from(glyph.left) * scale;
+                let baseline = y + cell_height(positioned.key.size) - positioned.y;
+                self.field.instances.push(FieldInstance::new(
+                    [
+                        x,
+                        baseline - f32::from(glyph.top) * scale,
+                        f32::from(glyph.width) * scale,
+                        f32::from(glyph.height) * scale,
+                    ],
+                    [glyph.uv_left, glyph.uv_bot, glyph.uv_width, glyph.uv_height],
+                    text.color,
+                    scale,
+                    &text.effects,
+                ));
             }
+        }
+    }
 
-            x += glyph.width + self.spacing;
+    fn render_batch(&mut self) {
+        if !self.field.instances.is_empty() {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0);
+                gl::BindTexture(gl::TEXTURE_2D, self.field.tex);
+            }
+            self.active_tex = self.field.tex;
+            self.field.draw();
         }
 
-        self.render_batch();
-    }
+        if self.batch.is_empty() {
+            return;
+        }
 
-    pub fn render_batch(&mut self) {
         unsafe {
             gl::UseProgram(self.program.id);
 
@@ -419,11 +777,6 @@ impl TextRenderer {
                 self.active_tex = self.batch.tex;
             }
 
-            gl::Uniform2f(
-                gl::GetUniformLocation(self.program.id, b"cellDim\0".as_ptr() as *const _),
-                self.size.as_f32_pts(),
-                self.size.as_f32_pts() * 2.,
-            );
             gl::DrawElementsInstanced(
                 gl::TRIANGLES,
                 6,
@@ -444,64 +797,259 @@ impl TextRenderer {
         }
     }
 
-    pub fn get_glyph(&mut self, key: GlyphKey) -> Glyph {
-        if let Some(glyph) = self.cache.get(&key) {
-            return *glyph;
-        };
+    pub fn get_glyph(&mut self, key: ShapedKey) -> Glyph {
+        self.cached_glyph((key, GlyphMode::Bitmap))
+    }
 
-        let glyph = match self.rasterizer.get_glyph(key) {
-            Ok(rasterized) => self.load_glyph(rasterized),
+    fn cached_glyph(&mut self, key: CacheKey) -> Glyph {
+        self.clock += 1;
+        match self.cache.get_mut(&key) {
+            Some(Cached::Loaded { glyph, used, .. }) => {
+                *used = self.clock;
+                return *glyph;
+            }
+            Some(Cached::Missing { used }) => {
+                *used = self.clock;
+                return self.cached_glyph(missing_key(key));
+            }
+            None => {}
+        }
+
+        match self.rasterize(key) {
+            Ok(rasterized) => self.load_glyph(key, rasterized),
             Err(RasterizerError::MissingGlyph(rasterized)) => {
-                let missing_key = GlyphKey {
-                    character: '\0',
-                    ..key
-                };
-                if let Some(glyph) = self.cache.get(&missing_key) {
-                    *glyph
-                } else {
-                    let glyph = self.load_glyph(rasterized);
-                    self.cache.insert(missing_key, glyph);
+                let missing_key = missing_key(key);
+                if missing_key == key {
+                    return self.load_glyph(key, rasterized);
+                }
 
-                    glyph
+                self.insert_missing(key);
+                if self.cache.contains_key(&missing_key) {
+                    self.cached_glyph(missing_key)
+                } else {
+                    self.load_glyph(missing_key, rasterized)
                 }
             }
-            Err(_) => self.load_glyph(Default::default()),
+            Err(_) => self.load_glyph(key, Default::default()),
+        }
+    }
+
+    /// Caches that the font has no glyph for `key`, after dropping the least
+    /// recently used half of these keys once there are `MISSING_MAX`.
+    fn insert_missing(&mut self, key: CacheKey) {
+        if self.missing >= MISSING_MAX {
+            let mut oldest: Vec<(u64, CacheKey)> = self
+                .cache
+                .iter()
+                .filter_map(|(key, cached)| match cached {
+                    Cached::Missing { used } => Some((*used, *key)),
+                    Cached::Loaded { .. } => None,
+                })
+                .collect();
+            oldest.sort_unstable_by_key(|(used, _)| *used);
+            for (_, key) in oldest.into_iter().take(MISSING_MAX / 2) {
+                self.forget(key);
+            }
+        }
+
+        self.cache.insert(key, Cached::Missing { used: self.clock });
+        self.missing += 1;
+    }
+
+    /// Rasterizes a glyph for its mode.
+    fn rasterize(&mut self, (key, mode): CacheKey) -> Result<RasterizedGlyph, RasterizerError> {
+        match mode {
+            GlyphMode::Bitmap => self.text.shaper.rasterize(key),
+            GlyphMode::DistanceField => sdf::rasterize(&mut self.text.shaper, key),
+        }
+    }
+
+    /// Puts a glyph into an atlas and caches it under `key`.
+    fn load_glyph(&mut self, key: CacheKey, rasterized: RasterizedGlyph) -> Glyph {
+        let (glyph, atlas, allocation) = self.insert_glyph(&rasterized);
+        self.cache.insert(
+            key,
+            Cached::Loaded {
+                glyph,
+                atlas,
+                allocation,
+                used: self.clock,
+            },
+        );
+
+        glyph
+    }
+
+    /// Finds room in the first atlas with enough of it, a new atlas is only
+    /// created within the budget.
+    fn insert_glyph(&mut self, rasterized: &RasterizedGlyph) -> (Glyph, usize, Allocation) {
+        for index in 0..self.atlas.len() {
+            if let Some(inserted) = self.insert_into(index, rasterized) {
+                return inserted;
+            }
+        }
+
+        if self.atlas.len() < self.max_atlases() {
+            self.active_tex = 0;
+            self.atlas.push(Atlas::new(ATLAS_SIZE));
+            if let Some(inserted) = self.insert_into(self.atlas.len() - 1, rasterized) {
+                return inserted;
+            }
+        }
+
+        self.evict_for(rasterized)
+    }
+
+    /// Inserts a glyph into one atlas unless it is full. Glyphs too large
+    /// for any atlas are not drawn.
+    fn insert_into(
+        &mut self,
+        index: usize,
+        rasterized: &RasterizedGlyph,
+    ) -> Option<(Glyph, usize, Allocation)> {
+        match self.atlas[index].insert(rasterized, &mut self.active_tex) {
+            Ok((glyph, allocation)) => Some((glyph, index, allocation)),
+            Err(AtlasError::Full) => None,
+            Err(AtlasError::TooLarge | AtlasError::InvalidImage) => Some(self.empty_glyph(index)),
+        }
+    }
+
+    /// A glyph without pixels, drawn in place of one that has no room.
+    fn empty_glyph(&self, index: usize) -> (Glyph, usize, Allocation) {
+        let glyph = Glyph {
+            tex_id: self.atlas[index].id,
+            multicolor: false,
+            top: 0,
+            left: 0,
+            width: 0,
+            height: 0,
+            uv_bot: 0.,
+            uv_left: 0.,
+            uv_width: 0.,
+            uv_height: 0.,
         };
+        (glyph, index, Allocation::default())
+    }
+
+    /// Evicts the least recently used glyphs until `rasterized` fits into
+    /// the atlas of the last one evicted. An atlas whose free room is too
+    /// scattered for it is packed again first.
+    fn evict_for(&mut self, rasterized: &RasterizedGlyph) -> (Glyph, usize, Allocation) {
+        // Queued glyphs may be evicted
+        self.render_batch();
+
+        let mut oldest: Vec<(u64, CacheKey)> = self
+            .cache
+            .iter()
+            .filter_map(|(key, cached)| match cached {
+                Cached::Loaded { used, .. } => Some((*used, *key)),
+                Cached::Missing { .. } => None,
+            })
+            .collect();
+        oldest.sort_unstable_by_key(|(used, _)| *used);
+
+        let mut compacted = vec![false; self.atlas.len()];
+        for (_, key) in oldest {
+            let index = match self.forget(key) {
+                Some((index, allocation)) => {
+                    self.atlas[index].packer.free(allocation);
+                    index
+                }
+                None => continue,
+            };
+            if let Some(inserted) = self.insert_into(index, rasterized) {
+                return inserted;
+            }
+
+            if !compacted[index] && self.atlas[index].packer.occupancy().ratio() < COMPACT_RATIO {
+                compacted[index] = true;
+                self.compact(index);
+                if let Some(inserted) = self.insert_into(index, rasterized) {
+                    return inserted;
+                }
+            }
+        }
 
-        *self.cache.entry(key).or_insert(glyph)
+        // Every glyph was evicted, so the atlases are empty. A glyph without
+        // room even then is drawn empty, like one larger than an atlas.
+        for atlas in &mut self.atlas {
+            atlas.clear();
+        }
+        self.insert_into(0, rasterized)
+            .unwrap_or_else(|| self.empty_glyph(0))
     }
 
-    pub fn load_glyph(&mut self, rasterized: RasterizedGlyph) -> Glyph {
-        match self.atlas[self.current_atlas].insert(&rasterized, &mut self.active_tex) {
-            Ok(glyph) => glyph,
-            Err(AtlasInsertError::Full) => {
-                self.current_atlas += 1;
-                if self.current_atlas == self.atlas.len() {
-                    let new = Atlas::new(ATLAS_SIZE);
-                    self.active_tex = 0;
-                    self.atlas.push(new);
+    /// Packs the glyphs of an atlas again, tallest first, which joins the
+    /// free room scattered between them.
+    fn compact(&mut self, index: usize) {
+        let mut kept: Vec<(i16, CacheKey)> = self
+            .cache
+            .iter()
+            .filter_map(|(key, cached)| match cached {
+                Cached::Loaded { glyph, atlas, .. } if *atlas == index => {
+                    Some((glyph.height, *key))
+                }
+                _ => None,
+            })
+            .collect();
+        kept.sort_unstable_by_key(|(height, _)| Reverse(*height));
+
+        self.atlas[index].clear();
+        for (_, key) in kept {
+            let rasterized = match self.rasterize(key) {
+                Ok(rasterized) | Err(RasterizerError::MissingGlyph(rasterized)) => rasterized,
+                Err(_) => Default::default(),
+            };
+
+            match self.insert_into(index, &rasterized) {
+                Some((new, _, new_allocation)) => {
+                    if let Some(Cached::Loaded {
+                        glyph, allocation, ..
+                    }) = self.cache.get_mut(&key)
+                    {
+                        *glyph = new;
+                        *allocation = new_allocation;
+                    }
+                }
+                None => {
+                    self.forget(key);
                 }
-                self.load_glyph(rasterized)
             }
-            Err(AtlasInsertError::GlyphTooLarge) => Glyph {
-                tex_id: self.atlas[self.current_atlas].id,
-                multicolor: false,
-                top: 0,
-                left: 0,
-                width: 0,
-                height: 0,
-                uv_bot: 0.,
-                uv_left: 0.,
-                uv_width: 0.,
-                uv_height: 0.,
-            },
         }
     }
+
+    /// Removes a glyph from the cache, with the keys drawn with it when it
+    /// is a missing glyph. Returns where it was loaded.
+    fn forget(&mut self, key: CacheKey) -> Option<(usize, Allocation)> {
+        let loaded = match self.cache.remove(&key)? {
+            Cached::Loaded {
+                atlas, allocation, ..
+            } => (atlas, allocation),
+            Cached::Missing { .. } => {
+                self.missing -= 1;
+                return None;
+            }
+        };
+
+        if missing_key(key) == key {
+            let len = self.cache.len();
+            self.cache.retain(|other, cached| {
+                !matches!(cached, Cached::Missing { .. }) || missing_key(*other) != key
+            });
+            self.missing -= len - self.cache.len();
+        }
+
+        Some(loaded)
+    }
 }
 
-enum AtlasInsertError {
-    Full,
-    GlyphTooLarge,
+/// The key of the glyph drawn when a font has no glyph for `key`.
+fn missing_key((key, mode): CacheKey) -> CacheKey {
+    let key = ShapedKey {
+        id: GlyphId::Char('\0'),
+        ..key
+    };
+    (key, mode)
 }
 
 impl Atlas {
@@ -533,87 +1081,74 @@ impl Atlas {
 
         Self {
             id,
-            width: size,
-            height: size,
-            row_extent: 0,
-            row_baseline: 0,
-            row_tallest: 0,
+            size,
+            packer: SkylinePacker::new(size, size, GLYPH_PADDING),
         }
     }
 
-    #[allow(dead_code)]
     pub fn clear(&mut self) {
-        self.row_extent = 0;
-        self.row_baseline = 0;
-        self.row_tallest = 0;
+        self.packer.clear();
     }
 
     pub fn insert(
         &mut self,
         glyph: &RasterizedGlyph,
         active_tex: &mut u32,
-    ) -> Result<Glyph, AtlasInsertError> {
-        if glyph.width > self.width || glyph.height > self.height {
-            return Err(AtlasInsertError::GlyphTooLarge);
-        }
-
-        if !self.room_in_row(glyph) {
-            self.advance_row()?;
-        }
-
-        if !self.room_in_row(glyph) {
-            return Err(AtlasInsertError::Full);
-        }
-
-        Ok(self.insert_inner(glyph, active_tex))
+    ) -> Result<(Glyph, Allocation), AtlasError> {
+        let allocation = self.packer.insert(glyph.width, glyph.height)?;
+        Ok((self.insert_inner(glyph, allocation, active_tex), allocation))
     }
 
-    fn insert_inner(&mut self, glyph: &RasterizedGlyph, active_tex: &mut u32) -> Glyph {
-        let offset_y = self.row_baseline;
-        let offset_x = self.row_extent;
-        let height = glyph.height as i32;
-        let width = glyph.width as i32;
+    fn insert_inner(
+        &mut self,
+        glyph: &RasterizedGlyph,
+        allocation: Allocation,
+        active_tex: &mut u32,
+    ) -> Glyph {
+        let (offset_x, offset_y) = (allocation.x, allocation.y);
+        let height = glyph.height;
+        let width = glyph.width;
         let multicolor;
 
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D, self.id);
 
-            let (format, buffer) = match &glyph.buffer {
+            let (format, bytes, buffer) = match &glyph.buffer {
                 BitmapBuffer::Rgb(buffer) => {
                     multicolor = false;
-                    (gl::RGB, buffer)
+                    (gl::RGB, 3, buffer)
                 }
                 BitmapBuffer::Rgba(buffer) => {
                     multicolor = true;
-                    (gl::RGBA, buffer)
+                    (gl::RGBA, 4, buffer)
                 }
             };
 
-            gl::TexSubImage2D(
-                gl::TEXTURE_2D,
-                0,
-                offset_x,
-                offset_y,
-                width,
-                height,
-                format,
-                gl::UNSIGNED_BYTE,
-                buffer.as_ptr() as *const _,
-            );
+            // The padding is cleared too, it may hold an old glyph
+            if width > 0 && height > 0 {
+                let padded = padded(buffer, width, height, bytes);
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    offset_x - GLYPH_PADDING,
+                    offset_y - GLYPH_PADDING,
+                    width + GLYPH_PADDING * 2,
+                    height + GLYPH_PADDING * 2,
+                    format,
+                    gl::UNSIGNED_BYTE,
+                    padded.as_ptr() as *const _,
+                );
+            }
 
             gl::BindTexture(gl::TEXTURE_2D, 0);
             *active_tex = 0;
         }
 
-        self.row_extent += width;
-        if height > self.row_tallest {
-            self.row_tallest = height;
-        }
-
-        let uv_bot = offset_y as f32 / self.height as f32;
-        let uv_left = offset_x as f32 / self.width as f32;
-        let uv_height = height as f32 / self.height as f32;
-        let uv_width = width as f32 / self.width as f32;
+        let (atlas_width, atlas_height) = (self.size, self.size);
+        let uv_bot = offset_y as f32 / atlas_height as f32;
+        let uv_left = offset_x as f32 / atlas_width as f32;
+        let uv_height = height as f32 / atlas_height as f32;
+        let uv_width = width as f32 / atlas_width as f32;
 
         Glyph {
             tex_id: self.id,
@@ -628,27 +1163,20 @@ impl Atlas {
             uv_height,
         }
     }
+}
 
-    fn room_in_row(&self, raw: &RasterizedGlyph) -> bool {
-        let next_extent = self.row_extent + raw.width as i32;
-        let enough_width = next_extent <= self.width;
-        let enough_height = (raw.height as i32) < (self.height - self.row_baseline);
+/// A copy of `buffer` inside a transparent border of `GLYPH_PADDING`.
+fn padded(buffer: &[u8], width: i32, height: i32, bytes: usize) -> Vec<u8> {
+    let (width, height, padding) = (width as usize, height as usize, GLYPH_PADDING as usize);
+    let stride = (width + padding * 2) * bytes;
+    let mut padded = vec![0; stride * (height + padding * 2)];
 
-        enough_width && enough_height
+    for (row, line) in buffer.chunks_exact(width * bytes).enumerate() {
+        let start = (row + padding) * stride + padding * bytes;
+        padded[start..start + line.len()].copy_from_slice(line);
     }
 
-    fn advance_row(&mut self) -> Result<(), AtlasInsertError> {
-        let advance_to = self.row_baseline + self.row_tallest;
-        if self.height - advance_to <= 0 {
-            return Err(AtlasInsertError::Full);
-        }
-
-        self.row_baseline = advance_to;
-        self.row_extent = 0;
-        self.row_tallest = 0;
-
-        Ok(())
-    }
+    padded
 }
 
 impl Drop for Atlas {
@@ -658,3 +1186,423 @@ impl Drop for Atlas {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{assert_golden, compare, render, TOLERANCE};
+
+    #[test]
+    fn draw_string() {
+        let image = render(256, 64, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 16., size, 1.).unwrap();
+            font.draw_string(
+                "Hello, World!",
+                8,
+                14,
+                Rgb::from_hex(0xFFFFFF).into(),
+                FontStyle::REGULAR,
+            );
+            font.draw_string(
+                "0xC0FFEE",
+                140,
+                14,
+                Rgb::from_hex(0xC0FFEE).into(),
+                FontStyle::REGULAR,
+            );
+            font.flush();
+        });
+
+        assert_golden("draw_string", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn draws_are_queued() {
+        use crate::renderer::{RenderRect, Renderer};
+
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        let image = render(128, 32, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 16., size, 1.).unwrap();
+            font.draw_string("Hidden", 8, 24, white, FontStyle::REGULAR);
+
+            // Text queued before a shape is below it once flushed first
+            let mut renderer = Renderer::new(size).unwrap();
+            renderer.rectangle(&RenderRect {
+                x: 0.,
+                y: 0.,
+                width: 128.,
+                height: 32.,
+                color: Rgb::from_hex(0x000000).into(),
+            });
+            font.flush();
+            renderer.draw();
+
+            font.draw_string("Never flushed", 8, 24, white, FontStyle::REGULAR);
+        });
+
+        assert!(image.pixels.chunks(4).all(|pixel| pixel[..3] == [0, 0, 0]));
+    }
+
+    #[test]
+    fn atlas_rows() {
+        // Enough glyphs at a large size to spill into several atlas rows
+        let image = render(512, 256, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 48., size, 1.).unwrap();
+            for (i, line) in ["ABCDEFGHIJKLM", "nopqrstuvwxyz", "0123456789@&%"]
+                .iter()
+                .enumerate()
+            {
+                font.draw_string(
+                    line,
+                    8,
+                    i as i16 * 72,
+                    Rgb::from_hex(0xFFFFFF).into(),
+                    FontStyle::REGULAR,
+                );
+            }
+            font.flush();
+        });
+
+        assert_golden("atlas_rows", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn styles() {
+        let image = render(256, 160, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 16., size, 1.).unwrap();
+            let styles = [
+                FontStyle::REGULAR,
+                FontStyle::BOLD,
+                FontStyle::ITALIC,
+                FontStyle::BOLD_ITALIC,
+                FontStyle::new(200, false),
+            ];
+            for (i, style) in styles.iter().enumerate() {
+                font.draw_string(
+                    "Sphinx of quartz",
+                    8,
+                    2 + i as i16 * 28,
+                    Rgb::from_hex(0xFFFFFF).into(),
+                    *style,
+                );
+            }
+            font.flush();
+        });
+
+        assert_golden("styles", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn layout() {
+        let text = "The quick brown fox jumps over the lazy dog.\nPack my box.";
+        let image = render(512, 256, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 12., size, 1.).unwrap();
+            let aligns = [Align::Left, Align::Center, Align::Right, Align::Justify];
+            for (i, align) in aligns.iter().enumerate() {
+                let layout = font.layout(text, 112., *align, FontStyle::REGULAR);
+                font.draw_layout(
+                    &layout,
+                    8 + i as i16 * 128,
+                    8,
+                    Rgb::from_hex(0xFFFFFF).into(),
+                );
+            }
+            font.flush();
+        });
+
+        assert_golden("layout", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn resize() {
+        let draw = |font: &mut TextRenderer| {
+            font.draw_string(
+                "Hello, World!",
+                8,
+                14,
+                Rgb::from_hex(0xFFFFFF).into(),
+                FontStyle::REGULAR,
+            );
+            font.flush();
+        };
+
+        let resized = render(256, 64, |size| {
+            let half = Vec2f {
+                x: size.x / 2.,
+                y: size.y / 2.,
+            };
+            let mut font = TextRenderer::new("DejaVu Sans", 8., half, 1.).unwrap();
+            draw(&mut font);
+            font.resize(size, 2.);
+            unsafe {
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            draw(&mut font);
+        });
+        let fresh = render(256, 64, |size| {
+            draw(&mut TextRenderer::new("DejaVu Sans", 8., size, 2.).unwrap());
+        });
+
+        let (mismatched, _) = compare(&fresh, &resized, TOLERANCE);
+        assert_eq!(mismatched, 0);
+    }
+
+    #[test]
+    fn sizes() {
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        let heading = |font: &mut TextRenderer, size: f32| {
+            font.layout_sized(
+                "Heading",
+                size,
+                f32::INFINITY,
+                Align::Left,
+                FontStyle::REGULAR,
+            )
+        };
+        let body = |font: &mut TextRenderer, size: f32| {
+            font.layout_sized(
+                "body text",
+                size,
+                f32::INFINITY,
+                Align::Left,
+                FontStyle::REGULAR,
+            )
+        };
+
+        // One batch of glyphs at two sizes from the same atlas
+        let mixed = render(256, 96, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 16., size, 1.).unwrap();
+            let (heading, body) = (heading(&mut font, 32.), body(&mut font, 12.));
+            font.draw_layout(&heading, 8, 48, white);
+            font.draw_layout(&body, 8, 61, white);
+            font.flush();
+
+            assert_eq!(font.atlas_occupancy().len(), 1);
+            assert_eq!(font.text.metrics.len(), 3);
+            assert_eq!(font.font_size(), 16.);
+        });
+        let separate = render(256, 96, |size| {
+            let mut large = TextRenderer::new("DejaVu Sans", 32., size, 1.).unwrap();
+            let layout = heading(&mut large, 32.);
+            large.draw_layout(&layout, 8, 48, white);
+            large.flush();
+            let mut small = TextRenderer::new("DejaVu Sans", 12., size, 1.).unwrap();
+            let layout = body(&mut small, 12.);
+            small.draw_layout(&layout, 8, 61, white);
+            small.flush();
+        });
+
+        let (mismatched, _) = compare(&separate, &mixed, TOLERANCE);
+        assert_eq!(mismatched, 0);
+    }
+
+    #[test]
+    fn distance_field() {
+        use crate::renderer::sdf::{Glow, Outline, Shadow};
+
+        let image = render(512, 288, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 16., size, 1.).unwrap();
+            font.set_glyph_mode(GlyphMode::DistanceField);
+            let draw = |font: &mut TextRenderer, text: &str, font_size: f32, y: i16| {
+                let layout =
+                    font.layout_sized(text, font_size, f32::INFINITY, Align::Left, FontStyle::BOLD);
+                font.draw_layout(&layout, 16, y, Rgb::from_hex(0xFFFFFF).into());
+            };
+
+            draw(&mut font, "Distance fields at 12pt", 12., 0);
+            draw(&mut font, "and scaled up", 32., 12);
+
+            font.set_text_effects(TextEffects {
+                outline: Some(Outline {
+                    width: 2.,
+                    color: Rgb::from_hex(0xE04060).into(),
+                }),
+                ..TextEffects::default()
+            });
+            draw(&mut font, "Outline", 40., 59);
+
+            font.set_text_effects(TextEffects {
+                glow: Some(Glow {
+                    radius: 8.,
+                    color: Rgb::from_hex(0x40A0E0).into(),
+                }),
+                ..TextEffects::default()
+            });
+            draw(&mut font, "Glow", 40., 123);
+
+            font.set_text_effects(TextEffects {
+                shadow: Some(Shadow {
+                    offset: Vec2f { x: 4., y: 4. },
+                    blur: 6.,
+                    color: Rgb::from_hex(0xA0A0A0).alpha(192),
+                }),
+                ..TextEffects::default()
+            });
+            draw(&mut font, "Soft shadow", 40., 123 + 62);
+            font.flush();
+
+            // Fields are generated once and scaled to every size
+            let fields = font
+                .cache
+                .keys()
+                .filter(|(_, mode)| *mode == GlyphMode::DistanceField)
+                .all(|(key, _)| key.size == Size::new(FIELD_SIZE));
+            assert!(fields);
+        });
+
+        assert_golden("distance_field", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn text_effects() {
+        use crate::renderer::sdf::{Glow, Outline, Shadow};
+        use crate::renderer::{RenderRect, Renderer};
+
+        let image = render(256, 160, |size| {
+            // Text as bright as the background is only readable by its effects
+            let mut renderer = Renderer::new(size).unwrap();
+            renderer.rectangle(&RenderRect {
+                x: 0.,
+                y: 0.,
+                width: 256.,
+                height: 160.,
+                color: Rgb::from_hex(0xE0E0D0).into(),
+            });
+            renderer.draw();
+
+            let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+            let mut font = TextRenderer::new("DejaVu Sans", 24., size, 1.).unwrap();
+            let mut draw = |text: &str, y: i16, effects: &TextEffects| {
+                let layout = font.layout(text, f32::INFINITY, Align::Left, FontStyle::BOLD);
+                font.draw_layout_with_effects(&layout, 16, y, white, effects);
+                layout.lines[0].glyphs.len()
+            };
+
+            let outline = TextEffects {
+                outline: Some(Outline {
+                    width: 2.,
+                    color: Rgb::from_hex(0x202020).into(),
+                }),
+                ..TextEffects::default()
+            };
+            let shadow = TextEffects {
+                shadow: Some(Shadow {
+                    offset: Vec2f { x: 2., y: 3. },
+                    blur: 4.,
+                    color: Rgb::from_hex(0x000000).alpha(192),
+                }),
+                ..TextEffects::default()
+            };
+            let glow = TextEffects {
+                glow: Some(Glow {
+                    radius: 6.,
+                    color: Rgb::from_hex(0x2060C0).into(),
+                }),
+                ..TextEffects::default()
+            };
+            let glyphs = draw("Outlined", 10, &outline)
+                + draw("Shadowed", 54, &shadow)
+                + draw("Glowing", 98, &glow);
+
+            // Draws with other effects share a batch
+            font.batch_queued();
+            assert_eq!(font.field.instances.len(), glyphs);
+            font.flush();
+
+            // Glyphs without effects stay bitmaps
+            font.draw_string(
+                "plain",
+                180,
+                10,
+                Rgb::from_hex(0x202020).into(),
+                FontStyle::REGULAR,
+            );
+            font.flush();
+            assert_eq!(font.glyph_mode(), GlyphMode::Bitmap);
+        });
+
+        assert_golden("text_effects", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn missing_glyphs() {
+        render(64, 64, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 16., size, 1.).unwrap();
+            let font_key = font.text.font_key(FontStyle::REGULAR);
+            let glyphs = font.memory_usage().glyphs;
+
+            // Private use characters no font has a glyph for
+            for character in ('\u{F0000}'..).take(MISSING_MAX + 1) {
+                font.get_glyph(ShapedKey {
+                    font_key,
+                    id: GlyphId::Char(character),
+                    size: font.text.size(),
+                });
+            }
+
+            let usage = font.memory_usage();
+            assert_eq!(usage.missing, MISSING_MAX / 2 + 1);
+            // Only the missing glyph itself takes room
+            assert_eq!(usage.glyphs, glyphs + 1);
+        });
+    }
+
+    #[test]
+    fn eviction() {
+        let white: Rgba = Rgb::from_hex(0xFFFFFF).into();
+        let draw = |font: &mut TextRenderer| {
+            font.draw_string("Ag&", 8, 300, white, FontStyle::REGULAR);
+            font.flush();
+        };
+
+        let evicted = render(512, 320, |size| {
+            let mut font = TextRenderer::new("DejaVu Sans", 200., size, 1.).unwrap();
+            font.set_memory_budget(Some(0));
+
+            // Far more large glyphs than fit into one atlas, with one used
+            // all the time
+            let characters: Vec<char> = ('\u{C0}'..='\u{24F}').collect();
+            for chunk in characters.chunks(16) {
+                let string: String = chunk.iter().chain(['A'].iter()).collect();
+                font.draw_string(&string, 0, 0, white, FontStyle::REGULAR);
+                font.flush();
+            }
+
+            let usage = font.memory_usage();
+            assert_eq!(usage.atlases, 1);
+            assert_eq!(usage.texture_bytes, ATLAS_BYTES);
+            assert!(usage.glyph_bytes <= ATLAS_BYTES);
+            assert!(usage.glyphs < characters.len());
+
+            let layout = font.layout("A", f32::INFINITY, Align::Left, FontStyle::REGULAR);
+            let key = layout.lines[0].glyphs[0].key;
+            let key = (key, GlyphMode::Bitmap);
+            assert!(matches!(font.cache.get(&key), Some(Cached::Loaded { .. })));
+
+            // A glyph larger than an atlas is drawn empty, and evicts nothing
+            let glyphs = font.memory_usage().glyphs;
+            let huge = RasterizedGlyph {
+                width: ATLAS_SIZE + 1,
+                height: 1,
+                buffer: BitmapBuffer::Rgb(vec![0; (ATLAS_SIZE + 1) as usize * 3]),
+                ..Default::default()
+            };
+            let (glyph, _, allocation) = font.insert_glyph(&huge);
+            assert_eq!((glyph.width, glyph.height), (0, 0));
+            assert_eq!(allocation, Allocation::default());
+            assert_eq!(font.memory_usage().glyphs, glyphs);
+
+            unsafe {
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            draw(&mut font);
+        });
+        let fresh = render(512, 320, |size| {
+            draw(&mut TextRenderer::new("DejaVu Sans", 200., size, 1.).unwrap());
+        });
+
+        assert!(fresh.pixels.iter().any(|channel| *channel > 0));
+        let (mismatched, _) = compare(&fresh, &evicted, TOLERANCE);
+        assert_eq!(mismatched, 0);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::renderer::shaping::FontStyle;
+
+use crossfont::Error as RasterizerError;
+
+use std::path::PathBuf;
+
+/// A font file and the face in it that matched a family and style.
+#[derive(Debug, Clone)]
+pub struct FontFile {
+    pub path: PathBuf,
+    pub index: u32,
+    /// Name of the matched style, like "Bold Oblique" or "ExtraLight".
+    pub style: Option<String>,
+}
+
+/// Converts an OpenType weight (100 to 950) into a fontconfig weight.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn fc_weight(weight: u16) -> i32 {
+    use fontconfig::fontconfig as fc;
+
+    const MAP: [(u16, i32); 11] = [
+        (100, fc::FC_WEIGHT_THIN),
+        (200, fc::FC_WEIGHT_EXTRALIGHT),
+        (300, fc::FC_WEIGHT_LIGHT),
+        (350, fc::FC_WEIGHT_BOOK),
+        (400, fc::FC_WEIGHT_REGULAR),
+        (500, fc::FC_WEIGHT_MEDIUM),
+        (600, fc::FC_WEIGHT_DEMIBOLD),
+        (700, fc::FC_WEIGHT_BOLD),
+        (800, fc::FC_WEIGHT_EXTRABOLD),
+        (900, fc::FC_WEIGHT_BLACK),
+        (950, fc::FC_WEIGHT_EXTRABLACK),
+    ];
+
+    let weight = weight.clamp(MAP[0].0, MAP[MAP.len() - 1].0);
+    let upper = MAP.iter().position(|&(ot, _)| ot >= weight).unwrap();
+    if upper == 0 {
+        return MAP[0].1;
+    }
+
+    let ((ot_low, fc_low), (ot_high, fc_high)) = (MAP[upper - 1], MAP[upper]);
+    fc_low + (fc_high - fc_low) * i32::from(weight - ot_low) / i32::from(ot_high - ot_low)
+}
+
+/// Finds the file and face index fontconfig picks for a family and style.
+///
+/// This runs the same match as crossfont, so the file is the one its
+/// `Rasterizer` loaded for the `FontKey`.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn locate(family: &str, style: FontStyle) -> Result<FontFile, RasterizerError> {
+    use fontconfig::fontconfig as fc;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::c_char;
+
+    let not_found = || RasterizerError::PlatformError(format!("no font file for {}", family));
+    let family = CString::new(family).map_err(|_| not_found())?;
+
+    let slant = if style.italic {
+        fc::FC_SLANT_ITALIC
+    } else {
+        fc::FC_SLANT_ROMAN
+    };
+    let weight = fc_weight(style.weight);
+
+    unsafe {
+        let config = fc::FcConfigGetCurrent();
+        let pattern = fc::FcPatternCreate();
+
+        fc::FcPatternAddString(
+            pattern,
+            b"family\0".as_ptr() as *const c_char,
+            family.as_ptr() as *const _,
+        );
+        fc::FcPatternAddInteger(pattern, b"slant\0".as_ptr() as *const c_char, slant);
+        fc::FcPatternAddInteger(pattern, b"weight\0".as_ptr() as *const c_char, weight);
+        fc::FcConfigSubstitute(config, pattern, fc::FcMatchPattern);
+        fc::FcDefaultSubstitute(pattern);
+
+        let mut result = fc::FcResultMatch;
+        let matched = fc::FcFontMatch(config, pattern, &mut result);
+        fc::FcPatternDestroy(pattern);
+
+        if matched.is_null() {
+            return Err(not_found());
+        }
+
+        let get_string = |object: &[u8]| {
+            let mut value: *mut fc::FcChar8 = std::ptr::null_mut();
+            if fc::FcPatternGetString(matched, object.as_ptr() as *const c_char, 0, &mut value)
+                == fc::FcResultMatch
+            {
+                Some(
+                    CStr::from_ptr(value as *const c_char)
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+            } else {
+                None
+            }
+        };
+
+        let mut index = 0;
+        fc::FcPatternGetInteger(matched, b"index\0".as_ptr() as *const c_char, 0, &mut index);
+        let path = get_string(b"file\0");
+        let style = get_string(b"style\0");
+        fc::FcPatternDestroy(matched);
+
+        path.map(|path| FontFile {
+            path: PathBuf::from(path),
+            index: index as u32,
+            style,
+        })
+        .ok_or_else(not_found)
+    }
+}
+
+/// Finds the file and face index of a family and style in the font
+/// directories of the system.
+///
+/// Without fontconfig the file crossfont loaded can't be asked for, so the
+/// face whose family matches and whose style is closest is used.
+#[cfg(any(target_os = "macos", windows))]
+pub fn locate(family: &str, style: FontStyle) -> Result<FontFile, RasterizerError> {
+    use std::sync::OnceLock;
+
+    static FACES: OnceLock<Vec<IndexedFace>> = OnceLock::new();
+    let faces = FACES.get_or_init(|| index_faces(&font_dirs()));
+
+    best_match(faces, family, style)
+        .ok_or_else(|| RasterizerError::PlatformError(format!("no font file for {}", family)))
+}
+
+#[cfg(target_os = "macos")]
+fn font_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![
+        PathBuf::from("/System/Library/Fonts"),
+        PathBuf::from("/Library/Fonts"),
+    ];
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join("Library/Fonts"));
+    }
+    dirs
+}
+
+#[cfg(windows)]
+fn font_dirs() -> Vec<PathBuf> {
+    let windows = std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+    let mut dirs = vec![PathBuf::from(windows).join("Fonts")];
+    if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+        dirs.push(PathBuf::from(local).join("Microsoft\\Windows\\Fonts"));
+    }
+    dirs
+}
+
+/// A face of a font file found in a font directory.
+#[cfg(any(target_os = "macos", windows, test))]
+#[derive(Debug)]
+struct IndexedFace {
+    file: FontFile,
+    families: Vec<String>,
+    /// Narrower or wider than normal.
+    stretched: bool,
+    weight: u16,
+    italic: bool,
+}
+
+/// The faces of the font files in `dirs` and their subdirectories.
+#[cfg(any(target_os = "macos", windows, test))]
+fn index_faces(dirs: &[PathBuf]) -> Vec<IndexedFace> {
+    use swash::{Attributes, FontDataRef, Stretch, StringId, Style as Slope};
+
+    let mut faces = Vec::new();
+    let mut pending: Vec<PathBuf> = dirs.to_vec();
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for path in entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+        {
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            let is_font = extension.is_some_and(|extension| {
+                ["ttf", "otf", "ttc", "otc"]
+                    .iter()
+                    .any(|font| extension.eq_ignore_ascii_case(font))
+            });
+            let data = match is_font.then(|| std::fs::read(&path)) {
+                Some(Ok(data)) => data,
+                _ => continue,
+            };
+            let fonts = match FontDataRef::new(&data) {
+                Some(fonts) => fonts,
+                None => continue,
+            };
+
+            for (index, font) in fonts.fonts().enumerate() {
+                let strings = font.localized_strings();
+                let families = strings
+                    .filter(|string| {
+                        matches!(string.id(), StringId::Family | StringId::TypographicFamily)
+                    })
+                    .map(|string| string.to_string())
+                    .collect();
+                let style = strings
+                    .find_by_id(StringId::TypographicSubFamily, None)
+                    .or_else(|| strings.find_by_id(StringId::SubFamily, None))
+                    .map(|string| string.to_string());
+                let attributes: Attributes = font.attributes();
+
+                faces.push(IndexedFace {
+                    file: FontFile {
+                        path: path.clone(),
+                        index: index as u32,
+                        style,
+                    },
+                    families,
+                    stretched: attributes.stretch() != Stretch::NORMAL,
+                    weight: attributes.weight().0,
+                    italic: attributes.style() != Slope::Normal,
+                });
+            }
+        }
+    }
+
+    faces
+}
+
+/// The face of `family` with the slant of `style` and the closest weight,
+/// preferring faces of normal width. Any slant is used if the family has no
+/// face with the right one.
+#[cfg(any(target_os = "macos", windows, test))]
+fn best_match(faces: &[IndexedFace], family: &str, style: FontStyle) -> Option<FontFile> {
+    faces
+        .iter()
+        .filter(|face| {
+            face.families
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(family))
+        })
+        .min_by_key(|face| {
+            (
+                face.italic != style.italic,
+                face.stretched,
+                (i32::from(face.weight) - i32::from(style.weight)).abs(),
+            )
+        })
+        .map(|face| face.file.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn font_directories() {
+        let faces = index_faces(&[PathBuf::from("/usr/share/fonts")]);
+        let file = |family, style| best_match(&faces, family, style).map(|file| file.path);
+        let name = |family, style| {
+            file(family, style)
+                .and_then(|path| path.file_name()?.to_str().map(String::from))
+                .unwrap_or_default()
+        };
+
+        assert_eq!(name("dejavu sans", FontStyle::REGULAR), "DejaVuSans.ttf");
+        assert_eq!(name("DejaVu Sans", FontStyle::BOLD), "DejaVuSans-Bold.ttf");
+        assert_eq!(
+            name("DejaVu Serif", FontStyle::BOLD_ITALIC),
+            "DejaVuSerif-BoldItalic.ttf"
+        );
+        // The same files fontconfig finds
+        assert_eq!(
+            file("DejaVu Sans", FontStyle::ITALIC),
+            locate("DejaVu Sans", FontStyle::ITALIC)
+                .ok()
+                .map(|file| file.path)
+        );
+        assert_eq!(file("No Such Family", FontStyle::REGULAR), None);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::gl;
+use gl::types::*;
+
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferError {
+    /// `glCheckFramebufferStatus` did not report the framebuffer complete.
+    Incomplete(GLenum),
+}
+
+impl std::error::Error for FramebufferError {}
+
+impl Display for FramebufferError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FramebufferError::Incomplete(status) => {
+                write!(f, "Incomplete framebuffer: status {:#x}", status)
+            }
+        }
+    }
+}
+
+/// An offscreen RGBA8 render target.
+///
+/// While bound, `Renderer` and `TextRenderer` draw into it just like into the
+/// window, and the result can be read back with [`Framebuffer::read_pixels`].
+#[derive(Debug)]
+pub struct Framebuffer {
+    fbo: GLuint,
+    rbo: GLuint,
+    width: i32,
+    height: i32,
+}
+
+impl Framebuffer {
+    pub fn new(width: i32, height: i32) -> Result<Self, FramebufferError> {
+        let mut fbo: GLuint = 0;
+        let mut rbo: GLuint = 0;
+
+        let status = unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::GenRenderbuffers(1, &mut rbo);
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width, height);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                rbo,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+
+        // Dropped on failure, which deletes both objects again
+        let framebuffer = Self {
+            fbo,
+            rbo,
+            width,
+            height,
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(FramebufferError::Incomplete(status));
+        }
+
+        Ok(framebuffer)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Binds the framebuffer and sets the viewport to cover it.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.width, self.height);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    /// Reads the framebuffer back as tightly packed RGBA rows, top row first.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let stride = self.width as usize * 4;
+        let mut pixels = vec![0u8; stride * self.height as usize];
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+
+        // OpenGL starts at the bottom left
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks_exact(stride).rev() {
+            flipped.extend_from_slice(row);
+        }
+
+        flipped
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), png::EncodingError> {
+        write_png(
+            path,
+            self.width as u32,
+            self.height as u32,
+            &self.read_pixels(),
+        )
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteRenderbuffers(1, &self.rbo);
+        }
+    }
+}
+
+pub fn write_png<P: AsRef<Path>>(
+    path: P,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)
+}
@@ -0,0 +1,305 @@
+use crate::renderer::path::{FillRule, Path};
+use crate::renderer::shape::RoundedRect;
+use crate::renderer::{RenderRect, Rgba};
+use crate::vectors::Vec2f;
+
+use std::f32::consts::TAU;
+
+/// Number of colors a gradient is sampled into, one row of the ramp texture
+/// of `Renderer`.
+pub const RAMP_WIDTH: usize = 256;
+
+/// A color at an offset along a gradient, from 0 at its start to 1 at its end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorStop {
+    pub offset: f32,
+    pub color: Rgba,
+}
+
+impl ColorStop {
+    pub fn new(offset: f32, color: Rgba) -> Self {
+        Self { offset, color }
+    }
+}
+
+/// How a gradient continues outside of its stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Extend {
+    /// The colors of the first and the last stop.
+    Pad = 0,
+    /// The stops start over.
+    Repeat = 1,
+    /// The stops run back and forth.
+    Reflect = 2,
+}
+
+impl Extend {
+    /// Maps an offset along the gradient into the stops. Mirrors
+    /// `gradientColor` in `base.frag`.
+    pub fn apply(self, offset: f32) -> f32 {
+        let offset = match self {
+            Extend::Pad => offset,
+            Extend::Repeat => offset.rem_euclid(1.),
+            Extend::Reflect => 1. - (offset.rem_euclid(2.) - 1.).abs(),
+        };
+        offset.clamp(0., 1.)
+    }
+}
+
+/// The geometry of a gradient in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientKind {
+    /// Colors change along the line from `start` to `end`.
+    Linear { start: Vec2f, end: Vec2f },
+    /// Colors change from `center` to the circle of `radius` around it.
+    Radial { center: Vec2f, radius: f32 },
+    /// Colors change clockwise around `center`, starting at `angle` radians
+    /// from the positive x axis.
+    Conic { center: Vec2f, angle: f32 },
+}
+
+impl GradientKind {
+    /// Stored in `Vertex::params` of `Renderer`, 0 is a solid color.
+    pub(crate) fn code(self) -> f32 {
+        match self {
+            GradientKind::Linear { .. } => 1.,
+            GradientKind::Radial { .. } => 2.,
+            GradientKind::Conic { .. } => 3.,
+        }
+    }
+}
+
+/// Colors blended between stops, which are sorted by their offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+    pub extend: Extend,
+}
+
+impl Gradient {
+    /// A gradient padded with the colors of its first and last stop.
+    pub fn new(kind: GradientKind, stops: Vec<ColorStop>) -> Self {
+        Self {
+            kind,
+            stops,
+            extend: Extend::Pad,
+        }
+    }
+
+    /// Position of `point` relative to the gradient, which is affine over
+    /// the screen and so can be interpolated between vertices.
+    ///
+    /// Linear gradients only use the offset in `x`. Radial and conic
+    /// gradients are measured from their center, scaled to the radius or
+    /// rotated to the start angle. Gradients of zero length or radius stay
+    /// at their start.
+    pub fn coordinates(&self, point: Vec2f) -> Vec2f {
+        match self.kind {
+            GradientKind::Linear { start, end } => {
+                let direction = end - start;
+                let length = direction.dot(direction);
+                if length > 0. {
+                    Vec2f::new((point - start).dot(direction) / length, 0.)
+                } else {
+                    Vec2f::new(0., 0.)
+                }
+            }
+            GradientKind::Radial { center, radius } => {
+                if radius > 0. {
+                    (point - center) * radius.recip()
+                } else {
+                    Vec2f::new(0., 0.)
+                }
+            }
+            GradientKind::Conic { center, angle } => {
+                let (sin, cos) = angle.sin_cos();
+                let p = point - center;
+                Vec2f::new(cos * p.x + sin * p.y, -sin * p.x + cos * p.y)
+            }
+        }
+    }
+
+    /// Offset along the gradient at interpolated `coordinates`, before it is
+    /// extended. Mirrors `gradientColor` in `base.frag`.
+    pub fn offset(&self, coordinates: Vec2f) -> f32 {
+        match self.kind {
+            GradientKind::Linear { .. } => coordinates.x,
+            GradientKind::Radial { .. } => coordinates.length(),
+            GradientKind::Conic { .. } => (coordinates.y.atan2(coordinates.x) / TAU).rem_euclid(1.),
+        }
+    }
+
+    /// Color of the pixel whose center is at `point`, picked from `ramp`
+    /// like `base.frag` does.
+    pub fn sample(&self, ramp: &[Rgba], point: Vec2f) -> Rgba {
+        let offset = self.extend.apply(self.offset(self.coordinates(point)));
+        ramp[((offset * RAMP_WIDTH as f32) as usize).min(RAMP_WIDTH - 1)]
+    }
+
+    /// The colors at the centers of `RAMP_WIDTH` equal steps from 0 to 1.
+    pub fn ramp(&self) -> Vec<Rgba> {
+        (0..RAMP_WIDTH)
+            .map(|i| self.color_at((i as f32 + 0.5) / RAMP_WIDTH as f32))
+            .collect()
+    }
+
+    /// Blends the stops around `offset` with premultiplied alpha, so
+    /// transparent stops do not darken their neighbours.
+    fn color_at(&self, offset: f32) -> Rgba {
+        let stops = &self.stops;
+        let (before, after) = match stops.iter().position(|stop| stop.offset > offset) {
+            None => match stops.last() {
+                Some(stop) => return stop.color,
+                None => return Rgba::default(),
+            },
+            Some(0) => return stops[0].color,
+            Some(i) => (stops[i - 1], stops[i]),
+        };
+
+        let factor = ((offset - before.offset) / (after.offset - before.offset)).clamp(0., 1.);
+        let premultiply = |color: Rgba| {
+            let alpha = f32::from(color.a) / 255.;
+            [
+                f32::from(color.r) * alpha,
+                f32::from(color.g) * alpha,
+                f32::from(color.b) * alpha,
+                f32::from(color.a),
+            ]
+        };
+        let (from, to) = (premultiply(before.color), premultiply(after.color));
+        let [r, g, b, a] = [0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * factor);
+
+        if a <= 0. {
+            return Rgba::default();
+        }
+        let straight = |channel: f32| (channel * 255. / a).round().min(255.) as u8;
+        Rgba {
+            r: straight(r),
+            g: straight(g),
+            b: straight(b),
+            a: a.round() as u8,
+        }
+    }
+}
+
+/// A shape whose color is replaced by a gradient, the border of a rounded
+/// rectangle keeps its color.
+#[derive(Debug, Clone)]
+pub enum GradientShape {
+    Rect(RenderRect),
+    RoundedRect(RoundedRect),
+    Path { path: Path, rule: FillRule },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::Rgb;
+
+    use std::f32::consts::PI;
+
+    fn stops() -> Vec<ColorStop> {
+        vec![
+            ColorStop::new(0., Rgb::from_hex(0xFF0000).into()),
+            ColorStop::new(0.5, Rgb::from_hex(0x0000FF).into()),
+            ColorStop::new(0.5, Rgb::from_hex(0x00FF00).into()),
+            ColorStop::new(1., Rgb::from_hex(0x00FF00).alpha(0)),
+        ]
+    }
+
+    #[test]
+    fn ramp() {
+        let kind = GradientKind::Linear {
+            start: Vec2f::new(0., 0.),
+            end: Vec2f::new(1., 0.),
+        };
+        let ramp = Gradient::new(kind, stops()).ramp();
+
+        assert_eq!(ramp.len(), RAMP_WIDTH);
+        assert_eq!(
+            ramp[0],
+            Rgba {
+                r: 254,
+                g: 0,
+                b: 1,
+                a: 255
+            }
+        );
+        assert_eq!(
+            ramp[64],
+            Rgba {
+                r: 127,
+                g: 0,
+                b: 128,
+                a: 255
+            }
+        );
+        // A hard edge between two stops at the same offset
+        assert_eq!((ramp[127].g, ramp[128].b), (0, 0));
+        // Fading out keeps the color
+        assert_eq!(
+            ramp[192],
+            Rgba {
+                r: 0,
+                g: 255,
+                b: 0,
+                a: 127
+            }
+        );
+
+        let empty = Gradient::new(kind, Vec::new());
+        assert!(empty.ramp().iter().all(|color| *color == Rgba::default()));
+    }
+
+    #[test]
+    fn extend() {
+        let offsets = [-0.25, 0.25, 1.25, 1.75, 2.5];
+        let extended = |extend: Extend| offsets.map(|offset| extend.apply(offset));
+
+        assert_eq!(extended(Extend::Pad), [0., 0.25, 1., 1., 1.]);
+        assert_eq!(extended(Extend::Repeat), [0.75, 0.25, 0.25, 0.75, 0.5]);
+        assert_eq!(extended(Extend::Reflect), [0.25, 0.25, 0.75, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn offsets() {
+        let offset = |kind: GradientKind, x: f32, y: f32| {
+            let gradient = Gradient::new(kind, stops());
+            gradient.offset(gradient.coordinates(Vec2f::new(x, y)))
+        };
+        let approx = |a: f32, b: f32| assert!((a - b).abs() < 1e-5, "{} is not {}", a, b);
+
+        let linear = GradientKind::Linear {
+            start: Vec2f::new(10., 10.),
+            end: Vec2f::new(30., 30.),
+        };
+        approx(offset(linear, 20., 20.), 0.5);
+        approx(offset(linear, 30., 10.), 0.5);
+        approx(offset(linear, 0., 0.), -0.5);
+
+        let radial = GradientKind::Radial {
+            center: Vec2f::new(10., 10.),
+            radius: 20.,
+        };
+        approx(offset(radial, 10., 10.), 0.);
+        approx(offset(radial, 22., 26.), 1.);
+
+        // Clockwise on the screen, starting straight up
+        let conic = GradientKind::Conic {
+            center: Vec2f::new(10., 10.),
+            angle: -PI / 2.,
+        };
+        approx(offset(conic, 10., 0.), 0.);
+        approx(offset(conic, 20., 10.), 0.25);
+        approx(offset(conic, 10., 20.), 0.5);
+        approx(offset(conic, 0., 10.), 0.75);
+
+        let point = GradientKind::Linear {
+            start: Vec2f::new(10., 10.),
+            end: Vec2f::new(10., 10.),
+        };
+        approx(offset(point, 40., 20.), 0.);
+    }
+}
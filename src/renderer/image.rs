@@ -0,0 +1,498 @@
+use crate::gl;
+use crate::renderer::{Rect, Rgba};
+
+use gl::types::*;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::{fs, io};
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The bytes are not a PNG, JPEG or QOI image.
+    UnknownFormat,
+    Png(png::DecodingError),
+    Jpeg(jpeg_decoder::Error),
+    Qoi(qoi::Error),
+    /// The image does not fit into a texture of `max` pixels on each side.
+    TooLarge {
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+    /// No image was loaded with the id, or it was removed.
+    NotLoaded(ImageId),
+    /// The image has not `width * height * 4` bytes of pixels.
+    InvalidSize {
+        width: u32,
+        height: u32,
+        len: usize,
+    },
+}
+
+impl std::error::Error for ImageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImageError::Io(err) => Some(err),
+            ImageError::Png(err) => Some(err),
+            ImageError::Jpeg(err) => Some(err),
+            ImageError::Qoi(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(err) => write!(f, "Unable to read image: {}", err),
+            ImageError::UnknownFormat => write!(f, "Unknown image format"),
+            ImageError::Png(err) => write!(f, "Failed decoding PNG: {}", err),
+            ImageError::Jpeg(err) => write!(f, "Failed decoding JPEG: {}", err),
+            ImageError::Qoi(err) => write!(f, "Failed decoding QOI: {}", err),
+            ImageError::TooLarge { width, height, max } => write!(
+                f,
+                "Image of {}x{} pixels is larger than {} pixels",
+                width, height, max
+            ),
+            ImageError::NotLoaded(id) => write!(f, "Image {} is not loaded", id.0),
+            ImageError::InvalidSize { width, height, len } => write!(
+                f,
+                "Image of {}x{} pixels has {} bytes of pixels",
+                width, height, len
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(err: io::Error) -> Self {
+        ImageError::Io(err)
+    }
+}
+
+/// The encodings an [`Image`] can be decoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Qoi,
+}
+
+impl ImageFormat {
+    /// Recognizes an encoding by the magic bytes at its start.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"qoif") {
+            Some(ImageFormat::Qoi)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decoded RGBA pixels with straight alpha, top row first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Decodes a PNG, JPEG or QOI image, whichever `bytes` start like.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ImageError> {
+        match ImageFormat::detect(bytes).ok_or(ImageError::UnknownFormat)? {
+            ImageFormat::Png => decode_png(bytes).map_err(ImageError::Png),
+            ImageFormat::Jpeg => decode_jpeg(bytes).map_err(ImageError::Jpeg),
+            ImageFormat::Qoi => decode_qoi(bytes).map_err(ImageError::Qoi),
+        }
+    }
+
+    /// Fails unless there are `width * height * 4` bytes of pixels, which
+    /// backends check before loading an image.
+    pub fn check_size(&self) -> Result<(), ImageError> {
+        if self.pixels.len() != self.width as usize * self.height as usize * 4 {
+            return Err(ImageError::InvalidSize {
+                width: self.width,
+                height: self.height,
+                len: self.pixels.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Multiplies the colors with their alpha, which filtering needs to not
+    /// bleed the color of transparent pixels.
+    pub fn premultiplied(&self) -> Image {
+        let mut pixels = self.pixels.clone();
+        for pixel in pixels.chunks_exact_mut(4) {
+            let alpha = u32::from(pixel[3]);
+            for channel in &mut pixel[..3] {
+                *channel = ((u32::from(*channel) * alpha + 127) / 255) as u8;
+            }
+        }
+
+        Image { pixels, ..*self }
+    }
+
+    /// The texel at `u` and `v` from 0 to 1 across the image, with its
+    /// channels from 0 to 1. Mirrors `texture` in GLSL with edges clamped.
+    pub fn sample(&self, u: f32, v: f32, filter: Filter) -> [f32; 4] {
+        if self.width == 0 || self.height == 0 {
+            return [0.; 4];
+        }
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let texel = |x: i64, y: i64| {
+            let index = (y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize * 4;
+            let pixel = &self.pixels[index..index + 4];
+            [0, 1, 2, 3].map(|channel| f32::from(pixel[channel]) / 255.)
+        };
+
+        let (x, y) = (u * self.width as f32, v * self.height as f32);
+        match filter {
+            Filter::Nearest => texel(x.floor() as i64, y.floor() as i64),
+            Filter::Linear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (left, top) = (x.floor(), y.floor());
+                let (fx, fy) = (x - left, y - top);
+                let (left, top) = (left as i64, top as i64);
+
+                let lerp = |a: [f32; 4], b: [f32; 4], t: f32| {
+                    [0, 1, 2, 3].map(|channel| a[channel] + (b[channel] - a[channel]) * t)
+                };
+                let upper = lerp(texel(left, top), texel(left + 1, top), fx);
+                let lower = lerp(texel(left, top + 1), texel(left + 1, top + 1), fx);
+                lerp(upper, lower, fy)
+            }
+        }
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<Image, png::DecodingError> {
+    let mut decoder = png::Decoder::new(bytes);
+    // Palettes, transparency chunks and small or large depths become 8 bits
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    buffer.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Grayscale => expand(&buffer, 1, |p| [p[0], p[0], p[0], 255]),
+        png::ColorType::GrayscaleAlpha => expand(&buffer, 2, |p| [p[0], p[0], p[0], p[1]]),
+        png::ColorType::Rgb => expand(&buffer, 3, |p| [p[0], p[1], p[2], 255]),
+        png::ColorType::Rgba | png::ColorType::Indexed => buffer,
+    };
+
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+fn decode_jpeg(bytes: &[u8]) -> Result<Image, jpeg_decoder::Error> {
+    use jpeg_decoder::PixelFormat;
+
+    let mut decoder = jpeg_decoder::Decoder::new(bytes);
+    let buffer = decoder.decode()?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| jpeg_decoder::Error::Format("missing image info".into()))?;
+
+    let pixels = match info.pixel_format {
+        PixelFormat::L8 => expand(&buffer, 1, |p| [p[0], p[0], p[0], 255]),
+        PixelFormat::L16 => expand(&buffer, 2, |p| {
+            let gray = (u16::from_ne_bytes([p[0], p[1]]) >> 8) as u8;
+            [gray, gray, gray, 255]
+        }),
+        PixelFormat::RGB24 => expand(&buffer, 3, |p| [p[0], p[1], p[2], 255]),
+        PixelFormat::CMYK32 => expand(&buffer, 4, |p| {
+            let ink = |channel: u8| {
+                let white = (255 - u32::from(channel)) * (255 - u32::from(p[3]));
+                ((white + 127) / 255) as u8
+            };
+            [ink(p[0]), ink(p[1]), ink(p[2]), 255]
+        }),
+    };
+
+    Ok(Image {
+        width: u32::from(info.width),
+        height: u32::from(info.height),
+        pixels,
+    })
+}
+
+fn decode_qoi(bytes: &[u8]) -> Result<Image, qoi::Error> {
+    let (header, buffer) = qoi::decode_to_vec(bytes)?;
+    let pixels = match header.channels {
+        qoi::Channels::Rgb => expand(&buffer, 3, |p| [p[0], p[1], p[2], 255]),
+        qoi::Channels::Rgba => buffer,
+    };
+
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+/// Converts pixels of `channels` bytes each into RGBA.
+fn expand(buffer: &[u8], channels: usize, rgba: impl Fn(&[u8]) -> [u8; 4]) -> Vec<u8> {
+    buffer.chunks_exact(channels).flat_map(rgba).collect()
+}
+
+/// How the texels of an image are picked when it is scaled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// The closest texel, for pixel art.
+    Nearest,
+    /// The four closest texels blended.
+    Linear,
+}
+
+impl Filter {
+    fn gl(self) -> GLint {
+        match self {
+            Filter::Nearest => gl::NEAREST as GLint,
+            Filter::Linear => gl::LINEAR as GLint,
+        }
+    }
+}
+
+/// Where and how an image is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDraw {
+    /// Part of the image in pixels, or all of it.
+    pub source: Option<Rect>,
+    /// Area of the screen the source is stretched over.
+    pub destination: Rect,
+    /// Multiplied with the color of every pixel.
+    pub tint: Rgba,
+    pub filter: Filter,
+}
+
+impl ImageDraw {
+    /// Draws all of the image untinted, blending its texels linearly.
+    pub fn new(destination: Rect) -> Self {
+        Self {
+            source: None,
+            destination,
+            tint: Rgba {
+                r: 255,
+                g: 255,
+                b: 255,
+                a: 255,
+            },
+            filter: Filter::Linear,
+        }
+    }
+
+    /// The source in pixels of an image of `width` and `height`.
+    pub fn source_rect(&self, width: u32, height: u32) -> Rect {
+        self.source.unwrap_or(Rect {
+            x: 0.,
+            y: 0.,
+            width: width as f32,
+            height: height as f32,
+        })
+    }
+}
+
+/// An image loaded by a `Backend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageId(pub(crate) u32);
+
+/// An image uploaded to the GPU with premultiplied alpha.
+#[derive(Debug)]
+pub struct Texture {
+    pub id: GLuint,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Texture {
+    pub fn new(image: &Image) -> Result<Self, ImageError> {
+        image.check_size()?;
+
+        let mut max: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max);
+        }
+        let max = max as u32;
+        if image.width > max || image.height > max {
+            return Err(ImageError::TooLarge {
+                width: image.width,
+                height: image.height,
+                max,
+            });
+        }
+
+        let premultiplied = image.premultiplied();
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                image.width as i32,
+                image.height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                premultiplied.pixels.as_ptr() as *const _,
+            );
+
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        Ok(Self {
+            id,
+            width: image.width,
+            height: image.height,
+        })
+    }
+
+    /// Picks the texels with `filter` until it is set again, the texture
+    /// has to be bound.
+    pub(crate) fn set_filter(filter: Filter) {
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter.gl());
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter.gl());
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::BufWriter;
+
+    fn encode_png(width: u32, height: u32, color: png::ColorType, pixels: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(BufWriter::new(&mut bytes), width, height);
+            encoder.set_color(color);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(pixels).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn png() {
+        let rgba = [255, 0, 0, 255, 0, 255, 0, 128, 0, 0, 255, 0, 10, 20, 30, 40];
+        let bytes = encode_png(2, 2, png::ColorType::Rgba, &rgba);
+        let image = Image::from_bytes(&bytes).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.pixels, rgba);
+
+        let gray = encode_png(
+            3,
+            1,
+            png::ColorType::GrayscaleAlpha,
+            &[0, 255, 128, 64, 255, 0],
+        );
+        assert_eq!(
+            Image::from_bytes(&gray).unwrap().pixels,
+            [0, 0, 0, 255, 128, 128, 128, 64, 255, 255, 255, 0]
+        );
+    }
+
+    #[test]
+    fn jpeg() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/images/quadrants.jpg");
+        let image = Image::open(path).unwrap();
+        assert_eq!((image.width, image.height), (16, 16));
+
+        // Lossy, but the quadrants keep their colors
+        let pixel = |x: usize, y: usize| {
+            let index = (y * 16 + x) * 4;
+            &image.pixels[index..index + 4]
+        };
+        let quadrants = [
+            (2, 2, [255, 0, 0]),
+            (13, 2, [0, 255, 0]),
+            (2, 13, [0, 0, 255]),
+            (13, 13, [255, 255, 255]),
+        ];
+        for (x, y, color) in quadrants {
+            let pixel = pixel(x, y);
+            for channel in 0..3 {
+                let difference = i32::from(pixel[channel]) - color[channel];
+                assert!(difference.abs() <= 8, "{:?} is not {:?}", pixel, color);
+            }
+            assert_eq!(pixel[3], 255);
+        }
+    }
+
+    #[test]
+    fn qoi() {
+        let rgb = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let bytes = qoi::encode_to_vec(rgb, 3, 1).unwrap();
+        let image = Image::from_bytes(&bytes).unwrap();
+        assert_eq!(image.pixels, [1, 2, 3, 255, 4, 5, 6, 255, 7, 8, 9, 255]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            Image::from_bytes(b"GIF89a"),
+            Err(ImageError::UnknownFormat)
+        ));
+        assert!(matches!(
+            Image::from_bytes(b"\x89PNG\r\n\x1a\n\0"),
+            Err(ImageError::Png(_))
+        ));
+        assert!(matches!(
+            Image::from_bytes(&[0xFF, 0xD8, 0xFF, 0]),
+            Err(ImageError::Jpeg(_))
+        ));
+        assert!(matches!(
+            Image::from_bytes(b"qoif"),
+            Err(ImageError::Qoi(_))
+        ));
+        assert!(matches!(Image::open("missing.png"), Err(ImageError::Io(_))));
+    }
+
+    #[test]
+    fn sample() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![0, 0, 0, 255, 255, 255, 255, 255],
+        };
+
+        assert_eq!(image.sample(0.2, 0.5, Filter::Nearest), [0., 0., 0., 1.]);
+        assert_eq!(image.sample(0.6, 0.5, Filter::Nearest), [1., 1., 1., 1.]);
+        // Halfway between the texel centers, clamped at the edges
+        assert_eq!(image.sample(0.5, 0.5, Filter::Linear)[0], 0.5);
+        assert_eq!(image.sample(0.1, 0.5, Filter::Linear)[0], 0.);
+        assert_eq!(image.sample(1., 0.5, Filter::Linear)[0], 1.);
+    }
+}
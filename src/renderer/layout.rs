@@ -0,0 +1,329 @@
+use crate::renderer::decoration::DecorationMetrics;
+use crate::renderer::shaping::{self, PositionedGlyph, Shaper};
+
+use std::ops::Range;
+
+use crossfont::{FontKey, Size};
+use unicode_bidi::BidiInfo;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+/// Direction of a paragraph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+impl Direction {
+    pub fn is_rtl(self) -> bool {
+        self == Direction::RightToLeft
+    }
+
+    /// Direction of the paragraph of `bidi` the byte at `index` belongs to.
+    fn of(bidi: &BidiInfo, index: usize) -> Self {
+        match shaping::paragraph(bidi, index) {
+            Some(paragraph) if paragraph.level.is_rtl() => Direction::RightToLeft,
+            _ => Direction::LeftToRight,
+        }
+    }
+}
+
+/// Horizontal alignment of the lines of a [`Layout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+    /// Left in left-to-right paragraphs and right in right-to-left ones.
+    Start,
+    /// Right in left-to-right paragraphs and left in right-to-left ones.
+    End,
+    /// Stretches the spaces of wrapped lines to fill the width, the last line
+    /// of a paragraph stays aligned to its start.
+    Justify,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutLine {
+    /// Glyphs relative to the top left corner of the layout, in the order
+    /// they are displayed from left to right.
+    pub glyphs: Vec<PositionedGlyph>,
+    /// The bytes of the laid out string on this line, with the line break.
+    pub range: Range<usize>,
+    pub x: f32,
+    pub y: f32,
+    /// Width of the line without trailing whitespace.
+    pub width: f32,
+    /// Base direction of the paragraph of the line.
+    pub direction: Direction,
+}
+
+/// Lines of text wrapped to a width and aligned.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub lines: Vec<LayoutLine>,
+    /// Width of the widest line.
+    pub width: f32,
+    pub height: f32,
+    /// Distance of the lines, from the metrics of the font size.
+    pub line_height: f32,
+    /// Where underlines, strikethroughs and overlines of the lines are drawn.
+    pub decoration_metrics: DecorationMetrics,
+}
+
+fn is_line_break(character: char) -> bool {
+    matches!(
+        character,
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+/// Wraps `text` into lines no wider than `max_width`, at the line break
+/// opportunities of the Unicode line breaking algorithm.
+///
+/// Words wider than `max_width` are not broken and overflow their line. With
+/// an infinite `max_width` lines only end at mandatory breaks, like `'\n'`,
+/// and are aligned to the widest line.
+///
+/// Paragraphs are read in the direction of their first strong character,
+/// runs of the other direction are reordered by the Unicode Bidirectional
+/// Algorithm.
+#[allow(clippy::too_many_arguments)]
+pub fn layout(
+    shaper: &mut Shaper,
+    text: &str,
+    font_key: FontKey,
+    size: Size,
+    max_width: f32,
+    align: Align,
+    line_height: f32,
+) -> Layout {
+    let bidi = BidiInfo::new(text, None);
+    let mut shape = |range: Range<usize>| shaper.shape_bidi(&bidi, range, font_key, size).advance;
+
+    // Ranges of the lines and whether they end in a mandatory break
+    let mut breaks = Vec::new();
+
+    let mut line_start = 0;
+    let mut line_width = 0.;
+    let mut segment_start = 0;
+    for (end, opportunity) in linebreaks(text) {
+        // The trailing whitespace of a segment may hang over the width, so
+        // it is measured apart from the visible text
+        let visible_end = segment_start + text[segment_start..end].trim_end().len();
+        let visible = shape(segment_start..visible_end);
+
+        if line_start < segment_start && line_width + visible > max_width {
+            breaks.push((line_start..segment_start, false));
+            line_start = segment_start;
+            line_width = 0.;
+        }
+        line_width += visible + shape(visible_end..end);
+
+        if opportunity == BreakOpportunity::Mandatory {
+            breaks.push((line_start..end, true));
+            line_start = end;
+            line_width = 0.;
+        }
+        segment_start = end;
+    }
+
+    let mut layout = Layout {
+        line_height,
+        ..Layout::default()
+    };
+    for (i, (range, mandatory)) in breaks.into_iter().enumerate() {
+        let line = text[range.clone()]
+            .trim_end_matches(is_line_break)
+            .trim_end();
+        let direction = Direction::of(&bidi, range.start);
+        let line = range.start..range.start + line.len();
+        let shaped = shaper.shape_bidi(&bidi, line, font_key, size);
+
+        let mut glyphs = shaped.glyphs;
+        for glyph in &mut glyphs {
+            glyph.cluster += range.start;
+        }
+
+        layout.width = layout.width.max(shaped.advance);
+        layout.lines.push(LayoutLine {
+            glyphs,
+            range,
+            x: 0.,
+            y: i as f32 * line_height,
+            width: shaped.advance,
+            direction,
+        });
+
+        // Justify every line but the last of a paragraph
+        if align == Align::Justify && !mandatory && max_width.is_finite() {
+            justify(layout.lines.last_mut().unwrap(), text, max_width);
+        }
+    }
+    layout.height = layout.lines.len() as f32 * line_height;
+
+    let width = if max_width.is_finite() {
+        max_width
+    } else {
+        layout.width
+    };
+    for line in &mut layout.lines {
+        let rtl = line.direction.is_rtl();
+        let right = (width - line.width).max(0.);
+        line.x = match align {
+            Align::Left => 0.,
+            Align::Center => right / 2.,
+            Align::Right => right,
+            Align::Start | Align::Justify if rtl => right,
+            Align::End if !rtl => right,
+            Align::Start | Align::End | Align::Justify => 0.,
+        };
+
+        for glyph in &mut line.glyphs {
+            glyph.x += line.x;
+        }
+    }
+
+    layout
+}
+
+/// Spreads the space left on `line` evenly over its spaces.
+fn justify(line: &mut LayoutLine, text: &str, width: f32) {
+    let is_space = |glyph: &PositionedGlyph| text[glyph.cluster..].starts_with(' ');
+
+    let spaces = line.glyphs.iter().filter(|glyph| is_space(glyph)).count();
+    if spaces == 0 || line.width >= width {
+        return;
+    }
+
+    let extra = (width - line.width) / spaces as f32;
+    let mut shift = 0.;
+    for glyph in &mut line.glyphs {
+        glyph.x += shift;
+        if is_space(glyph) {
+            shift += extra;
+        }
+    }
+    line.width = width;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::shaping::FontStyle;
+
+    fn shaper() -> (Shaper, FontKey, Size) {
+        let size = Size::new(16.);
+        let mut shaper = Shaper::new(1.).unwrap();
+        let font_key = shaper
+            .load_font("DejaVu Sans", FontStyle::REGULAR, size)
+            .unwrap();
+
+        (shaper, font_key, size)
+    }
+
+    fn lines<'a>(layout: &Layout, text: &'a str) -> Vec<&'a str> {
+        layout
+            .lines
+            .iter()
+            .map(|line| &text[line.range.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn wrapping() {
+        let (mut shaper, font_key, size) = shaper();
+        let text = "one two three\nfour";
+        let width = shaper.shape("one two", font_key, size).advance;
+
+        let layout = layout(&mut shaper, text, font_key, size, width, Align::Left, 20.);
+        assert_eq!(lines(&layout, text), ["one two ", "three\n", "four"]);
+        assert_eq!(layout.height, 60.);
+        assert_eq!(layout.lines[2].y, 40.);
+        assert!(layout.lines.iter().all(|line| line.width <= width));
+    }
+
+    #[test]
+    fn alignment() {
+        let (mut shaper, font_key, size) = shaper();
+        let text = "wide line\nab";
+
+        let right = layout(&mut shaper, text, font_key, size, 200., Align::Right, 20.);
+        for line in &right.lines {
+            assert!((line.x + line.width - 200.).abs() < 0.01);
+        }
+
+        // Without a width lines are aligned to the widest one
+        let center = layout(
+            &mut shaper,
+            text,
+            font_key,
+            size,
+            f32::INFINITY,
+            Align::Center,
+            20.,
+        );
+        let (wide, narrow) = (&center.lines[0], &center.lines[1]);
+        assert_eq!(wide.x, 0.);
+        assert!((narrow.x * 2. + narrow.width - wide.width).abs() < 0.01);
+    }
+
+    #[test]
+    fn justified() {
+        let (mut shaper, font_key, size) = shaper();
+        let text = "a b c d e f g h";
+        let width = shaper.shape("a b c d", font_key, size).advance + 10.;
+
+        let layout = layout(
+            &mut shaper,
+            text,
+            font_key,
+            size,
+            width,
+            Align::Justify,
+            20.,
+        );
+        let (first, last) = (&layout.lines[0], layout.lines.last().unwrap());
+        assert_eq!(first.width, width);
+
+        let end = first.glyphs.last().unwrap();
+        assert!(end.x > shaper.shape("a b c ", font_key, size).advance + 9.);
+        assert!(last.width < width);
+    }
+
+    #[test]
+    fn bidirectional() {
+        let (mut shaper, font_key, size) = shaper();
+        let text = "abc שלום 123\nשלום abc\nمرحبا";
+        let layout = layout(&mut shaper, text, font_key, size, 300., Align::Start, 20.);
+        let visual = |line: &LayoutLine| -> String {
+            line.glyphs
+                .iter()
+                .filter_map(|glyph| text[glyph.cluster..].chars().next())
+                .collect()
+        };
+
+        let (mixed, rtl, arabic) = (&layout.lines[0], &layout.lines[1], &layout.lines[2]);
+        assert_eq!(mixed.direction, Direction::LeftToRight);
+        assert_eq!(rtl.direction, Direction::RightToLeft);
+        assert_eq!(arabic.direction, Direction::RightToLeft);
+
+        // Runs are reordered, numbers stay left-to-right
+        assert_eq!(visual(mixed), "abc 123 םולש");
+        assert_eq!(visual(rtl), "abc םולש");
+        for line in &layout.lines {
+            assert!(line.glyphs.windows(2).all(|pair| pair[0].x < pair[1].x));
+        }
+
+        // Joined Arabic letters are shaped from right to left
+        let clusters: Vec<usize> = arabic.glyphs.iter().map(|glyph| glyph.cluster).collect();
+        assert!(clusters.windows(2).all(|pair| pair[0] > pair[1]));
+
+        // Right-to-left lines start at the right and measure like their words
+        assert_eq!(mixed.x, 0.);
+        assert!((rtl.x + rtl.width - 300.).abs() < 0.01);
+        let words = shaper.shape("abc ", font_key, size).advance
+            + shaper.shape("שלום", font_key, size).advance;
+        assert!((rtl.width - words).abs() < 0.01);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::renderer::Rgba;
+
+/// A corner of a [`Mesh`] triangle in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub x: f32,
+    pub y: f32,
+    /// Signed distance from the center line of a stroke.
+    pub across: f32,
+    /// Distance inside from an edge not covered by `across`, plus 0.5.
+    pub edge: f32,
+}
+
+/// Triangles anti-aliased by the distances interpolated between their
+/// corners, used for tessellated shapes.
+///
+/// Both distances are affine over every triangle, so interpolating them gives
+/// the exact distance of a pixel to the edges of the shape.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    /// Three vertices per triangle.
+    pub vertices: Vec<MeshVertex>,
+    /// Half of the width of the stroke `across` is measured in.
+    pub half_width: f32,
+    pub color: Rgba,
+}
+
+impl Mesh {
+    pub fn new(half_width: f32, color: Rgba) -> Self {
+        Self {
+            vertices: Vec::new(),
+            half_width,
+            color,
+        }
+    }
+
+    /// A mesh whose coverage only depends on `MeshVertex::edge`.
+    pub fn fill(color: Rgba) -> Self {
+        Self::new(f32::MAX / 4., color)
+    }
+
+    pub fn triangle(&mut self, a: MeshVertex, b: MeshVertex, c: MeshVertex) {
+        self.vertices.extend_from_slice(&[a, b, c]);
+    }
+
+    /// Triangulates a convex polygon.
+    pub fn fan(&mut self, polygon: &[MeshVertex]) {
+        for i in 2..polygon.len() {
+            self.triangle(polygon[0], polygon[i - 1], polygon[i]);
+        }
+    }
+}
+
+/// Coverage of a pixel with the interpolated distances of a [`Mesh`].
+/// Mirrors the `MESH` shape of `base.frag`.
+pub fn coverage(across: f32, edge: f32, half_width: f32) -> f32 {
+    let across = half_width + 0.5 - across.abs();
+
+    // Lines thinner than a pixel are fainter instead
+    across.min(edge).min(half_width * 2.).clamp(0., 1.)
+}
@@ -1,41 +1,177 @@
 // oriented by the renderer of Alacritty
 
+pub mod atlas;
+pub mod backend;
+pub mod command;
+pub mod cpu;
+pub mod decoration;
 pub mod font;
+pub mod font_file;
+pub mod framebuffer;
+pub mod gradient;
+pub mod image;
+pub mod layout;
+pub mod mesh;
+pub mod path;
+pub mod sdf;
 pub mod shader;
+pub mod shape;
+pub mod shaping;
+pub mod stroke;
+pub mod text;
 
 use crate::gl;
+use crate::renderer::atlas::SpriteBatch;
+use crate::renderer::gradient::{Gradient, GradientShape, RAMP_WIDTH};
+use crate::renderer::image::{Filter, ImageDraw, Texture};
+use crate::renderer::mesh::Mesh;
+use crate::renderer::path::{FillRule, Path};
+use crate::renderer::shape::{Arc, Curve, CurveKind, Ellipse, Pie, RoundedRect};
+use crate::renderer::stroke::Stroke;
 use crate::vectors::Vec2f;
 
 use gl::types::*;
+use std::fmt::{self, Display, Formatter};
 use std::mem;
 
-#[derive(Debug, Clone)]
+use crossfont::Error as RasterizerError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Rgb {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+impl Rgb {
+    /// A color packed as `0xRRGGBB`.
+    pub const fn from_hex(hex: u32) -> Self {
+        Self {
+            r: (hex >> 16) as u8,
+            g: (hex >> 8) as u8,
+            b: hex as u8,
+        }
+    }
+
+    pub const fn alpha(self, a: u8) -> Rgba {
+        Rgba {
+            r: self.r,
+            g: self.g,
+            b: self.b,
+            a,
+        }
+    }
+}
+
+/// A color with straight, not premultiplied, alpha.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl From<Rgb> for Rgba {
+    fn from(color: Rgb) -> Self {
+        color.alpha(255)
+    }
+}
+
+/// An area in pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
 #[derive(Debug, Clone)]
 pub struct RenderRect {
     pub x: f32,
     pub y: f32,
     pub width: f32,
     pub height: f32,
-    pub color: Rgb,
+    pub color: Rgba,
+}
+
+/// Why a renderer or backend could not be created.
+#[derive(Debug)]
+pub enum RendererError {
+    Shader(shader::ShaderError),
+    Rasterizer(RasterizerError),
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::Shader(err) => Some(err),
+            RendererError::Rasterizer(err) => Some(err),
+        }
+    }
+}
+
+impl Display for RendererError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::Shader(err) => write!(f, "{}", err),
+            RendererError::Rasterizer(err) => write!(f, "Unable to load font: {}", err),
+        }
+    }
+}
+
+impl From<shader::ShaderError> for RendererError {
+    fn from(err: shader::ShaderError) -> Self {
+        RendererError::Shader(err)
+    }
+}
+
+impl From<RasterizerError> for RendererError {
+    fn from(err: RasterizerError) -> Self {
+        RendererError::Rasterizer(err)
+    }
 }
 
 static FRAGMENT: &str = include_str!("../../res/base.frag");
 static VERTEX: &str = include_str!("../../res/base.vert");
 
+/// Gradients one batch can draw, the height of the ramp texture.
+const MAX_RAMPS: usize = 1024;
+
+/// Shapes `base.frag` can evaluate, stored in `Vertex::params`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum ShapeKind {
+    Solid = 0,
+    RoundedRect = 1,
+    Ellipse = 2,
+    Arc = 3,
+    Pie = 4,
+    Mesh = 5,
+    Image = 6,
+}
+
 #[repr(C)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 struct Vertex {
     x: f32,
     y: f32,
-    r: u8,
-    g: u8,
-    b: u8,
+    color: [u8; 4],
+    border_color: [u8; 4],
+    /// Position in pixels relative to the center of the shape, the
+    /// distances of a mesh vertex or the texture coordinates of an image.
+    local: [f32; 2],
+    /// Half of the size of the shape.
+    extent: [f32; 2],
+    /// Corner radii, or the bisector and aperture of a wedge.
+    radii: [f32; 4],
+    /// Border width, shape kind, stroke width and gradient kind.
+    params: [f32; 4],
+    /// Position relative to the gradient, see `Gradient::coordinates`.
+    gradient: [f32; 2],
+    /// Row of the gradient in the ramp texture and its `Extend`.
+    ramp: [f32; 2],
 }
 
 #[derive(Debug)]
@@ -47,12 +183,20 @@ pub struct Renderer {
     size: Vec2f,
 
     vertices: Vec<Vertex>,
+
+    /// Texture with the colors of one gradient per row.
+    ramp_texture: GLuint,
+    /// The rows of the ramp texture for the queued vertices.
+    ramps: Vec<Vec<Rgba>>,
+    /// Image textures bound from a vertex index on, with their filter.
+    textures: Vec<(usize, GLuint, Filter)>,
 }
 
 impl Renderer {
     pub fn new(size: Vec2f) -> Result<Self, shader::ShaderError> {
         let mut vao: GLuint = 0;
         let mut vbo: GLuint = 0;
+        let mut ramp_texture: GLuint = 0;
         let program = shader::Program::new(
             shader::Shader::new(gl::VERTEX_SHADER, VERTEX)?,
             shader::Shader::new(gl::FRAGMENT_SHADER, FRAGMENT)?,
@@ -65,31 +209,48 @@ impl Renderer {
             gl::BindVertexArray(vao);
             gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
 
+            let mut index = 0;
             let mut attribute_offset = 0;
 
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Vertex>() as i32,
-                attribute_offset as *const _,
-            );
-            gl::EnableVertexAttribArray(0);
-            attribute_offset += mem::size_of::<f32>() * 2;
-
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::UNSIGNED_BYTE,
-                gl::TRUE,
-                mem::size_of::<Vertex>() as i32,
-                attribute_offset as *const _,
-            );
-            gl::EnableVertexAttribArray(1);
+            macro_rules! add_attr {
+                ($count:expr, $gl_type:expr, $normalized:expr, $type:ty) => {
+                    gl::VertexAttribPointer(
+                        index,
+                        $count,
+                        $gl_type,
+                        $normalized,
+                        mem::size_of::<Vertex>() as i32,
+                        attribute_offset as *const _,
+                    );
+                    gl::EnableVertexAttribArray(index);
+
+                    #[allow(unused_assignments)]
+                    {
+                        attribute_offset += $count * mem::size_of::<$type>();
+                        index += 1;
+                    }
+                };
+            }
+
+            add_attr!(2, gl::FLOAT, gl::FALSE, f32);
+            add_attr!(4, gl::UNSIGNED_BYTE, gl::TRUE, u8);
+            add_attr!(4, gl::UNSIGNED_BYTE, gl::TRUE, u8);
+            add_attr!(2, gl::FLOAT, gl::FALSE, f32);
+            add_attr!(2, gl::FLOAT, gl::FALSE, f32);
+            add_attr!(4, gl::FLOAT, gl::FALSE, f32);
+            add_attr!(4, gl::FLOAT, gl::FALSE, f32);
+            add_attr!(2, gl::FLOAT, gl::FALSE, f32);
+            add_attr!(2, gl::FLOAT, gl::FALSE, f32);
 
             gl::BindVertexArray(0);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+
+            // Ramps are read with `texelFetch`, so the filter is never used
+            gl::GenTextures(1, &mut ramp_texture);
+            gl::BindTexture(gl::TEXTURE_2D, ramp_texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
         }
 
         Ok(Self {
@@ -98,10 +259,27 @@ impl Renderer {
             program,
             size,
             vertices: Vec::new(),
+            ramp_texture,
+            ramps: Vec::new(),
+            textures: Vec::new(),
         })
     }
 
+    /// Adapts to a new screen size in pixels, rectangles queued before are
+    /// still placed for the old size.
+    pub fn resize(&mut self, size: Vec2f) {
+        self.size = size;
+
+        unsafe {
+            gl::Viewport(0, 0, size.x as i32, size.y as i32);
+        }
+    }
+
     pub fn draw(&mut self) {
+        if self.vertices.is_empty() {
+            return;
+        }
+
         unsafe {
             gl::BindVertexArray(self.vao);
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
@@ -115,48 +293,333 @@ impl Renderer {
 
             gl::UseProgram(self.program.id);
 
-            gl::DrawArrays(gl::TRIANGLES, 0, self.vertices.len() as i32);
+            // Unit 1 keeps the glyph atlas bound by `TextRenderer` on unit 0
+            if !self.ramps.is_empty() {
+                let texels: Vec<u8> = self
+                    .ramps
+                    .iter()
+                    .flatten()
+                    .flat_map(|color| [color.r, color.g, color.b, color.a])
+                    .collect();
+
+                gl::ActiveTexture(gl::TEXTURE1);
+                gl::BindTexture(gl::TEXTURE_2D, self.ramp_texture);
+                gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    gl::RGBA as i32,
+                    RAMP_WIDTH as i32,
+                    self.ramps.len() as i32,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    texels.as_ptr() as *const _,
+                );
+                gl::Uniform1i(
+                    gl::GetUniformLocation(self.program.id, b"ramps\0".as_ptr() as *const _),
+                    1,
+                );
+            }
+
+            gl::Enable(gl::BLEND);
+            gl::BlendFuncSeparate(
+                gl::SRC_ALPHA,
+                gl::ONE_MINUS_SRC_ALPHA,
+                gl::ONE,
+                gl::ONE_MINUS_SRC_ALPHA,
+            );
 
+            // Images switch their texture between draw calls
+            let mut first = 0;
+            for &(start, texture, filter) in &self.textures {
+                if start > first {
+                    gl::DrawArrays(gl::TRIANGLES, first as i32, (start - first) as i32);
+                    first = start;
+                }
+
+                gl::ActiveTexture(gl::TEXTURE2);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                Texture::set_filter(filter);
+                gl::Uniform1i(
+                    gl::GetUniformLocation(self.program.id, b"image\0".as_ptr() as *const _),
+                    2,
+                );
+                gl::ActiveTexture(gl::TEXTURE0);
+            }
+            gl::DrawArrays(
+                gl::TRIANGLES,
+                first as i32,
+                (self.vertices.len() - first) as i32,
+            );
+
+            gl::Disable(gl::BLEND);
+            if !self.textures.is_empty() {
+                gl::ActiveTexture(gl::TEXTURE2);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::ActiveTexture(gl::TEXTURE0);
+            }
+            if !self.ramps.is_empty() {
+                gl::ActiveTexture(gl::TEXTURE1);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::ActiveTexture(gl::TEXTURE0);
+            }
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
             gl::UseProgram(0);
         }
 
         self.vertices.clear();
+        self.ramps.clear();
+        self.textures.clear();
     }
 
     pub fn rectangle(&mut self, rect: &RenderRect) {
-        let x = rect.x / (self.size.x / 2.) - 1.0;
-        let y = -rect.y / (self.size.y / 2.) + 1.0;
-        let quad = [
+        let color = rect.color;
+        self.quad(
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
             Vertex {
-                x,
-                y,
-                r: rect.color.r,
-                g: rect.color.g,
-                b: rect.color.b,
+                color: [color.r, color.g, color.b, color.a],
+                params: [0., f32::from(ShapeKind::Solid as u8), 0., 0.],
+                ..Vertex::default()
             },
+        );
+    }
+
+    /// Queues a rectangle with rounded corners and an optional border, the
+    /// edges are anti-aliased.
+    pub fn rounded_rect(&mut self, rect: &RoundedRect) {
+        let radii = rect.radii.clamp(rect.width, rect.height);
+        let (color, border) = (rect.color, rect.border);
+        let border_color = border.map_or(color, |border| border.color);
+
+        // One more pixel around the shape for its anti-aliased edge
+        self.quad(
+            rect.x - 1.,
+            rect.y - 1.,
+            rect.width + 2.,
+            rect.height + 2.,
             Vertex {
-                x,
-                y: y - rect.height / (self.size.y / 2.),
-                r: rect.color.r,
-                g: rect.color.g,
-                b: rect.color.b,
+                color: [color.r, color.g, color.b, color.a],
+                border_color: [
+                    border_color.r,
+                    border_color.g,
+                    border_color.b,
+                    border_color.a,
+                ],
+                extent: [rect.width / 2., rect.height / 2.],
+                radii: [
+                    radii.top_left,
+                    radii.top_right,
+                    radii.bottom_right,
+                    radii.bottom_left,
+                ],
+                params: [
+                    border.map_or(0., |border| border.width),
+                    f32::from(ShapeKind::RoundedRect as u8),
+                    0.,
+                    0.,
+                ],
+                ..Vertex::default()
             },
+        );
+    }
+
+    /// Queues an anti-aliased ellipse.
+    pub fn ellipse(&mut self, ellipse: &Ellipse) {
+        if let Some(curve) = ellipse.curve() {
+            self.curve(&curve);
+        }
+    }
+
+    /// Queues an anti-aliased section of the outline of an ellipse.
+    pub fn arc(&mut self, arc: &Arc) {
+        if let Some(curve) = arc.curve() {
+            self.curve(&curve);
+        }
+    }
+
+    /// Queues an anti-aliased slice of an ellipse.
+    pub fn pie(&mut self, pie: &Pie) {
+        if let Some(curve) = pie.curve() {
+            self.curve(&curve);
+        }
+    }
+
+    /// Queues an ellipse, arc or pie slice in the shape `base.frag` draws.
+    pub fn curve(&mut self, curve: &Curve) {
+        let color = curve.color;
+        let (kind, wedge) = match curve.kind {
+            CurveKind::Ellipse => (ShapeKind::Ellipse, None),
+            CurveKind::Arc(wedge) => (ShapeKind::Arc, Some(wedge)),
+            CurveKind::Pie(wedge) => (ShapeKind::Pie, Some(wedge)),
+        };
+        let radii = wedge.map_or([0.; 4], |wedge| {
+            let ([cos, sin], [aperture_sin, aperture_cos]) = (wedge.bisector, wedge.aperture);
+            [cos, sin, aperture_sin, aperture_cos]
+        });
+
+        // One more pixel around the shape for its anti-aliased edge
+        self.quad(
+            curve.x - curve.radius_x - 1.,
+            curve.y - curve.radius_y - 1.,
+            curve.radius_x * 2. + 2.,
+            curve.radius_y * 2. + 2.,
             Vertex {
-                x: x + rect.width / (self.size.x / 2.),
-                y,
-                r: rect.color.r,
-                g: rect.color.g,
-                b: rect.color.b,
+                color: [color.r, color.g, color.b, color.a],
+                extent: [curve.radius_x, curve.radius_y],
+                radii,
+                params: [0., f32::from(kind as u8), curve.stroke_width, 0.],
+                ..Vertex::default()
             },
+        );
+    }
+
+    /// Queues an anti-aliased line.
+    pub fn line(&mut self, from: Vec2f, to: Vec2f, stroke: &Stroke) {
+        self.polyline(&[from, to], false, stroke);
+    }
+
+    /// Queues an anti-aliased line through `points`, back to the first point
+    /// if `closed`.
+    pub fn polyline(&mut self, points: &[Vec2f], closed: bool, stroke: &Stroke) {
+        self.mesh(&stroke::tessellate(points, closed, stroke));
+    }
+
+    /// Queues the area inside `path`, anti-aliased by the exact coverage of
+    /// every pixel.
+    pub fn fill_path(&mut self, path: &Path, rule: FillRule, color: Rgba) {
+        self.mesh(&path.fill(rule, color, self.size.x, self.size.y));
+    }
+
+    /// Queues the outline of every subpath of `path`.
+    pub fn stroke_path(&mut self, path: &Path, stroke: &Stroke) {
+        self.mesh(&path.stroke(stroke));
+    }
+
+    /// Queues a shape filled with `gradient`.
+    pub fn gradient(&mut self, shape: &GradientShape, gradient: &Gradient) {
+        let ramp = gradient.ramp();
+        let row = match self.ramps.iter().position(|row| *row == ramp) {
+            Some(row) => row,
+            None => {
+                // Shapes queued before keep the ramps they were queued with
+                if self.ramps.len() == MAX_RAMPS {
+                    self.draw();
+                }
+                self.ramps.push(ramp);
+                self.ramps.len() - 1
+            }
+        };
+
+        let start = self.vertices.len();
+        match shape {
+            GradientShape::Rect(rect) => self.rectangle(rect),
+            GradientShape::RoundedRect(rect) => self.rounded_rect(rect),
+            GradientShape::Path { path, rule } => self.fill_path(path, *rule, Rgba::default()),
+        }
+
+        let (half_width, half_height) = (self.size.x / 2., self.size.y / 2.);
+        for vertex in &mut self.vertices[start..] {
+            let position = Vec2f::new((vertex.x + 1.) * half_width, (1. - vertex.y) * half_height);
+            let coordinates = gradient.coordinates(position);
+
+            vertex.params[3] = gradient.kind.code();
+            vertex.gradient = [coordinates.x, coordinates.y];
+            vertex.ramp = [row as f32, f32::from(gradient.extend as u8)];
+        }
+    }
+
+    /// Queues `texture` stretched over a rectangle, the texture has to live
+    /// until the next draw.
+    pub fn image(&mut self, texture: &Texture, draw: &ImageDraw) {
+        let binding = (texture.id, draw.filter);
+        if self.textures.last().map(|&(_, id, filter)| (id, filter)) != Some(binding) {
+            self.textures
+                .push((self.vertices.len(), texture.id, draw.filter));
+        }
+
+        let tint = draw.tint;
+        let source = draw.source_rect(texture.width, texture.height);
+        let destination = draw.destination;
+        let start = self.vertices.len();
+        self.quad(
+            destination.x,
+            destination.y,
+            destination.width,
+            destination.height,
             Vertex {
-                x: x + rect.width / (self.size.x / 2.),
-                y: y - rect.height / (self.size.y / 2.),
-                r: rect.color.r,
-                g: rect.color.g,
-                b: rect.color.b,
+                color: [tint.r, tint.g, tint.b, tint.a],
+                params: [0., f32::from(ShapeKind::Image as u8), 0., 0.],
+                ..Vertex::default()
             },
+        );
+
+        // From the center of the quad to normalized texture coordinates
+        let (width, height) = (texture.width as f32, texture.height as f32);
+        for vertex in &mut self.vertices[start..] {
+            let [x, y] = vertex.local;
+            vertex.local = [
+                (source.x + source.width * (0.5 + x / destination.width)) / width,
+                (source.y + source.height * (0.5 + y / destination.height)) / height,
+            ];
+        }
+    }
+
+    /// Queues every sprite of `batch`, they are drawn with one draw call
+    /// from `texture`.
+    pub fn sprites(&mut self, texture: &Texture, batch: &SpriteBatch) {
+        for sprite in &batch.sprites {
+            self.image(
+                texture,
+                &ImageDraw {
+                    source: Some(sprite.source),
+                    destination: sprite.destination,
+                    tint: sprite.tint,
+                    filter: batch.filter,
+                },
+            );
+        }
+    }
+
+    pub fn mesh(&mut self, mesh: &Mesh) {
+        let color = mesh.color;
+        let shape = Vertex {
+            color: [color.r, color.g, color.b, color.a],
+            extent: [mesh.half_width, 0.],
+            params: [0., f32::from(ShapeKind::Mesh as u8), 0., 0.],
+            ..Vertex::default()
+        };
+
+        for vertex in &mesh.vertices {
+            self.vertices.push(Vertex {
+                x: vertex.x / (self.size.x / 2.) - 1.0,
+                y: -vertex.y / (self.size.y / 2.) + 1.0,
+                local: [vertex.across, vertex.edge],
+                ..shape
+            });
+        }
+    }
+
+    /// Queues two triangles covering a rectangle in pixels, `shape` provides
+    /// everything but the positions.
+    fn quad(&mut self, x: f32, y: f32, width: f32, height: f32, shape: Vertex) {
+        let (center_x, center_y) = (x + width / 2., y + height / 2.);
+        let corner = |px: f32, py: f32| Vertex {
+            x: px / (self.size.x / 2.) - 1.0,
+            y: -py / (self.size.y / 2.) + 1.0,
+            local: [px - center_x, py - center_y],
+            ..shape
+        };
+
+        let quad = [
+            corner(x, y),
+            corner(x, y + height),
+            corner(x + width, y),
+            corner(x + width, y + height),
         ];
 
         self.vertices.push(quad[0]);
@@ -171,8 +634,169 @@ impl Renderer {
 impl Drop for Renderer {
     fn drop(&mut self) {
         unsafe {
+            gl::DeleteTextures(1, &self.ramp_texture);
             gl::DeleteBuffers(1, &self.vbo);
             gl::DeleteVertexArrays(1, &self.vao);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{assert_golden, render, TOLERANCE};
+
+    fn rectangles_scene(renderer: &mut Renderer) {
+        let colors = [(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)];
+        for (i, (r, g, b)) in colors.iter().enumerate() {
+            renderer.rectangle(&RenderRect {
+                x: 8. + i as f32 * 28.,
+                y: 8. + i as f32 * 16.,
+                width: 24.,
+                height: 40.,
+                color: Rgba {
+                    r: *r,
+                    g: *g,
+                    b: *b,
+                    a: 255,
+                },
+            });
+        }
+
+        renderer.draw();
+    }
+
+    #[test]
+    fn rectangles() {
+        let image = render(128, 96, |size| {
+            rectangles_scene(&mut Renderer::new(size).unwrap());
+        });
+
+        assert_golden("rectangles", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn resize() {
+        let image = render(128, 96, |size| {
+            let mut renderer = Renderer::new(Vec2f { x: 64., y: 48. }).unwrap();
+            renderer.resize(size);
+            rectangles_scene(&mut renderer);
+        });
+
+        assert_golden("rectangles", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn translucent_rectangles() {
+        let image = render(128, 96, |size| {
+            let mut renderer = Renderer::new(size).unwrap();
+
+            let colors = [0xFF0000, 0x00FF00, 0x0000FF];
+            for (i, color) in colors.iter().enumerate() {
+                renderer.rectangle(&RenderRect {
+                    x: 16. + i as f32 * 24.,
+                    y: 16. + i as f32 * 16.,
+                    width: 56.,
+                    height: 48.,
+                    color: Rgb::from_hex(*color).alpha(128),
+                });
+            }
+
+            renderer.draw();
+        });
+
+        assert_golden("translucent_rectangles", &image, TOLERANCE);
+    }
+
+    #[test]
+    fn sprites_share_a_draw_call() {
+        use crate::renderer::atlas::{SpriteAtlas, SpriteBatch};
+        use crate::renderer::image::{Filter, Image, ImageId};
+
+        let image = render(128, 96, |size| {
+            let mut renderer = Renderer::new(size).unwrap();
+
+            let mut atlas = SpriteAtlas::new(16, 16, 1);
+            let white = Image {
+                width: 2,
+                height: 2,
+                pixels: vec![255; 16],
+            };
+            let sprite = atlas.insert(&white).unwrap();
+            let texture = Texture::new(atlas.image()).unwrap();
+
+            let mut batch = SpriteBatch::new(ImageId(0), Filter::Nearest);
+            for i in 0..4096 {
+                let destination = Rect {
+                    x: (i % 64) as f32 * 2.,
+                    y: (i / 64) as f32 * 1.5,
+                    width: 1.,
+                    height: 1.,
+                };
+                batch.push(sprite, destination, Rgb::from_hex(0xFFFFFF).into());
+            }
+            renderer.sprites(&texture, &batch);
+
+            assert_eq!(renderer.vertices.len(), 4096 * 6);
+            assert_eq!(renderer.textures.len(), 1);
+            renderer.draw();
+        });
+
+        let covered = image.pixels.chunks_exact(4).filter(|pixel| pixel[0] == 255);
+        assert_eq!(covered.count(), 4096);
+    }
+
+    #[test]
+    fn curve_coverage() {
+        use crate::renderer::shape::{Ellipse, Pie, Style};
+        use std::f32::consts::PI;
+
+        let white = Rgb::from_hex(0xFFFFFF).into();
+        let ring = Ellipse {
+            x: 40.,
+            y: 40.,
+            radius_x: 36.,
+            radius_y: 24.,
+            color: white,
+            style: Style::Stroke(4.),
+        };
+        let pie = Pie {
+            x: 40.,
+            y: 40.,
+            radius_x: 30.,
+            radius_y: 30.,
+            start: 0.3,
+            sweep: 2.,
+            color: white,
+            style: Style::Fill,
+        };
+        let curves = [
+            (
+                Ellipse::circle(40.3, 40.6, 30., white, Style::Fill).curve(),
+                PI * 30. * 30.,
+            ),
+            (ring.curve(), PI * (36. * 24. - 32. * 20.)),
+            (pie.curve(), 30. * 30. * 2. / 2.),
+        ];
+
+        for (curve, area) in curves.iter() {
+            let image = render(80, 80, |size| {
+                let mut renderer = Renderer::new(size).unwrap();
+                renderer.curve(&curve.unwrap());
+                renderer.draw();
+            });
+
+            let covered: f32 = image
+                .pixels
+                .chunks_exact(4)
+                .map(|pixel| f32::from(pixel[0]) / 255.)
+                .sum();
+            assert!(
+                (covered - area).abs() / area < 0.01,
+                "{} is not {}",
+                covered,
+                area
+            );
+        }
+    }
+}
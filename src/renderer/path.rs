@@ -0,0 +1,606 @@
+use crate::renderer::mesh::{Mesh, MeshVertex};
+use crate::renderer::stroke::{self, Stroke};
+use crate::renderer::Rgba;
+use crate::vectors::Vec2f;
+
+use std::f32::consts::TAU;
+use std::mem;
+
+/// Largest distance between a curve and the lines it is flattened into.
+const TOLERANCE: f32 = 0.05;
+
+/// Which areas enclosed by a path are filled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    /// Areas the path winds around at least once in total.
+    NonZero,
+    /// Areas enclosed by an odd number of edges.
+    EvenOdd,
+}
+
+impl FillRule {
+    /// How much of a pixel is filled with the winding number accumulated
+    /// over it.
+    fn coverage(self, winding: f32) -> f32 {
+        match self {
+            FillRule::NonZero => winding.abs().min(1.),
+            FillRule::EvenOdd => {
+                let winding = winding.abs() % 2.;
+                if winding > 1. {
+                    2. - winding
+                } else {
+                    winding
+                }
+            }
+        }
+    }
+}
+
+/// A sequence of connected lines, curves are already flattened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubPath {
+    pub points: Vec<Vec2f>,
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    pub subpaths: Vec<SubPath>,
+}
+
+/// Builds a [`Path`] in pixels, like the path commands of SVG.
+#[derive(Debug, Clone, Default)]
+pub struct PathBuilder {
+    path: Path,
+    current: Vec<Vec2f>,
+}
+
+impl PathBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new subpath.
+    pub fn move_to(&mut self, point: Vec2f) -> &mut Self {
+        self.finish(false);
+        self.current.push(point);
+        self
+    }
+
+    pub fn line_to(&mut self, point: Vec2f) -> &mut Self {
+        self.current.push(point);
+        self
+    }
+
+    pub fn quadratic_to(&mut self, control: Vec2f, to: Vec2f) -> &mut Self {
+        let from = self.position();
+
+        // The second derivative bounds the distance to the chords
+        let deviation = (from - control * 2. + to).length();
+        let steps = (deviation / (4. * TOLERANCE)).sqrt().ceil().max(1.) as usize;
+
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let u = 1. - t;
+            self.current
+                .push(from * (u * u) + control * (2. * u * t) + to * (t * t));
+        }
+        self
+    }
+
+    pub fn cubic_to(&mut self, control1: Vec2f, control2: Vec2f, to: Vec2f) -> &mut Self {
+        let from = self.position();
+
+        let deviation = (from - control1 * 2. + control2)
+            .length()
+            .max((control1 - control2 * 2. + to).length());
+        let steps = (3. * deviation / (4. * TOLERANCE)).sqrt().ceil().max(1.) as usize;
+
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let u = 1. - t;
+            self.current.push(
+                from * (u * u * u)
+                    + control1 * (3. * u * u * t)
+                    + control2 * (3. * u * t * t)
+                    + to * (t * t * t),
+            );
+        }
+        self
+    }
+
+    /// Adds an elliptical arc to `to`, like the `A` command of SVG.
+    ///
+    /// The ellipse is rotated by `rotation` radians and scaled up if it is
+    /// too small to reach `to`. Of the four arcs through both points,
+    /// `large_arc` picks one spanning more than 180 degrees and `sweep` one
+    /// going clockwise.
+    pub fn arc_to(
+        &mut self,
+        radii: Vec2f,
+        rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Vec2f,
+    ) -> &mut Self {
+        let from = self.position();
+        let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+        if rx == 0. || ry == 0. || from == to {
+            return self.line_to(to);
+        }
+
+        // The center parameterization from the SVG implementation notes
+        let (sin, cos) = rotation.sin_cos();
+        let half = (from - to) * 0.5;
+        let x1 = cos * half.x + sin * half.y;
+        let y1 = -sin * half.x + cos * half.y;
+
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1. {
+            rx *= lambda.sqrt();
+            ry *= lambda.sqrt();
+        }
+
+        let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut factor = (numerator.max(0.) / denominator).sqrt();
+        if large_arc == sweep {
+            factor = -factor;
+        }
+        let cx = factor * rx * y1 / ry;
+        let cy = -factor * ry * x1 / rx;
+        let center = Vec2f::new(
+            cos * cx - sin * cy + (from.x + to.x) / 2.,
+            sin * cx + cos * cy + (from.y + to.y) / 2.,
+        );
+
+        let angle = |x: f32, y: f32| y.atan2(x);
+        let start = angle((x1 - cx) / rx, (y1 - cy) / ry);
+        let mut delta = angle((-x1 - cx) / rx, (-y1 - cy) / ry) - start;
+        if sweep && delta < 0. {
+            delta += TAU;
+        } else if !sweep && delta > 0. {
+            delta -= TAU;
+        }
+
+        let radius = rx.max(ry);
+        let step = if radius > TOLERANCE {
+            2. * (1. - TOLERANCE / radius).acos()
+        } else {
+            TAU
+        };
+        let steps = (delta.abs() / step).ceil().max(1.) as usize;
+
+        for i in 1..steps {
+            let (sin_t, cos_t) = (start + delta * i as f32 / steps as f32).sin_cos();
+            let (x, y) = (rx * cos_t, ry * sin_t);
+            self.current
+                .push(center + Vec2f::new(cos * x - sin * y, sin * x + cos * y));
+        }
+        self.line_to(to)
+    }
+
+    /// Connects the subpath back to its first point.
+    pub fn close(&mut self) -> &mut Self {
+        let start = self.current.first().copied();
+        self.finish(true);
+
+        // Following segments start where the closed subpath did
+        if let Some(start) = start {
+            self.current.push(start);
+        }
+        self
+    }
+
+    /// Takes the path built so far.
+    pub fn build(&mut self) -> Path {
+        self.finish(false);
+        mem::take(&mut self.path)
+    }
+
+    fn position(&mut self) -> Vec2f {
+        match self.current.last() {
+            Some(point) => *point,
+            None => {
+                let start = Vec2f::new(0., 0.);
+                self.current.push(start);
+                start
+            }
+        }
+    }
+
+    fn finish(&mut self, closed: bool) {
+        let points = mem::take(&mut self.current);
+        if points.len() > 1 {
+            self.path.subpaths.push(SubPath { points, closed });
+        }
+    }
+}
+
+impl Path {
+    /// Tessellates the outline of every subpath.
+    pub fn stroke(&self, stroke: &Stroke) -> Mesh {
+        let mut mesh = Mesh::new(stroke.width / 2., stroke.color);
+        for subpath in &self.subpaths {
+            let outline = stroke::tessellate(&subpath.points, subpath.closed, stroke);
+            mesh.vertices.extend(outline.vertices);
+        }
+        mesh
+    }
+
+    /// Tessellates the area inside the path, every subpath is closed. Only
+    /// the part inside `width` and `height` from the origin is covered.
+    ///
+    /// The exact area of every pixel covered by the path is accumulated
+    /// row by row, like font rasterizers do, and drawn as quads of constant
+    /// coverage. Neighbouring pixels with the same coverage share a quad.
+    pub fn fill(&self, rule: FillRule, color: Rgba, width: f32, height: f32) -> Mesh {
+        let mut mesh = Mesh::fill(color);
+
+        let points = self.subpaths.iter().flat_map(|subpath| &subpath.points);
+        let (mut min, mut max) = (Vec2f::new(width, height), Vec2f::new(0., 0.));
+        for point in points {
+            min = Vec2f::new(min.x.min(point.x), min.y.min(point.y));
+            max = Vec2f::new(max.x.max(point.x), max.y.max(point.y));
+        }
+        let left = min.x.max(0.).floor() as i32;
+        let top = min.y.max(0.).floor() as i32;
+        let right = max.x.min(width).ceil() as i32;
+        let bottom = max.y.min(height).ceil() as i32;
+        if left >= right || top >= bottom {
+            return mesh;
+        }
+
+        // Lines right of the bounds add their winding to a column after the
+        // last one, which is followed by one more for the area spilling over
+        let columns = (right - left) as usize + 2;
+        let origin = Vec2f::new(left as f32, top as f32);
+        let mut accumulation = Accumulation {
+            cells: vec![0.; columns * (bottom - top) as usize],
+            columns,
+            rows: (bottom - top) as usize,
+        };
+        for subpath in &self.subpaths {
+            let points = &subpath.points;
+            for (i, from) in points.iter().enumerate() {
+                let to = points[(i + 1) % points.len()];
+                accumulation.line(*from - origin, to - origin);
+            }
+        }
+
+        for (row, cells) in accumulation.cells.chunks_exact(columns).enumerate() {
+            let y = (top + row as i32) as f32;
+            let x = |column: usize| origin.x + column as f32;
+
+            let mut winding = 0.;
+            let mut run: Option<(usize, f32)> = None;
+            for (column, cell) in cells.iter().enumerate().take(columns - 2) {
+                winding += cell;
+
+                // Quantized like the color it ends up in
+                let coverage = (rule.coverage(winding) * 255.).round() / 255.;
+                match run {
+                    Some((_, previous)) if previous == coverage => {}
+                    _ => {
+                        if let Some((start, previous)) = run {
+                            quad(&mut mesh, x(start), x(column), y, previous);
+                        }
+                        run = (coverage > 0.).then_some((column, coverage));
+                    }
+                }
+            }
+            if let Some((start, coverage)) = run {
+                quad(&mut mesh, x(start), x(columns - 2), y, coverage);
+            }
+        }
+
+        mesh
+    }
+}
+
+/// Adds a quad of constant coverage over one row of pixels.
+fn quad(mesh: &mut Mesh, left: f32, right: f32, y: f32, coverage: f32) {
+    let corner = |x, y| MeshVertex {
+        x,
+        y,
+        across: 0.,
+        edge: coverage,
+    };
+    let (a, b) = (corner(left, y), corner(right, y));
+    let (c, d) = (corner(right, y + 1.), corner(left, y + 1.));
+    mesh.fan(&[a, b, c, d]);
+}
+
+/// Signed areas covered by lines, the prefix sum of a row is the winding
+/// number of every pixel weighted by how much of it is covered.
+struct Accumulation {
+    cells: Vec<f32>,
+    columns: usize,
+    rows: usize,
+}
+
+impl Accumulation {
+    fn line(&mut self, from: Vec2f, to: Vec2f) {
+        // Parts left of the cells only add their winding to the first column,
+        // parts right of them to the one after the last
+        let limit = (self.columns - 2) as f32;
+        let mut points = vec![from];
+        for x in [0., limit] {
+            let (a, b) = (from.x - x, to.x - x);
+            if a * b < 0. {
+                let t = a / (a - b);
+                points.push(from + (to - from) * t);
+            }
+        }
+        points.push(to);
+        points.sort_by(|a, b| {
+            let t = |point: &Vec2f| (*point - from).dot(to - from);
+            t(a).total_cmp(&t(b))
+        });
+
+        for pair in points.windows(2) {
+            let clamp = |point: Vec2f| Vec2f::new(point.x.clamp(0., limit), point.y);
+            self.clipped_line(clamp(pair[0]), clamp(pair[1]));
+        }
+    }
+
+    fn clipped_line(&mut self, from: Vec2f, to: Vec2f) {
+        if from.y == to.y {
+            return;
+        }
+        let (direction, from, to) = if from.y < to.y {
+            (1., from, to)
+        } else {
+            (-1., to, from)
+        };
+        let x_at = |y: f32| from.x + (to.x - from.x) * (y - from.y) / (to.y - from.y);
+
+        let first = from.y.max(0.).floor() as usize;
+        let last = (to.y.ceil().max(0.) as usize).min(self.rows);
+        for row in first..last {
+            let top = (row as f32).max(from.y);
+            let bottom = ((row + 1) as f32).min(to.y);
+            if bottom <= top {
+                continue;
+            }
+
+            let cells = &mut self.cells[row * self.columns..(row + 1) * self.columns];
+            deposit(cells, x_at(top), x_at(bottom), (bottom - top) * direction);
+        }
+    }
+}
+
+/// Adds the area right of a line within one row, which spans `height`
+/// pixels of the row, to the cells it covers.
+fn deposit(cells: &mut [f32], x0: f32, x1: f32, height: f32) {
+    let (x0, x1) = if x0 < x1 { (x0, x1) } else { (x1, x0) };
+    let start = x0.floor() as usize;
+    let end = x1.ceil() as usize;
+
+    if end <= start + 1 {
+        let middle = 0.5 * (x0 + x1) - start as f32;
+        cells[start] += height - height * middle;
+        cells[start + 1] += height * middle;
+        return;
+    }
+
+    let slope = (x1 - x0).recip();
+    let start_fraction = x0 - start as f32;
+    let first = 0.5 * slope * (1. - start_fraction) * (1. - start_fraction);
+    let end_fraction = x1 - end as f32 + 1.;
+    let last = 0.5 * slope * end_fraction * end_fraction;
+
+    cells[start] += height * first;
+    if end == start + 2 {
+        cells[start + 1] += height * (1. - first - last);
+    } else {
+        let second = slope * (1.5 - start_fraction);
+        cells[start + 1] += height * (second - first);
+        for cell in &mut cells[start + 2..end - 1] {
+            *cell += height * slope;
+        }
+        let before_last = second + (end - start - 3) as f32 * slope;
+        cells[end - 1] += height * (1. - before_last - last);
+    }
+    cells[end] += height * last;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::{self, assert_area};
+    use crate::renderer::backend::Backend;
+    use crate::renderer::Rgb;
+
+    use std::f32::consts::PI;
+
+    /// Sums the coverage of a white fill on black.
+    fn area(path: &Path, rule: FillRule) -> f32 {
+        coverage::area(|backend| backend.fill_path(path, rule, Rgb::from_hex(0xFFFFFF).into()))
+    }
+
+    fn square(builder: &mut PathBuilder, x: f32, y: f32, size: f32, clockwise: bool) {
+        let mut corners = [
+            Vec2f::new(x + size, y),
+            Vec2f::new(x + size, y + size),
+            Vec2f::new(x, y + size),
+        ];
+        if !clockwise {
+            corners.reverse();
+        }
+
+        builder.move_to(Vec2f::new(x, y));
+        for corner in corners {
+            builder.line_to(corner);
+        }
+        builder.close();
+    }
+
+    #[test]
+    fn builder() {
+        let mut builder = PathBuilder::new();
+        builder
+            .move_to(Vec2f::new(1., 2.))
+            .line_to(Vec2f::new(3., 4.))
+            .close()
+            .line_to(Vec2f::new(5., 6.))
+            .move_to(Vec2f::new(7., 8.));
+        let path = builder.build();
+
+        assert_eq!(
+            path.subpaths,
+            [
+                SubPath {
+                    points: vec![Vec2f::new(1., 2.), Vec2f::new(3., 4.)],
+                    closed: true,
+                },
+                SubPath {
+                    points: vec![Vec2f::new(1., 2.), Vec2f::new(5., 6.)],
+                    closed: false,
+                },
+            ]
+        );
+        assert!(builder.build().subpaths.is_empty());
+    }
+
+    #[test]
+    fn flattening() {
+        let (from, control, to) = (
+            Vec2f::new(10., 100.),
+            Vec2f::new(60., 0.),
+            Vec2f::new(110., 100.),
+        );
+        let path = PathBuilder::new()
+            .move_to(from)
+            .quadratic_to(control, to)
+            .build();
+
+        // Every chord stays within the tolerance of the parabola
+        let points = &path.subpaths[0].points;
+        for pair in points.windows(2) {
+            let middle = (pair[0] + pair[1]) * 0.5;
+            let t = (middle.x - from.x) / (to.x - from.x);
+            let y = from.y * (1. - t) * (1. - t) + control.y * 2. * t * (1. - t) + to.y * t * t;
+            assert!((middle.y - y).abs() <= TOLERANCE);
+        }
+
+        // A parabolic segment covers two thirds of its bounding box
+        assert_area(area(&path, FillRule::NonZero), 2. / 3. * 100. * 50.);
+
+        let (radius, kappa) = (40., 0.552_284_8);
+        let handle = radius * kappa;
+        let path = PathBuilder::new()
+            .move_to(Vec2f::new(24., 64.))
+            .cubic_to(
+                Vec2f::new(24., 64. - handle),
+                Vec2f::new(64. - handle, 24.),
+                Vec2f::new(64., 24.),
+            )
+            .cubic_to(
+                Vec2f::new(64. + handle, 24.),
+                Vec2f::new(104., 64. - handle),
+                Vec2f::new(104., 64.),
+            )
+            .cubic_to(
+                Vec2f::new(104., 64. + handle),
+                Vec2f::new(64. + handle, 104.),
+                Vec2f::new(64., 104.),
+            )
+            .cubic_to(
+                Vec2f::new(64. - handle, 104.),
+                Vec2f::new(24., 64. + handle),
+                Vec2f::new(24., 64.),
+            )
+            .build();
+        assert_area(area(&path, FillRule::NonZero), PI * radius * radius);
+    }
+
+    #[test]
+    fn arcs() {
+        let radii = Vec2f::new(30., 30.);
+        let circle = PathBuilder::new()
+            .move_to(Vec2f::new(34.3, 64.))
+            .arc_to(radii, 0., false, true, Vec2f::new(94.3, 64.))
+            .arc_to(radii, 0., false, true, Vec2f::new(34.3, 64.))
+            .build();
+        assert_area(area(&circle, FillRule::NonZero), PI * 900.);
+
+        // Too small radii are scaled up to a half circle
+        let half = PathBuilder::new()
+            .move_to(Vec2f::new(34., 64.))
+            .arc_to(Vec2f::new(10., 10.), 0., true, false, Vec2f::new(94., 64.))
+            .close()
+            .build();
+        assert_area(area(&half, FillRule::NonZero), PI * 450.);
+
+        // A rotated ellipse, the large arc of the same endpoints
+        let ellipse = PathBuilder::new()
+            .move_to(Vec2f::new(40., 40.))
+            .arc_to(
+                Vec2f::new(40., 20.),
+                PI / 4.,
+                true,
+                true,
+                Vec2f::new(41., 41.),
+            )
+            .build();
+        let large = area(&ellipse, FillRule::NonZero);
+        assert!(large > 0.9 * PI * 800., "{}", large);
+    }
+
+    #[test]
+    fn fill_rules() {
+        // Overlapping squares winding the same way
+        let mut builder = PathBuilder::new();
+        square(&mut builder, 20., 20., 40., true);
+        square(&mut builder, 40.5, 40.5, 40., true);
+        let path = builder.build();
+        let overlap = 19.5 * 19.5;
+        assert_area(area(&path, FillRule::NonZero), 3200. - overlap);
+        assert_area(area(&path, FillRule::EvenOdd), 3200. - 2. * overlap);
+
+        // A hole winding the other way
+        let mut builder = PathBuilder::new();
+        square(&mut builder, 10.3, 10.3, 80., true);
+        square(&mut builder, 30.3, 30.3, 40., false);
+        let path = builder.build();
+        assert_area(area(&path, FillRule::NonZero), 6400. - 1600.);
+        assert_area(area(&path, FillRule::EvenOdd), 6400. - 1600.);
+
+        // The pentagon in the middle of a star winds twice
+        let mut builder = PathBuilder::new();
+        for i in 0..5 {
+            let angle = i as f32 * 4. * PI / 5. - PI / 2.;
+            let point = Vec2f::new(64. + 50. * angle.cos(), 64. + 50. * angle.sin());
+            if i == 0 {
+                builder.move_to(point);
+            } else {
+                builder.line_to(point);
+            }
+        }
+        let star = builder.close().build();
+        let non_zero = area(&star, FillRule::NonZero);
+        let even_odd = area(&star, FillRule::EvenOdd);
+
+        // The circumradius of the pentagon inside a regular pentagram
+        let inner = 50. * (2. * PI / 5.).cos() / (PI / 5.).cos();
+        let pentagon = 2.5 * inner * inner * (2. * PI / 5.).sin();
+        assert_area(non_zero - even_odd, pentagon);
+    }
+
+    #[test]
+    fn clipping() {
+        let mut builder = PathBuilder::new();
+        square(&mut builder, -40., -20., 80., true);
+        square(&mut builder, 100., 100.5, 60., true);
+        let path = builder.build();
+        assert_area(area(&path, FillRule::NonZero), 40. * 60. + 28. * 27.5);
+
+        // Winding stays correct left of the canvas
+        let mut builder = PathBuilder::new();
+        square(&mut builder, -60., 10., 100., true);
+        square(&mut builder, -50., 20., 20., false);
+        let path = builder.build();
+        assert_area(area(&path, FillRule::NonZero), 40. * 100.);
+    }
+}
@@ -0,0 +1,593 @@
+use crate::gl;
+use crate::renderer::path::Path;
+use crate::renderer::shader::{Program, Shader, ShaderError};
+use crate::renderer::shaping::{ShapedKey, Shaper};
+use crate::renderer::Rgba;
+use crate::vectors::Vec2f;
+
+use gl::types::*;
+use std::mem::size_of;
+use std::ptr;
+
+use crossfont::{BitmapBuffer, Error as RasterizerError, RasterizedGlyph};
+
+/// Size in points distance fields are generated at, they are scaled to
+/// every other size.
+pub const FIELD_SIZE: f32 = 36.;
+
+/// Distance in field pixels a field reaches out of and into a glyph.
+/// Effects further away from the outline are cut off.
+pub const SPREAD: f32 = 12.;
+
+static FRAGMENT: &str = include_str!("../../res/sdf.frag");
+static VERTEX: &str = include_str!("../../res/sdf.vert");
+
+/// A line around the glyphs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    pub width: f32,
+    pub color: Rgba,
+}
+
+/// Light fading out from the outline of the glyphs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glow {
+    pub radius: f32,
+    pub color: Rgba,
+}
+
+/// The glyphs again below them, moved by `offset` and blurred by `blur`
+/// pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Shadow {
+    pub offset: Vec2f,
+    pub blur: f32,
+    pub color: Rgba,
+}
+
+/// Drawn around glyphs from their distance fields, shadows below glows below
+/// outlines. Every effect is drawn for all glyphs of a batch before the next
+/// one, so no effect covers another glyph.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TextEffects {
+    pub outline: Option<Outline>,
+    pub glow: Option<Glow>,
+    pub shadow: Option<Shadow>,
+}
+
+/// A layer of the glyphs drawn from distance fields, in the order they are
+/// drawn. The discriminant is the `pass` of `sdf.frag`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pass {
+    Shadow = 0,
+    Glow = 1,
+    Outline = 2,
+    Fill = 3,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 4] = [Pass::Shadow, Pass::Glow, Pass::Outline, Pass::Fill];
+
+    /// Whether glyphs with `effects` have anything to draw in the pass.
+    pub fn draws(self, effects: &TextEffects) -> bool {
+        match self {
+            Pass::Shadow => effects.shadow.is_some_and(|shadow| shadow.color.a > 0),
+            Pass::Glow => effects.glow.is_some_and(|glow| glow.radius > 0.),
+            Pass::Outline => effects.outline.is_some_and(|outline| outline.width > 0.),
+            Pass::Fill => true,
+        }
+    }
+}
+
+/// Encodes a distance in field pixels, positive inside of the glyph.
+fn encode(distance: f32) -> u8 {
+    ((0.5 + distance / (2. * SPREAD)).clamp(0., 1.) * 255.).round() as u8
+}
+
+/// A distance field as a glyph, which atlases store like any other glyph.
+fn field_glyph(left: i32, top: i32, width: i32, height: i32, field: Vec<u8>) -> RasterizedGlyph {
+    RasterizedGlyph {
+        character: '\0',
+        width,
+        height,
+        top,
+        left,
+        buffer: BitmapBuffer::Rgb(field.iter().flat_map(|&d| [d, d, d]).collect()),
+    }
+}
+
+/// The distance field of a glyph outline in pixels, relative to the origin
+/// of the glyph with y pointing down.
+pub fn from_outline(outline: &Path) -> RasterizedGlyph {
+    let mut segments = Vec::new();
+    for subpath in &outline.subpaths {
+        let points = &subpath.points;
+        for (i, from) in points.iter().enumerate() {
+            segments.push((*from, points[(i + 1) % points.len()]));
+        }
+    }
+
+    let points = outline.subpaths.iter().flat_map(|subpath| &subpath.points);
+    let (mut min, mut max) = (
+        Vec2f::new(f32::MAX, f32::MAX),
+        Vec2f::new(f32::MIN, f32::MIN),
+    );
+    for point in points {
+        min = Vec2f::new(min.x.min(point.x), min.y.min(point.y));
+        max = Vec2f::new(max.x.max(point.x), max.y.max(point.y));
+    }
+    if segments.is_empty() || min.x > max.x {
+        return RasterizedGlyph::default();
+    }
+
+    let left = (min.x - SPREAD).floor() as i32;
+    let top = (min.y - SPREAD).floor() as i32;
+    let width = (max.x + SPREAD).ceil() as i32 - left;
+    let height = (max.y + SPREAD).ceil() as i32 - top;
+
+    let mut field = Vec::with_capacity((width * height) as usize);
+    for row in 0..height {
+        for column in 0..width {
+            let point = Vec2f::new((left + column) as f32 + 0.5, (top + row) as f32 + 0.5);
+
+            let mut nearest = f32::MAX;
+            let mut winding = 0;
+            for &(from, to) in &segments {
+                nearest = nearest.min(segment_distance(point, from, to));
+
+                let cross =
+                    (to.x - from.x) * (point.y - from.y) - (point.x - from.x) * (to.y - from.y);
+                if from.y <= point.y && to.y > point.y && cross > 0. {
+                    winding += 1;
+                } else if to.y <= point.y && from.y > point.y && cross < 0. {
+                    winding -= 1;
+                }
+            }
+
+            field.push(encode(if winding != 0 { nearest } else { -nearest }));
+        }
+    }
+
+    field_glyph(left, -top, width, height, field)
+}
+
+/// The distance field of a rasterized glyph, for glyphs without an outline.
+/// Pixels at least half covered are inside, colored glyphs lose their
+/// colors.
+pub fn from_coverage(glyph: &RasterizedGlyph) -> RasterizedGlyph {
+    let (bytes, coverage_byte, buffer) = match &glyph.buffer {
+        BitmapBuffer::Rgb(buffer) => (3, 0, buffer),
+        BitmapBuffer::Rgba(buffer) => (4, 3, buffer),
+    };
+    if glyph.width == 0 || glyph.height == 0 {
+        return RasterizedGlyph::default();
+    }
+
+    let inside = |column: i32, row: i32| {
+        column >= 0
+            && row >= 0
+            && column < glyph.width
+            && row < glyph.height
+            && buffer[((row * glyph.width + column) * bytes + coverage_byte) as usize] >= 128
+    };
+
+    let spread = SPREAD.ceil() as i32;
+    let (width, height) = (glyph.width + spread * 2, glyph.height + spread * 2);
+    let mut field = Vec::with_capacity((width * height) as usize);
+    for row in -spread..glyph.height + spread {
+        for column in -spread..glyph.width + spread {
+            let state = inside(column, row);
+
+            // The nearest pixel on the other side, its edge is half a
+            // pixel closer
+            let mut nearest = SPREAD + 0.5;
+            for y in row - spread..=row + spread {
+                for x in column - spread..=column + spread {
+                    if inside(x, y) != state {
+                        let (dx, dy) = ((x - column) as f32, (y - row) as f32);
+                        nearest = nearest.min((dx * dx + dy * dy).sqrt());
+                    }
+                }
+            }
+
+            let distance = nearest - 0.5;
+            field.push(encode(if state { distance } else { -distance }));
+        }
+    }
+
+    field_glyph(
+        glyph.left - spread,
+        glyph.top + spread,
+        width,
+        height,
+        field,
+    )
+}
+
+fn segment_distance(point: Vec2f, from: Vec2f, to: Vec2f) -> f32 {
+    let direction = to - from;
+    let length = direction.dot(direction);
+    let t = if length > 0. {
+        ((point - from).dot(direction) / length).clamp(0., 1.)
+    } else {
+        0.
+    };
+
+    (point - (from + direction * t)).length()
+}
+
+/// The distance field of a glyph, generated from its outline or from its
+/// coverage for glyphs without one.
+pub(crate) fn rasterize(
+    shaper: &mut Shaper,
+    key: ShapedKey,
+) -> Result<RasterizedGlyph, RasterizerError> {
+    if let Some(outline) = shaper.outline(key) {
+        return Ok(from_outline(&outline));
+    }
+    match shaper.rasterize(key) {
+        Ok(rasterized) => Ok(from_coverage(&rasterized)),
+        Err(RasterizerError::MissingGlyph(rasterized)) => {
+            Err(RasterizerError::MissingGlyph(from_coverage(&rasterized)))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Distance in field pixels from a point of a field to the outline, positive
+/// inside of the glyph. Fields are filtered linearly with the padding around
+/// them in the atlas, like `fieldDistance` in `sdf.frag`.
+pub(crate) fn field_distance(field: &RasterizedGlyph, x: f32, y: f32) -> f32 {
+    let (width, height) = (field.width as f32, field.height as f32);
+    if x < 0. || y < 0. || x > width || y > height {
+        return -SPREAD;
+    }
+
+    let buffer = match &field.buffer {
+        BitmapBuffer::Rgb(buffer) => buffer,
+        BitmapBuffer::Rgba(_) => return -SPREAD,
+    };
+    let texel = |column: i32, row: i32| {
+        if column < 0 || row < 0 || column >= field.width || row >= field.height {
+            return 0;
+        }
+        i32::from(buffer[((row * field.width + column) * 3) as usize])
+    };
+
+    // In fixed point with 8 bits of fraction, like the texture units
+    let (x, y) = (
+        ((x - 0.5) * 256.).floor() as i32,
+        ((y - 0.5) * 256.).floor() as i32,
+    );
+    let (column, row) = (x >> 8, y >> 8);
+    let (fx, fy) = (x & 255, y & 255);
+    let lerp = |from: i32, to: i32, weight: i32| from + (((to - from) * weight + 128) >> 8);
+
+    let top = lerp(texel(column, row), texel(column + 1, row), fx);
+    let bottom = lerp(texel(column, row + 1), texel(column + 1, row + 1), fx);
+    let value = lerp(top, bottom, fy) as f32 / 255.;
+    (value - 0.5) * 2. * SPREAD
+}
+
+/// The premultiplied color `pass` draws at a pixel `inside` screen pixels
+/// inside of the outline of a glyph, and `shadow` pixels inside of its
+/// shadow. Mirrors `main` in `sdf.frag`.
+pub(crate) fn shade(
+    pass: Pass,
+    inside: f32,
+    shadow: f32,
+    color: Rgba,
+    effects: &TextEffects,
+) -> [f32; 4] {
+    let premultiplied = |color: Rgba, alpha: f32| {
+        let alpha = f32::from(color.a) / 255. * alpha;
+        [color.r, color.g, color.b, 255].map(|channel| f32::from(channel) / 255. * alpha)
+    };
+    let coverage = |inside: f32, softness: f32| (inside / softness.max(1.) + 0.5).clamp(0., 1.);
+
+    match pass {
+        Pass::Shadow => match effects.shadow.filter(|shadow| shadow.color.a > 0) {
+            Some(effect) => premultiplied(effect.color, coverage(shadow, effect.blur)),
+            None => [0.; 4],
+        },
+        Pass::Glow => match effects.glow.filter(|glow| glow.radius > 0.) {
+            Some(glow) => {
+                let alpha = (1. + inside / glow.radius).clamp(0., 1.);
+                premultiplied(glow.color, alpha * alpha)
+            }
+            None => [0.; 4],
+        },
+        Pass::Outline => match effects.outline.filter(|outline| outline.width > 0.) {
+            Some(outline) => premultiplied(outline.color, coverage(inside + outline.width, 1.)),
+            None => [0.; 4],
+        },
+        Pass::Fill => premultiplied(color, coverage(inside, 1.)),
+    }
+}
+
+/// A glyph drawn from its distance field.
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct FieldInstance {
+    /// Left, top, width and height on the screen.
+    pub rect: [f32; 4],
+    /// Left, top, width and height of the field in the atlas.
+    pub uv: [f32; 4],
+    pub color: [u8; 4],
+    /// Screen pixels per field pixel.
+    pub scale: f32,
+    /// Width of the outline, radius of the glow and blur of the shadow.
+    pub effects: [f32; 3],
+    pub shadow_offset: [f32; 2],
+    pub outline_color: [u8; 4],
+    pub glow_color: [u8; 4],
+    pub shadow_color: [u8; 4],
+}
+
+impl FieldInstance {
+    /// A glyph with `effects` around it, which are kept with every glyph so
+    /// draws with other effects share a batch.
+    pub fn new(
+        rect: [f32; 4],
+        uv: [f32; 4],
+        color: Rgba,
+        scale: f32,
+        effects: &TextEffects,
+    ) -> Self {
+        let bytes = |color: Option<Rgba>| {
+            let color = color.unwrap_or_default();
+            [color.r, color.g, color.b, color.a]
+        };
+        let (outline, glow, shadow) = (effects.outline, effects.glow, effects.shadow);
+        let offset = shadow.map_or(Vec2f::new(0., 0.), |shadow| shadow.offset);
+
+        Self {
+            rect,
+            uv,
+            color: bytes(Some(color)),
+            scale,
+            effects: [
+                outline.map_or(0., |outline| outline.width),
+                glow.map_or(0., |glow| glow.radius),
+                shadow.map_or(0., |shadow| shadow.blur),
+            ],
+            shadow_offset: [offset.x, offset.y],
+            outline_color: bytes(outline.map(|outline| outline.color)),
+            glow_color: bytes(glow.map(|glow| glow.color)),
+            shadow_color: bytes(shadow.map(|shadow| shadow.color)),
+        }
+    }
+
+    /// Whether the glyph has anything to draw in `pass`.
+    fn draws(&self, pass: Pass) -> bool {
+        match pass {
+            Pass::Shadow => self.shadow_color[3] > 0,
+            Pass::Glow => self.effects[1] > 0.,
+            Pass::Outline => self.effects[0] > 0.,
+            Pass::Fill => true,
+        }
+    }
+}
+
+/// Draws glyphs from their distance fields in an atlas.
+#[derive(Debug)]
+pub(crate) struct FieldPipeline {
+    pub program: Program,
+    vao: GLuint,
+    ebo: GLuint,
+    vbo_instance: GLuint,
+    pub tex: GLuint,
+    pub instances: Vec<FieldInstance>,
+}
+
+impl FieldPipeline {
+    pub fn new(capacity: usize) -> Result<Self, ShaderError> {
+        let program = Program::new(
+            Shader::new(gl::VERTEX_SHADER, VERTEX)?,
+            Shader::new(gl::FRAGMENT_SHADER, FRAGMENT)?,
+        )?;
+
+        let (mut vao, mut ebo, mut vbo_instance) = (0, 0, 0);
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut ebo);
+            gl::GenBuffers(1, &mut vbo_instance);
+            gl::BindVertexArray(vao);
+
+            let indices: [u32; 6] = [0, 1, 3, 1, 2, 3];
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (6 * size_of::<u32>()) as isize,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo_instance);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (capacity * size_of::<FieldInstance>()) as isize,
+                ptr::null(),
+                gl::STREAM_DRAW,
+            );
+
+            let stride = size_of::<FieldInstance>() as i32;
+            let attributes = [
+                (4, gl::FLOAT, gl::FALSE, 0),
+                (4, gl::FLOAT, gl::FALSE, 16),
+                (4, gl::UNSIGNED_BYTE, gl::TRUE, 32),
+                (1, gl::FLOAT, gl::FALSE, 36),
+                (3, gl::FLOAT, gl::FALSE, 40),
+                (2, gl::FLOAT, gl::FALSE, 52),
+                (4, gl::UNSIGNED_BYTE, gl::TRUE, 60),
+                (4, gl::UNSIGNED_BYTE, gl::TRUE, 64),
+                (4, gl::UNSIGNED_BYTE, gl::TRUE, 68),
+            ];
+            for (index, (count, kind, normalized, offset)) in attributes.iter().enumerate() {
+                let index = index as u32;
+                gl::VertexAttribPointer(
+                    index,
+                    *count,
+                    *kind,
+                    *normalized,
+                    stride,
+                    *offset as *const _,
+                );
+                gl::EnableVertexAttribArray(index);
+                gl::VertexAttribDivisor(index, 1);
+            }
+
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+        }
+
+        Ok(Self {
+            program,
+            vao,
+            ebo,
+            vbo_instance,
+            tex: 0,
+            instances: Vec::with_capacity(capacity),
+        })
+    }
+
+    /// Draws the queued glyphs with premultiplied alpha and clears them.
+    /// The texture of the atlas has to be bound to unit 0.
+    pub fn draw(&mut self) {
+        if self.instances.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::UseProgram(self.program.id);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo_instance);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                (self.instances.len() * size_of::<FieldInstance>()) as isize,
+                self.instances.as_ptr() as *const _,
+            );
+
+            let spread = gl::GetUniformLocation(self.program.id, b"spread\0".as_ptr() as *const _);
+            gl::Uniform1f(spread, SPREAD);
+
+            // Every layer over all glyphs before the next one, so effects
+            // never cover the glyphs next to them
+            let pass = gl::GetUniformLocation(self.program.id, b"pass\0".as_ptr() as *const _);
+            for layer in Pass::ALL {
+                if !self.instances.iter().any(|instance| instance.draws(layer)) {
+                    continue;
+                }
+
+                gl::Uniform1i(pass, layer as GLint);
+                gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    6,
+                    gl::UNSIGNED_INT,
+                    ptr::null(),
+                    self.instances.len() as GLsizei,
+                );
+            }
+
+            gl::UseProgram(0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+            gl::Disable(gl::BLEND);
+        }
+
+        self.instances.clear();
+        self.tex = 0;
+    }
+}
+
+impl Drop for FieldPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.ebo);
+            gl::DeleteBuffers(1, &self.vbo_instance);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::path::PathBuilder;
+
+    /// Distance in field pixels at a pixel of a field.
+    fn distance(field: &RasterizedGlyph, column: i32, row: i32) -> f32 {
+        let value = match &field.buffer {
+            BitmapBuffer::Rgb(buffer) => buffer[((row * field.width + column) * 3) as usize],
+            BitmapBuffer::Rgba(_) => unreachable!(),
+        };
+        (f32::from(value) / 255. - 0.5) * 2. * SPREAD
+    }
+
+    #[test]
+    fn outline() {
+        // A square from 0 to 20 above the baseline
+        let mut builder = PathBuilder::new();
+        builder
+            .move_to(Vec2f::new(0., -20.))
+            .line_to(Vec2f::new(20., -20.))
+            .line_to(Vec2f::new(20., 0.))
+            .line_to(Vec2f::new(0., 0.))
+            .close();
+        let field = from_outline(&builder.build());
+
+        let spread = SPREAD as i32;
+        assert_eq!((field.left, field.top), (-spread, 20 + spread));
+        assert_eq!(
+            (field.width, field.height),
+            (20 + spread * 2, 20 + spread * 2)
+        );
+
+        let approx = |a: f32, b: f32| assert!((a - b).abs() < 0.1, "{} is not {}", a, b);
+        // Pixel centers in the middle, inside the edge and outside of it
+        approx(distance(&field, spread + 10, spread + 10), 9.5);
+        approx(distance(&field, spread + 2, spread + 10), 2.5);
+        approx(distance(&field, spread - 3, spread + 10), -2.5);
+        approx(distance(&field, 0, 0), -SPREAD);
+
+        assert_eq!(from_outline(&Path::default()).width, 0);
+    }
+
+    #[test]
+    fn coverage() {
+        let mut buffer = vec![0; 10 * 10 * 3];
+        for row in 2..8 {
+            for column in 2..8 {
+                let start = (row * 10 + column) * 3;
+                buffer[start..start + 3].copy_from_slice(&[255, 255, 255]);
+            }
+        }
+        let glyph = RasterizedGlyph {
+            character: 'x',
+            width: 10,
+            height: 10,
+            top: 10,
+            left: 1,
+            buffer: BitmapBuffer::Rgb(buffer),
+        };
+        let field = from_coverage(&glyph);
+
+        let spread = SPREAD as i32;
+        assert_eq!((field.left, field.top), (1 - spread, 10 + spread));
+        assert_eq!(field.width, 10 + spread * 2);
+
+        // The edge lies between the covered pixels and their neighbours
+        let approx = |a: f32, b: f32| assert!((a - b).abs() < 0.1, "{} is not {}", a, b);
+        approx(distance(&field, spread + 2, spread + 5), 0.5);
+        approx(distance(&field, spread + 1, spread + 5), -0.5);
+        approx(distance(&field, spread + 4, spread + 4), 2.5);
+        approx(distance(&field, 0, 0), -SPREAD);
+    }
+}
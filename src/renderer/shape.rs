@@ -0,0 +1,501 @@
+use crate::renderer::Rgba;
+
+use std::f32::consts::TAU;
+
+/// Radii of the corners of a [`RoundedRect`], clockwise from the top left.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CornerRadii {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_right: f32,
+    pub bottom_left: f32,
+}
+
+impl CornerRadii {
+    pub const fn uniform(radius: f32) -> Self {
+        Self {
+            top_left: radius,
+            top_right: radius,
+            bottom_right: radius,
+            bottom_left: radius,
+        }
+    }
+
+    /// Limits every radius to half of the shorter side.
+    pub fn clamp(self, width: f32, height: f32) -> Self {
+        let max = width.min(height) / 2.;
+        let clamp = |radius: f32| radius.clamp(0., max.max(0.));
+
+        Self {
+            top_left: clamp(self.top_left),
+            top_right: clamp(self.top_right),
+            bottom_right: clamp(self.bottom_right),
+            bottom_left: clamp(self.bottom_left),
+        }
+    }
+}
+
+/// A border along the inside of the edge of a shape.
+#[derive(Debug, Clone, Copy)]
+pub struct Border {
+    pub width: f32,
+    pub color: Rgba,
+}
+
+#[derive(Debug, Clone)]
+pub struct RoundedRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub radii: CornerRadii,
+    pub color: Rgba,
+    pub border: Option<Border>,
+}
+
+/// Whether a curved shape is filled or only outlined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Fill,
+    /// A line of the given width along the inside of the edge.
+    Stroke(f32),
+}
+
+impl Style {
+    /// Width of the stroke, 0 for fills and `None` if nothing is drawn.
+    pub fn stroke_width(self) -> Option<f32> {
+        match self {
+            Style::Fill => Some(0.),
+            Style::Stroke(width) if width > 0. => Some(width),
+            Style::Stroke(_) => None,
+        }
+    }
+}
+
+/// An ellipse around `x`, `y`.
+#[derive(Debug, Clone)]
+pub struct Ellipse {
+    pub x: f32,
+    pub y: f32,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub color: Rgba,
+    pub style: Style,
+}
+
+impl Ellipse {
+    pub fn circle(x: f32, y: f32, radius: f32, color: Rgba, style: Style) -> Self {
+        Self {
+            x,
+            y,
+            radius_x: radius,
+            radius_y: radius,
+            color,
+            style,
+        }
+    }
+}
+
+/// A section of the outline of an ellipse, `width` pixels wide along the
+/// inside of the edge with flat ends.
+///
+/// Angles are in radians and grow clockwise from the positive x axis, a
+/// negative `sweep` goes counterclockwise.
+#[derive(Debug, Clone)]
+pub struct Arc {
+    pub x: f32,
+    pub y: f32,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub start: f32,
+    pub sweep: f32,
+    pub width: f32,
+    pub color: Rgba,
+}
+
+/// A slice of an ellipse between two angles, measured like those of an
+/// [`Arc`]. Stroking outlines the curve and both straight edges.
+#[derive(Debug, Clone)]
+pub struct Pie {
+    pub x: f32,
+    pub y: f32,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub start: f32,
+    pub sweep: f32,
+    pub color: Rgba,
+    pub style: Style,
+}
+
+impl Ellipse {
+    pub fn curve(&self) -> Option<Curve> {
+        Some(Curve {
+            x: self.x,
+            y: self.y,
+            radius_x: self.radius_x,
+            radius_y: self.radius_y,
+            kind: CurveKind::Ellipse,
+            stroke_width: self.style.stroke_width()?,
+            color: self.color,
+        })
+    }
+}
+
+impl Arc {
+    pub fn curve(&self) -> Option<Curve> {
+        if self.width <= 0. {
+            return None;
+        }
+
+        let kind = match Wedge::new(self.start, self.sweep) {
+            Some(wedge) => CurveKind::Arc(wedge),
+            None => CurveKind::Ellipse,
+        };
+        Some(Curve {
+            x: self.x,
+            y: self.y,
+            radius_x: self.radius_x,
+            radius_y: self.radius_y,
+            kind,
+            stroke_width: self.width,
+            color: self.color,
+        })
+    }
+}
+
+impl Pie {
+    pub fn curve(&self) -> Option<Curve> {
+        let kind = match Wedge::new(self.start, self.sweep) {
+            Some(wedge) => CurveKind::Pie(wedge),
+            None => CurveKind::Ellipse,
+        };
+        Some(Curve {
+            x: self.x,
+            y: self.y,
+            radius_x: self.radius_x,
+            radius_y: self.radius_y,
+            kind,
+            stroke_width: self.style.stroke_width()?,
+            color: self.color,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveKind {
+    Ellipse,
+    /// A stroke clipped by the wedge.
+    Arc(Wedge),
+    /// An ellipse clipped by the wedge, stroked after clipping.
+    Pie(Wedge),
+}
+
+/// An [`Ellipse`], [`Arc`] or [`Pie`] the way `base.frag` draws it.
+#[derive(Debug, Clone, Copy)]
+pub struct Curve {
+    pub x: f32,
+    pub y: f32,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub kind: CurveKind,
+    /// Width of the stroke, 0 for fills.
+    pub stroke_width: f32,
+    pub color: Rgba,
+}
+
+impl Curve {
+    /// Signed distance from a point relative to the center to the edge.
+    /// Mirrors `main` in `base.frag`.
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        let distance = ellipse_distance(x, y, self.radius_x, self.radius_y);
+
+        let distance = match self.kind {
+            CurveKind::Arc(wedge) => {
+                return stroke(distance, self.stroke_width).max(wedge.distance(x, y));
+            }
+            CurveKind::Pie(wedge) => distance.max(wedge.distance(x, y)),
+            CurveKind::Ellipse => distance,
+        };
+
+        if self.stroke_width > 0. {
+            stroke(distance, self.stroke_width)
+        } else {
+            distance
+        }
+    }
+}
+
+/// The angles between two rays from the origin, symmetric around the
+/// direction `bisector`, `aperture` holds the sine and cosine of half of the
+/// angle between both rays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wedge {
+    pub bisector: [f32; 2],
+    pub aperture: [f32; 2],
+}
+
+impl Wedge {
+    /// The wedge swept from `start`, `None` if it is a full turn.
+    pub fn new(start: f32, sweep: f32) -> Option<Self> {
+        if sweep.abs() >= TAU {
+            return None;
+        }
+
+        let half = sweep.abs() / 2.;
+        let middle = start + sweep / 2.;
+
+        Some(Self {
+            bisector: [middle.cos(), middle.sin()],
+            aperture: [half.sin(), half.cos()],
+        })
+    }
+
+    /// Signed distance from a point to the nearest ray, negative between
+    /// them. Mirrors `wedge` in `base.frag`.
+    pub fn distance(&self, x: f32, y: f32) -> f32 {
+        let [cos, sin] = self.bisector;
+        let [sx, sy] = self.aperture;
+
+        // Rotate the bisector onto the y axis, the wedge is symmetric
+        let px = (y * cos - x * sin).abs();
+        let py = x * cos + y * sin;
+
+        let along = (px * sx + py * sy).max(0.);
+        let (dx, dy) = (px - sx * along, py - sy * along);
+        let side = sy * px - sx * py;
+
+        // Zero on the rays, like `sign` in GLSL
+        let sign = if side == 0. { 0. } else { side.signum() };
+        (dx * dx + dy * dy).sqrt() * sign
+    }
+}
+
+/// Approximate signed distance from a point to the edge of an ellipse around
+/// the origin, exact for circles. Mirrors `ellipse` in `base.frag`.
+pub fn ellipse_distance(x: f32, y: f32, radius_x: f32, radius_y: f32) -> f32 {
+    let k1 = ((x / radius_x).powi(2) + (y / radius_y).powi(2)).sqrt();
+    let k2 = ((x / (radius_x * radius_x)).powi(2) + (y / (radius_y * radius_y)).powi(2)).sqrt();
+
+    if k2 == 0. {
+        return -radius_x.min(radius_y);
+    }
+    k1 * (k1 - 1.) / k2
+}
+
+/// Turns the distance to the edge of a shape into the distance to a line of
+/// `width` along the inside of that edge.
+pub fn stroke(distance: f32, width: f32) -> f32 {
+    distance.max(-(distance + width))
+}
+
+/// Signed distance from a point to the edge of a rounded rectangle, negative
+/// inside. The point is relative to the center of the rectangle, `y` grows
+/// downwards. Mirrors `roundedRect` in `base.frag`.
+pub fn rounded_rect_distance(
+    x: f32,
+    y: f32,
+    half_width: f32,
+    half_height: f32,
+    radii: CornerRadii,
+) -> f32 {
+    let radius = match (x > 0., y > 0.) {
+        (false, false) => radii.top_left,
+        (true, false) => radii.top_right,
+        (true, true) => radii.bottom_right,
+        (false, true) => radii.bottom_left,
+    };
+
+    let qx = x.abs() - half_width + radius;
+    let qy = y.abs() - half_height + radius;
+    let outside = (qx.max(0.).powi(2) + qy.max(0.).powi(2)).sqrt();
+
+    qx.max(qy).min(0.) + outside - radius
+}
+
+/// Color of a pixel whose center is `distance` away from the edge of a shape,
+/// anti-aliased over one pixel. Mirrors `shade` in `base.frag`.
+pub fn shade(distance: f32, color: Rgba, border: Option<Border>) -> Rgba {
+    let premultiply = |color: Rgba| {
+        let alpha = f32::from(color.a) / 255.;
+        [
+            f32::from(color.r) / 255. * alpha,
+            f32::from(color.g) / 255. * alpha,
+            f32::from(color.b) / 255. * alpha,
+            alpha,
+        ]
+    };
+
+    let coverage = |distance: f32| (0.5 - distance).clamp(0., 1.);
+
+    let mut shaded = premultiply(color);
+    if let Some(border) = border.filter(|border| border.width > 0.) {
+        let inner = coverage(distance + border.width);
+        let border = premultiply(border.color);
+        for (channel, border) in shaded.iter_mut().zip(border) {
+            *channel = border + (*channel - border) * inner;
+        }
+    }
+
+    let alpha = shaded[3] * coverage(distance);
+    if alpha <= 0. {
+        return Rgba {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+    }
+
+    let straight = |channel: f32| (channel / shaded[3] * 255.).round().clamp(0., 255.) as u8;
+    Rgba {
+        r: straight(shaded[0]),
+        g: straight(shaded[1]),
+        b: straight(shaded[2]),
+        a: (alpha * 255.).round() as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::assert_area;
+
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn distance() {
+        let radii = CornerRadii {
+            top_left: 0.,
+            top_right: 4.,
+            bottom_right: 0.,
+            bottom_left: 0.,
+        };
+
+        // Sharp corners and edges
+        assert_eq!(rounded_rect_distance(0., 0., 10., 5., radii), -5.);
+        assert_eq!(rounded_rect_distance(-12., 0., 10., 5., radii), 2.);
+        assert_eq!(rounded_rect_distance(-13., -9., 10., 5., radii), 5.);
+
+        // The rounded corner is a quarter circle around (6, -1)
+        let diagonal = rounded_rect_distance(10., -5., 10., 5., radii);
+        assert!((diagonal - (32f32.sqrt() - 4.)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn border() {
+        let color = Rgba {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let border = Border {
+            width: 2.,
+            color: Rgba {
+                r: 0,
+                g: 0,
+                b: 255,
+                a: 255,
+            },
+        };
+
+        assert_eq!(shade(-5., color, Some(border)), color);
+        assert_eq!(shade(-1., color, Some(border)), border.color);
+        assert_eq!(shade(0., color, Some(border)).a, 128);
+        assert_eq!(shade(1., color, Some(border)).a, 0);
+    }
+
+    /// Sums the anti-aliased coverage of the pixels of a `size` square
+    /// around the origin.
+    fn area(size: i32, distance: impl Fn(f32, f32) -> f32) -> f32 {
+        let mut area = 0.;
+        for y in -size..size {
+            for x in -size..size {
+                let distance = distance(x as f32 + 0.5, y as f32 + 0.5);
+                area += (0.5 - distance).clamp(0., 1.);
+            }
+        }
+        area
+    }
+
+    #[test]
+    fn ellipse_coverage() {
+        assert_eq!(ellipse_distance(0., 0., 4., 2.), -2.);
+        assert_eq!(ellipse_distance(3., 4., 10., 10.), -5.);
+        assert!(ellipse_distance(4., 0., 4., 2.).abs() < 1e-6);
+
+        let circle = area(40, |x, y| ellipse_distance(x, y, 30., 30.));
+        assert_area(circle, PI * 30. * 30.);
+
+        let ellipse = area(40, |x, y| ellipse_distance(x, y, 36., 20.));
+        assert_area(ellipse, PI * 36. * 20.);
+
+        let ring = area(40, |x, y| stroke(ellipse_distance(x, y, 30., 30.), 6.));
+        assert_area(ring, PI * (30. * 30. - 24. * 24.));
+    }
+
+    #[test]
+    fn curves() {
+        let color = Rgba {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let circle = Ellipse::circle(0., 0., 10., color, Style::Stroke(0.));
+        assert!(circle.curve().is_none());
+
+        let full = Pie {
+            x: 0.,
+            y: 0.,
+            radius_x: 10.,
+            radius_y: 10.,
+            start: 0.,
+            sweep: -TAU,
+            color,
+            style: Style::Stroke(2.),
+        };
+        let curve = full.curve().unwrap();
+        assert_eq!(curve.kind, CurveKind::Ellipse);
+        assert!((curve.distance(0., 9.) + 1.).abs() < 1e-5);
+
+        // Only the stroke of a pie slice includes the straight edges
+        let half = Pie { sweep: PI, ..full };
+        assert!(half.curve().unwrap().distance(5., 0.5) < 0.);
+        let arc = Arc {
+            x: 0.,
+            y: 0.,
+            radius_x: 10.,
+            radius_y: 10.,
+            start: 0.,
+            sweep: PI,
+            width: 2.,
+            color,
+        };
+        assert!(arc.curve().unwrap().distance(5., 0.5) > 0.);
+        assert!(arc.curve().unwrap().distance(0., 9.) < 0.);
+    }
+
+    #[test]
+    fn wedge_coverage() {
+        let quarter = Wedge::new(0., FRAC_PI_2).unwrap();
+        assert!(quarter.distance(1., 1.) < 0.);
+        assert!(quarter.distance(-1., 1.) > 0.);
+        assert!((quarter.distance(-3., 4.) - 3.).abs() < 1e-5);
+        assert!((quarter.distance(-3., -4.) - 5.).abs() < 1e-5);
+        assert_eq!(Wedge::new(1., -TAU), None);
+
+        for (start, sweep) in [(0., FRAC_PI_2), (1., -2.), (-0.5, 4.5)] {
+            let wedge = Wedge::new(start, sweep).unwrap();
+            let pie = area(40, |x, y| {
+                ellipse_distance(x, y, 30., 30.).max(wedge.distance(x, y))
+            });
+            assert_area(pie, 30. * 30. * sweep.abs() / 2.);
+
+            let arc = area(40, |x, y| {
+                stroke(ellipse_distance(x, y, 30., 30.), 8.).max(wedge.distance(x, y))
+            });
+            assert_area(arc, (30. * 30. - 22. * 22.) * sweep.abs() / 2.);
+        }
+    }
+}
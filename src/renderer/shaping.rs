@@ -0,0 +1,761 @@
+use crate::renderer::font_file;
+use crate::renderer::path::{Path, PathBuilder};
+use crate::vectors::Vec2f;
+
+use fnv::FnvHasher;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::BuildHasherDefault;
+use std::ops::Range;
+
+use crossfont::{
+    BitmapBuffer, Error as RasterizerError, FontDesc, FontKey, GlyphKey, Metrics, Rasterize,
+    RasterizedGlyph, Rasterizer, Size, Slant, Style, Weight,
+};
+
+use swash::scale::image::{Content, Image};
+use swash::scale::{Render, ScaleContext, Source, StrikeWith};
+use swash::zeno::{Format, Point, Verb};
+use unicode_bidi::{BidiInfo, ParagraphInfo};
+use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A glyph of a font, either a glyph index produced by shaping or a
+/// character rasterized by crossfont.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlyphId {
+    Index(u16),
+    Char(char),
+}
+
+/// Weight and slant of a font.
+///
+/// Weights are OpenType weights from 100 (thin) to 950 (extra black), the
+/// closest weight the family provides is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontStyle {
+    pub weight: u16,
+    pub italic: bool,
+}
+
+impl FontStyle {
+    pub const REGULAR: FontStyle = FontStyle::new(400, false);
+    pub const BOLD: FontStyle = FontStyle::new(700, false);
+    pub const ITALIC: FontStyle = FontStyle::new(400, true);
+    pub const BOLD_ITALIC: FontStyle = FontStyle::new(700, true);
+
+    pub const fn new(weight: u16, italic: bool) -> Self {
+        Self { weight, italic }
+    }
+
+    /// The style crossfont is asked for when the font file can't be located.
+    fn description(self) -> Style {
+        Style::Description {
+            slant: if self.italic {
+                Slant::Italic
+            } else {
+                Slant::Normal
+            },
+            weight: if self.weight >= 600 {
+                Weight::Bold
+            } else {
+                Weight::Normal
+            },
+        }
+    }
+}
+
+impl Default for FontStyle {
+    fn default() -> Self {
+        Self::REGULAR
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShapedKey {
+    pub font_key: FontKey,
+    pub id: GlyphId,
+    pub size: Size,
+}
+
+/// A glyph placed relative to the start of the shaped text.
+#[derive(Debug, Clone, Copy)]
+pub struct PositionedGlyph {
+    pub key: ShapedKey,
+    /// Byte offset of the grapheme cluster the glyph belongs to.
+    pub cluster: usize,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct ShapedText {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub advance: f32,
+}
+
+/// A font file, parsed again by each call since rustybuzz faces borrow the
+/// data they are parsed from.
+struct FontData {
+    data: Box<[u8]>,
+    index: u32,
+}
+
+impl FontData {
+    /// The font at `index` of `data`, if rustybuzz can parse it.
+    fn new(data: Vec<u8>, index: u32) -> Option<Self> {
+        rustybuzz::Face::from_slice(&data, index)?;
+
+        Some(Self {
+            data: data.into_boxed_slice(),
+            index,
+        })
+    }
+
+    fn face(&self) -> Option<rustybuzz::Face<'_>> {
+        rustybuzz::Face::from_slice(&self.data, self.index)
+    }
+
+    /// Shapes `text` in one direction, positions are in font units of the
+    /// returned em size. Right-to-left glyphs are returned from left to right,
+    /// and every glyph keeps the offset of its own character.
+    fn shape(&self, text: &str, rtl: bool) -> Option<(rustybuzz::GlyphBuffer, f32)> {
+        let face = self.face()?;
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_cluster_level(rustybuzz::BufferClusterLevel::MonotoneCharacters);
+        buffer.set_direction(if rtl {
+            rustybuzz::Direction::RightToLeft
+        } else {
+            rustybuzz::Direction::LeftToRight
+        });
+        buffer.guess_segment_properties();
+
+        Some((
+            rustybuzz::shape(&face, &[], buffer),
+            f32::from(face.units_per_em() as u16),
+        ))
+    }
+
+    fn has_glyph(&self, character: char) -> bool {
+        self.face()
+            .and_then(|face| face.glyph_index(character))
+            .is_some_and(|id| id.0 != 0)
+    }
+}
+
+/// Turns strings into positioned glyphs and rasterizes them.
+///
+/// Fonts are loaded through crossfont, which also provides the metrics, and
+/// their files are shaped with rustybuzz and rasterized by glyph index with
+/// swash. Every grapheme cluster is shaped with the first font of the
+/// fallback chain that has glyphs for all of its characters, so combining
+/// marks and emoji sequences stay in the font that joins them. Clusters no
+/// font has keep going through crossfont, which asks fontconfig for a
+/// fallback.
+pub struct Shaper {
+    rasterizer: Rasterizer,
+    dpr: f64,
+    fonts: HashMap<FontKey, FontData, BuildHasherDefault<FnvHasher>>,
+    fallbacks: Vec<FontKey>,
+    resolved: HashMap<(FontKey, char), Option<FontKey>, BuildHasherDefault<FnvHasher>>,
+    scale_context: ScaleContext,
+}
+
+impl Shaper {
+    pub fn new(dpr: f64) -> Result<Self, RasterizerError> {
+        Ok(Self {
+            rasterizer: Rasterizer::new(dpr as f32, false)?,
+            dpr,
+            fonts: HashMap::default(),
+            fallbacks: Vec::new(),
+            resolved: HashMap::default(),
+            scale_context: ScaleContext::new(),
+        })
+    }
+
+    pub fn load_font(
+        &mut self,
+        family: &str,
+        style: FontStyle,
+        size: Size,
+    ) -> Result<FontKey, RasterizerError> {
+        let file = font_file::locate(family, style).ok();
+
+        // crossfont only knows regular and bold, other weights are loaded by
+        // the name of the style fontconfig matched
+        let crossfont_style = match file.as_ref().and_then(|file| file.style.clone()) {
+            Some(name) if style.weight != 400 && style.weight != 700 => Style::Specific(name),
+            _ => style.description(),
+        };
+        let desc = FontDesc::new(family, crossfont_style);
+        let font_key = self.rasterizer.load_font(&desc, size)?;
+
+        // Without the file the font is still usable, just not shaped
+        if let (Some(file), Entry::Vacant(entry)) = (file, self.fonts.entry(font_key)) {
+            let font = fs::read(&file.path)
+                .ok()
+                .and_then(|data| FontData::new(data, file.index));
+            if let Some(font) = font {
+                entry.insert(font);
+            }
+        }
+
+        Ok(font_key)
+    }
+
+    /// Appends a font to the fallback chain, it is used for characters that
+    /// neither the requested font nor any earlier fallback has a glyph for.
+    pub fn add_fallback(
+        &mut self,
+        family: &str,
+        style: FontStyle,
+        size: Size,
+    ) -> Result<FontKey, RasterizerError> {
+        let font_key = self.load_font(family, style, size)?;
+        if !self.fallbacks.contains(&font_key) {
+            self.fallbacks.push(font_key);
+            self.resolved.clear();
+        }
+
+        Ok(font_key)
+    }
+
+    pub fn metrics(&mut self, font_key: FontKey, size: Size) -> Result<Metrics, RasterizerError> {
+        // crossfont only knows the size metrics of a face once a glyph was loaded
+        let _ = self.rasterizer.get_glyph(GlyphKey {
+            character: 'm',
+            font_key,
+            size,
+        });
+
+        self.rasterizer.metrics(font_key, size)
+    }
+
+    pub fn dpr(&self) -> f64 {
+        self.dpr
+    }
+
+    /// Changes the device pixel ratio glyphs are rasterized for, glyphs
+    /// rasterized before have the wrong size afterwards.
+    pub fn update_dpr(&mut self, dpr: f64) {
+        self.rasterizer.update_dpr(dpr as f32);
+        self.dpr = dpr;
+    }
+
+    /// Size of the em square in pixels.
+    pub fn pixel_size(&self, size: Size) -> f32 {
+        size.as_f32_pts() * self.dpr as f32 * 96. / 72.
+    }
+
+    /// Shapes a line of `text`, in the direction of its first strong
+    /// character.
+    pub fn shape(&mut self, text: &str, font_key: FontKey, size: Size) -> ShapedText {
+        let bidi = BidiInfo::new(text, None);
+        self.shape_bidi(&bidi, 0..text.len(), font_key, size)
+    }
+
+    /// Shapes the bytes `line` of the text of `bidi`, in the direction of
+    /// the paragraph it starts in. The glyphs of every run are placed in the
+    /// order they are displayed, from left to right, their clusters are
+    /// relative to the start of the line.
+    pub fn shape_bidi(
+        &mut self,
+        bidi: &BidiInfo,
+        line: Range<usize>,
+        font_key: FontKey,
+        size: Size,
+    ) -> ShapedText {
+        let mut shaped = ShapedText::default();
+        let paragraph = match paragraph(bidi, line.start) {
+            Some(paragraph) if !line.is_empty() => paragraph,
+            _ => return shaped,
+        };
+
+        let text = &bidi.text[line.clone()];
+        let (levels, runs) = bidi.visual_runs(paragraph, line.clone());
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+            let run = run.start - line.start..run.end - line.start;
+            self.shape_level_run(text, run, rtl, font_key, size, &mut shaped);
+        }
+
+        shaped
+    }
+
+    /// Appends the glyphs of a run of `text` in one direction to `shaped`.
+    fn shape_level_run(
+        &mut self,
+        text: &str,
+        run: Range<usize>,
+        rtl: bool,
+        font_key: FontKey,
+        size: Size,
+        shaped: &mut ShapedText,
+    ) {
+        // Split the run into runs of clusters resolved to the same font,
+        // clusters without any glyph stay in the current run
+        let mut font_runs = Vec::new();
+        let mut run_start = run.start;
+        let mut run_font = None;
+        for (index, cluster) in text[run.clone()].grapheme_indices(true) {
+            let index = run.start + index;
+            let font = match self.resolve_cluster(cluster, font_key, size) {
+                Some(font) => font,
+                None => continue,
+            };
+
+            match run_font {
+                Some(current) if current != font => {
+                    font_runs.push((run_start..index, current));
+                    run_start = index;
+                }
+                _ => (),
+            }
+            run_font = Some(font);
+        }
+        font_runs.push((run_start..run.end, run_font.unwrap_or(font_key)));
+
+        if rtl {
+            font_runs.reverse();
+        }
+        for (range, font) in font_runs {
+            self.shape_run(&text[range.clone()], range.start, font, size, rtl, shaped);
+        }
+    }
+
+    /// The first font of the chain starting at `font_key` that has a glyph
+    /// for every character of `cluster` that is drawn, or the font of its
+    /// first character if there is none.
+    fn resolve_cluster(&mut self, cluster: &str, font_key: FontKey, size: Size) -> Option<FontKey> {
+        let mut chars = cluster.chars();
+        let first = chars.next()?;
+        if chars.next().is_none() {
+            return self.resolve(first, font_key, size);
+        }
+
+        let chain: Vec<FontKey> = std::iter::once(font_key)
+            .chain(self.fallbacks.iter().copied())
+            .collect();
+        for candidate in chain {
+            let complete = cluster
+                .chars()
+                .filter(|&character| !is_ignorable(character))
+                .all(|character| self.has_glyph(candidate, character, size));
+            if complete {
+                return Some(candidate);
+            }
+        }
+
+        self.resolve(first, font_key, size)
+    }
+
+    /// The first font of the chain starting at `font_key` that has a glyph
+    /// for `character`.
+    fn resolve(&mut self, character: char, font_key: FontKey, size: Size) -> Option<FontKey> {
+        if let Some(resolved) = self.resolved.get(&(font_key, character)) {
+            return *resolved;
+        }
+
+        let chain: Vec<FontKey> = std::iter::once(font_key)
+            .chain(self.fallbacks.iter().copied())
+            .collect();
+        let resolved = chain
+            .into_iter()
+            .find(|&candidate| self.has_glyph(candidate, character, size));
+
+        self.resolved.insert((font_key, character), resolved);
+        resolved
+    }
+
+    fn has_glyph(&mut self, font_key: FontKey, character: char, size: Size) -> bool {
+        match self.fonts.get(&font_key) {
+            Some(font) => font.has_glyph(character),
+            None => self
+                .rasterizer
+                .get_glyph(GlyphKey {
+                    character,
+                    font_key,
+                    size,
+                })
+                .is_ok(),
+        }
+    }
+
+    /// Appends the glyphs of `text`, which starts at byte `offset` of the
+    /// shaped string, shaped with a single font in one direction to `shaped`.
+    fn shape_run(
+        &mut self,
+        text: &str,
+        offset: usize,
+        font_key: FontKey,
+        size: Size,
+        rtl: bool,
+        shaped: &mut ShapedText,
+    ) {
+        if text.is_empty() {
+            return;
+        }
+
+        let output = self.fonts.get(&font_key).and_then(|f| f.shape(text, rtl));
+        let (output, units_per_em) = match output {
+            Some(output) => output,
+            None => return self.shape_chars(text, offset, font_key, size, rtl, shaped),
+        };
+        let scale = self.pixel_size(size) / units_per_em;
+        let starts: Vec<usize> = text
+            .grapheme_indices(true)
+            .map(|(index, _)| index)
+            .collect();
+
+        // The cluster whose missing glyphs are already drawn by crossfont
+        let mut missing = None;
+        for (info, position) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+            let cluster = cluster_start(&starts, info.cluster as usize);
+            let mut id = GlyphId::Index(info.glyph_id as u16);
+            let mut x_advance = position.x_advance as f32 * scale;
+
+            if info.glyph_id == 0 {
+                let character = text[info.cluster as usize..].chars().next();
+                let character = match character {
+                    Some(character) if !is_ignorable(character) => character,
+                    _ => continue,
+                };
+
+                // A sequence the font can't join is drawn as its first
+                // character, marks stay on top of it
+                if is_extending(character) {
+                    x_advance = 0.;
+                } else if missing == Some(cluster) {
+                    continue;
+                } else {
+                    missing = Some(cluster);
+                    x_advance = self.char_advance(character, font_key, size);
+                }
+                id = GlyphId::Char(character);
+            }
+
+            shaped.glyphs.push(PositionedGlyph {
+                key: ShapedKey { font_key, id, size },
+                cluster: offset + cluster,
+                x: shaped.advance + position.x_offset as f32 * scale,
+                y: position.y_offset as f32 * scale,
+            });
+            shaped.advance += x_advance;
+        }
+    }
+
+    /// One glyph per grapheme cluster and its marks, for fonts whose file
+    /// could not be shaped.
+    fn shape_chars(
+        &mut self,
+        text: &str,
+        offset: usize,
+        font_key: FontKey,
+        size: Size,
+        rtl: bool,
+        shaped: &mut ShapedText,
+    ) {
+        let mut clusters: Vec<(usize, &str)> = text.grapheme_indices(true).collect();
+        if rtl {
+            clusters.reverse();
+        }
+
+        for (index, cluster) in clusters {
+            let x = shaped.advance;
+            let chars = cluster
+                .chars()
+                .filter(|&character| !is_ignorable(character));
+
+            // Crossfont can't join sequences, so only the first character
+            // and the marks on top of it are drawn
+            for (position, character) in chars.enumerate() {
+                if position > 0 && !is_extending(character) {
+                    continue;
+                }
+
+                shaped.glyphs.push(PositionedGlyph {
+                    key: ShapedKey {
+                        font_key,
+                        id: GlyphId::Char(character),
+                        size,
+                    },
+                    cluster: offset + index,
+                    x,
+                    y: 0.,
+                });
+                if position == 0 {
+                    shaped.advance += self.char_advance(character, font_key, size);
+                }
+            }
+        }
+    }
+
+    /// Crossfont glyphs have no advance, so it is estimated from the bitmap.
+    fn char_advance(&mut self, character: char, font_key: FontKey, size: Size) -> f32 {
+        let glyph = match self.rasterize(ShapedKey {
+            font_key,
+            id: GlyphId::Char(character),
+            size,
+        }) {
+            Ok(glyph) | Err(RasterizerError::MissingGlyph(glyph)) => glyph,
+            Err(_) => Default::default(),
+        };
+
+        if glyph.width > 0 {
+            (glyph.left + glyph.width) as f32
+        } else {
+            self.pixel_size(size) / 4.
+        }
+    }
+
+    pub fn rasterize(&mut self, key: ShapedKey) -> Result<RasterizedGlyph, RasterizerError> {
+        let index = match key.id {
+            GlyphId::Index(index) => index,
+            GlyphId::Char(character) => {
+                return self.rasterizer.get_glyph(GlyphKey {
+                    character,
+                    font_key: key.font_key,
+                    size: key.size,
+                })
+            }
+        };
+
+        let pixel_size = self.pixel_size(key.size);
+        let font = self
+            .fonts
+            .get(&key.font_key)
+            .ok_or(RasterizerError::UnknownFontKey)?;
+        let font_ref = swash::FontRef::from_index(&font.data, font.index as usize)
+            .ok_or(RasterizerError::UnknownFontKey)?;
+
+        let mut scaler = self
+            .scale_context
+            .builder(font_ref)
+            .size(pixel_size)
+            .hint(true)
+            .build();
+
+        let image = Render::new(&[
+            Source::ColorOutline(0),
+            Source::ColorBitmap(StrikeWith::BestFit),
+            Source::Outline,
+        ])
+        .format(Format::Alpha)
+        .render(&mut scaler, index);
+
+        // Glyphs without an outline, like spaces, have nothing to render
+        Ok(image.map(to_rasterized).unwrap_or_default())
+    }
+
+    /// The outline of a glyph in pixels, relative to its origin with y
+    /// pointing down. Characters rasterized by crossfont and glyphs without
+    /// an outline, like spaces, have none.
+    pub fn outline(&mut self, key: ShapedKey) -> Option<Path> {
+        let index = match key.id {
+            GlyphId::Index(index) => index,
+            GlyphId::Char(_) => return None,
+        };
+
+        let pixel_size = self.pixel_size(key.size);
+        let font = self.fonts.get(&key.font_key)?;
+        let font_ref = swash::FontRef::from_index(&font.data, font.index as usize)?;
+        let mut scaler = self
+            .scale_context
+            .builder(font_ref)
+            .size(pixel_size)
+            .build();
+        let outline = scaler.scale_outline(index)?;
+
+        let mut points = outline
+            .points()
+            .iter()
+            .map(|point: &Point| Vec2f::new(point.x, -point.y));
+        let mut builder = PathBuilder::new();
+        for verb in outline.verbs() {
+            match verb {
+                Verb::MoveTo => builder.move_to(points.next()?),
+                Verb::LineTo => builder.line_to(points.next()?),
+                Verb::QuadTo => {
+                    let control = points.next()?;
+                    builder.quadratic_to(control, points.next()?)
+                }
+                Verb::CurveTo => {
+                    let (control1, control2) = (points.next()?, points.next()?);
+                    builder.cubic_to(control1, control2, points.next()?)
+                }
+                Verb::Close => builder.close(),
+            };
+        }
+
+        let path = builder.build();
+        if path.subpaths.is_empty() {
+            None
+        } else {
+            Some(path)
+        }
+    }
+}
+
+/// Whether `character` is drawn on top of the character before it, like a
+/// combining mark.
+fn is_extending(character: char) -> bool {
+    matches!(
+        character.general_category(),
+        GeneralCategory::NonspacingMark | GeneralCategory::EnclosingMark
+    )
+}
+
+/// Whether `character` has no glyph of its own when a font doesn't join the
+/// cluster it is part of, like joiners, variation selectors and emoji
+/// modifiers.
+fn is_ignorable(character: char) -> bool {
+    matches!(character, '\u{200C}' | '\u{200D}' | '\u{FE00}'..='\u{FE0F}')
+        || matches!(character, '\u{1F3FB}'..='\u{1F3FF}')
+        || matches!(character, '\u{E0020}'..='\u{E007F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Byte offset of the cluster that contains byte `index`, given the sorted
+/// offsets of the clusters of a string.
+fn cluster_start(starts: &[usize], index: usize) -> usize {
+    match starts.binary_search(&index) {
+        Ok(position) => starts[position],
+        Err(position) => starts[position.saturating_sub(1)],
+    }
+}
+
+/// The paragraph of `bidi` the byte at `index` belongs to, the last one for
+/// the end of the text.
+pub(crate) fn paragraph<'a>(bidi: &'a BidiInfo, index: usize) -> Option<&'a ParagraphInfo> {
+    bidi.paragraphs
+        .iter()
+        .find(|paragraph| index < paragraph.range.end)
+        .or_else(|| bidi.paragraphs.last())
+}
+
+/// Converts a swash image into the buffers crossfont produces: coverage as
+/// RGB and colored glyphs as premultiplied RGBA.
+fn to_rasterized(image: Image) -> RasterizedGlyph {
+    let buffer = match image.content {
+        Content::Mask => BitmapBuffer::Rgb(image.data.iter().flat_map(|&a| [a, a, a]).collect()),
+        Content::SubpixelMask => BitmapBuffer::Rgb(
+            image
+                .data
+                .chunks_exact(4)
+                .flat_map(|p| [p[0], p[1], p[2]])
+                .collect(),
+        ),
+        Content::Color => BitmapBuffer::Rgba(
+            image
+                .data
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let premultiply = |c: u8| (u16::from(c) * u16::from(p[3]) / 255) as u8;
+                    [
+                        premultiply(p[0]),
+                        premultiply(p[1]),
+                        premultiply(p[2]),
+                        p[3],
+                    ]
+                })
+                .collect(),
+        ),
+    };
+
+    RasterizedGlyph {
+        character: '\0',
+        width: image.placement.width as i32,
+        height: image.placement.height as i32,
+        top: image.placement.top,
+        left: image.placement.left,
+        buffer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kerning() {
+        let size = Size::new(32.);
+        let mut shaper = Shaper::new(1.).unwrap();
+        let font_key = shaper
+            .load_font("DejaVu Sans", FontStyle::REGULAR, size)
+            .unwrap();
+
+        let pair = shaper.shape("AV", font_key, size);
+        let apart =
+            shaper.shape("A", font_key, size).advance + shaper.shape("V", font_key, size).advance;
+
+        assert!(pair
+            .glyphs
+            .iter()
+            .all(|g| matches!(g.key.id, GlyphId::Index(_))));
+        assert!(pair.advance < apart);
+    }
+
+    #[test]
+    fn fallback_chain() {
+        let size = Size::new(16.);
+        let mut shaper = Shaper::new(1.).unwrap();
+        let serif = shaper
+            .load_font("DejaVu Serif", FontStyle::REGULAR, size)
+            .unwrap();
+        let math = shaper
+            .add_fallback("DejaVu Math TeX Gyre", FontStyle::REGULAR, size)
+            .unwrap();
+        let sans = shaper
+            .add_fallback("DejaVu Sans", FontStyle::REGULAR, size)
+            .unwrap();
+
+        // Serif has no emoji, and only the math font has the bold A
+        let fonts: Vec<FontKey> = shaper
+            .shape("A\u{1F600}\u{1D400}\u{2200}", serif, size)
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.key.font_key)
+            .collect();
+        assert_eq!(fonts, [serif, sans, math, serif]);
+    }
+
+    #[test]
+    fn grapheme_clusters() {
+        let size = Size::new(16.);
+        let mut shaper = Shaper::new(1.).unwrap();
+        let serif = shaper
+            .load_font("DejaVu Serif", FontStyle::REGULAR, size)
+            .unwrap();
+        let sans = shaper
+            .add_fallback("DejaVu Sans", FontStyle::REGULAR, size)
+            .unwrap();
+
+        // Serif has the presentation selector but not the emoji, so the
+        // whole cluster goes to the fallback
+        let shaped = shaper.shape("q\u{301}\u{1F600}\u{FE0F}", serif, size);
+        let glyphs: Vec<(FontKey, usize)> = shaped
+            .glyphs
+            .iter()
+            .map(|glyph| (glyph.key.font_key, glyph.cluster))
+            .collect();
+        assert!(glyphs[..2].iter().all(|&glyph| glyph == (serif, 0)));
+        assert!(glyphs[2..].iter().all(|&glyph| glyph == (sans, 3)));
+        assert!(matches!(shaped.glyphs[1].key.id, GlyphId::Index(_)));
+
+        // Sequences no font has are drawn once, as their first character
+        let missing = shaper.shape("\u{1F1E9}\u{1F1EA}\u{1F44D}\u{1F3FD}", serif, size);
+        let ids: Vec<GlyphId> = missing.glyphs.iter().map(|glyph| glyph.key.id).collect();
+        assert_eq!(
+            ids,
+            [GlyphId::Char('\u{1F1E9}'), GlyphId::Char('\u{1F44D}')]
+        );
+
+        // Glyphs of the second regional indicator belong to the flag
+        let starts: Vec<usize> = "a🇩🇪b".grapheme_indices(true).map(|(i, _)| i).collect();
+        assert_eq!(starts, [0, 1, 9]);
+        assert_eq!(cluster_start(&starts, 5), 1);
+        assert_eq!(cluster_start(&starts, 9), 9);
+        assert!(is_extending('\u{20E3}') && !is_extending('\u{1F3FD}'));
+    }
+}
@@ -0,0 +1,540 @@
+use crate::renderer::mesh::{Mesh, MeshVertex};
+use crate::renderer::Rgba;
+use crate::vectors::Vec2f;
+
+use std::f32::consts::PI;
+
+/// Shape of the outside of the corner where two segments meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    /// Extends both edges until they meet, beveled beyond the miter limit.
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Shape of the ends of open lines and dashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    /// Extends the line by half of its width.
+    Square,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stroke {
+    pub width: f32,
+    pub color: Rgba,
+    pub join: LineJoin,
+    /// Longest miter relative to the width before a miter join is beveled.
+    pub miter_limit: f32,
+    pub cap: LineCap,
+    /// Lengths of alternating dashes and gaps, the line is solid if empty.
+    pub dashes: Vec<f32>,
+    /// Distance into the dash pattern at which the line starts.
+    pub dash_offset: f32,
+}
+
+impl Stroke {
+    /// A solid line with miter joins and butt caps.
+    pub fn new(width: f32, color: Rgba) -> Self {
+        Self {
+            width,
+            color,
+            join: LineJoin::Miter,
+            miter_limit: 4.,
+            cap: LineCap::Butt,
+            dashes: Vec::new(),
+            dash_offset: 0.,
+        }
+    }
+}
+
+/// Largest error of the distance to a round join or cap in pixels.
+const TOLERANCE: f32 = 0.025;
+
+/// Tessellates a line through `points`, back to the first point if `closed`.
+pub fn tessellate(points: &[Vec2f], closed: bool, stroke: &Stroke) -> Mesh {
+    let mut mesh = Mesh::new(stroke.width / 2., stroke.color);
+    if stroke.width <= 0. || !stroke.width.is_finite() {
+        return mesh;
+    }
+
+    let mut points = points.to_vec();
+    points.dedup_by(|b, a| (*b - *a).length() < 1e-3);
+    if closed && points.len() > 1 && (points[0] - points[points.len() - 1]).length() < 1e-3 {
+        points.pop();
+    }
+
+    let mut tessellator = Tessellator {
+        mesh: &mut mesh,
+        stroke,
+        // Half a pixel more on both sides for the anti-aliased edge
+        half: stroke.width / 2. + 0.5,
+    };
+    match dash_pattern(&stroke.dashes) {
+        Some(pattern) => {
+            for dash in dashes(&points, closed, &pattern, stroke.dash_offset) {
+                tessellator.polyline(&dash, false);
+            }
+        }
+        None => tessellator.polyline(&points, closed),
+    }
+
+    mesh
+}
+
+/// The pattern with an even number of lengths, `None` if it draws a solid line.
+fn dash_pattern(dashes: &[f32]) -> Option<Vec<f32>> {
+    let valid = dashes
+        .iter()
+        .all(|length| *length >= 0. && length.is_finite());
+    if !valid || dashes.iter().sum::<f32>() <= 0. {
+        return None;
+    }
+
+    // An odd number of lengths alternates between dashes and gaps
+    let mut pattern = dashes.to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_slice(dashes);
+    }
+    Some(pattern)
+}
+
+/// Splits a line into the polylines of its dashes.
+fn dashes(points: &[Vec2f], closed: bool, pattern: &[f32], offset: f32) -> Vec<Vec<Vec2f>> {
+    let mut result = Vec::new();
+    if points.is_empty() {
+        return result;
+    }
+
+    let total: f32 = pattern.iter().sum();
+    let mut index = 0;
+    let mut remaining = offset.rem_euclid(total);
+    while remaining >= pattern[index] {
+        remaining -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    remaining = pattern[index] - remaining;
+
+    let mut segments: Vec<(Vec2f, Vec2f)> = points.windows(2).map(|w| (w[0], w[1])).collect();
+    if closed && points.len() > 1 {
+        segments.push((points[points.len() - 1], points[0]));
+    }
+
+    let mut dash = Vec::new();
+    for (from, to) in segments {
+        let length = (to - from).length();
+        let direction = (to - from) * (1. / length);
+
+        let mut position = 0.;
+        loop {
+            let on = index % 2 == 0;
+            if on && dash.is_empty() {
+                dash.push(from + direction * position);
+            }
+
+            let step = remaining.min(length - position);
+            position += step;
+            remaining -= step;
+            if on && step > 0. {
+                dash.push(from + direction * position);
+            }
+
+            if remaining > 0. {
+                break;
+            }
+            if on {
+                result.push(std::mem::take(&mut dash));
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+    }
+    if !dash.is_empty() {
+        result.push(dash);
+    }
+
+    result
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    from: Vec2f,
+    direction: Vec2f,
+    normal: Vec2f,
+    length: f32,
+}
+
+impl Segment {
+    fn new(from: Vec2f, to: Vec2f) -> Self {
+        let length = (to - from).length();
+        let direction = if length > 0. {
+            (to - from) * (1. / length)
+        } else {
+            Vec2f::new(1., 0.)
+        };
+
+        Self {
+            from,
+            direction,
+            normal: direction.perp(),
+            length,
+        }
+    }
+
+    fn to(&self) -> Vec2f {
+        self.from + self.direction * self.length
+    }
+
+    /// Signed distance from the center line.
+    fn across(&self, point: Vec2f) -> f32 {
+        (point - self.from).dot(self.normal)
+    }
+}
+
+/// One end of the polygon of a segment, from its `normal` side to the other.
+#[derive(Debug, Clone, Default)]
+struct End {
+    points: Vec<Vec2f>,
+    /// A point on a flat cap and the direction into the segment.
+    cap: Option<(Vec2f, Vec2f)>,
+}
+
+struct Tessellator<'a> {
+    mesh: &'a mut Mesh,
+    stroke: &'a Stroke,
+    half: f32,
+}
+
+impl Tessellator<'_> {
+    fn polyline(&mut self, points: &[Vec2f], closed: bool) {
+        if points.len() == 1 {
+            // A dot, facing right
+            let segment = Segment::new(points[0], points[0]);
+            let (start, end) = (self.cap(&segment, true), self.cap(&segment, false));
+            self.segment(&segment, start, end);
+            return;
+        }
+
+        let count = if closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+        let segments: Vec<Segment> = (0..count)
+            .map(|i| Segment::new(points[i], points[(i + 1) % points.len()]))
+            .collect();
+
+        // The ends of the segments before and after every joint
+        let mut joints = Vec::new();
+        for i in 0..segments.len() {
+            if i > 0 || closed {
+                let before = &segments[(i + segments.len() - 1) % segments.len()];
+                joints.push(Some(self.join(before, &segments[i])));
+            } else {
+                joints.push(None);
+            }
+        }
+
+        for (i, segment) in segments.iter().enumerate() {
+            let start = match &joints[i] {
+                Some((_, after)) => after.clone(),
+                None => self.cap(segment, true),
+            };
+            let end = match joints.get(i + 1).or_else(|| closed.then(|| &joints[0])) {
+                Some(Some((before, _))) => before.clone(),
+                _ => self.cap(segment, false),
+            };
+
+            self.segment(segment, start, end);
+        }
+    }
+
+    /// Adds the polygon between both ends of a segment, split in half if
+    /// both have flat caps so each half is anti-aliased along one cap.
+    fn segment(&mut self, segment: &Segment, start: End, end: End) {
+        if start.cap.is_some() && end.cap.is_some() {
+            let middle = segment.from + segment.direction * (segment.length / 2.);
+            let split = End {
+                points: self.sides(segment, middle).to_vec(),
+                cap: None,
+            };
+
+            self.polygon(segment, &start.points, &split.points, start.cap);
+            self.polygon(segment, &split.points, &end.points, end.cap);
+        } else {
+            self.polygon(segment, &start.points, &end.points, start.cap.or(end.cap));
+        }
+    }
+
+    fn polygon(
+        &mut self,
+        segment: &Segment,
+        start: &[Vec2f],
+        end: &[Vec2f],
+        cap: Option<(Vec2f, Vec2f)>,
+    ) {
+        let polygon: Vec<MeshVertex> = start
+            .iter()
+            .chain(end.iter().rev())
+            .map(|point| {
+                let edge = cap.map_or(1., |(cap, inward)| 0.5 + (*point - cap).dot(inward));
+                vertex(*point, segment.across(*point), edge)
+            })
+            .collect();
+
+        self.mesh.fan(&polygon);
+    }
+
+    /// The corners of the stroke at `point`, on the normal side first.
+    fn sides(&self, segment: &Segment, point: Vec2f) -> [Vec2f; 2] {
+        let offset = segment.normal * self.half;
+        [point + offset, point - offset]
+    }
+
+    /// The start or end of a segment without a joint.
+    fn cap(&mut self, segment: &Segment, start: bool) -> End {
+        let (point, outward) = if start {
+            (segment.from, -segment.direction)
+        } else {
+            (segment.to(), segment.direction)
+        };
+
+        let line = match self.stroke.cap {
+            LineCap::Butt => point,
+            LineCap::Square => point + outward * (self.stroke.width / 2.),
+            LineCap::Round => {
+                // A half circle from the normal side around the end
+                let from = if start {
+                    segment.normal
+                } else {
+                    -segment.normal
+                };
+                self.arc(point, from * self.half, PI);
+
+                return End {
+                    points: self.sides(segment, point).to_vec(),
+                    cap: None,
+                };
+            }
+        };
+
+        End {
+            points: self.sides(segment, line + outward * 0.5).to_vec(),
+            cap: Some((line, -outward)),
+        }
+    }
+
+    /// Adds the outside of the corner between two segments, returns the end
+    /// of `before` and the start of `after`.
+    fn join(&mut self, before: &Segment, after: &Segment) -> (End, End) {
+        let point = after.from;
+        let turn = before.direction.cross(after.direction);
+        if turn.abs() < 1e-6 && before.direction.dot(after.direction) > 0. {
+            let end = |segment: &Segment| End {
+                points: self.sides(segment, point).to_vec(),
+                cap: None,
+            };
+            return (end(before), end(after));
+        }
+
+        // The inside of the corner is on the normal side for clockwise turns
+        let inside = if turn > 0. { self.half } else { -self.half };
+        let outer_before = point - before.normal * inside;
+        let outer_after = point - after.normal * inside;
+
+        // Both inner edges meet at the miter point, unless the segments are
+        // too short for it
+        let denominator = 1. + before.normal.dot(after.normal);
+        let miter = (before.normal + after.normal) * (1. / denominator.max(1e-6));
+        let inner = point + miter * inside;
+        let reach = (inner - point).dot(before.direction).abs();
+        let inner = (denominator > 1e-6 && reach <= before.length && reach <= after.length)
+            .then_some(inner);
+
+        let end = |segment: &Segment, outer: Vec2f| {
+            let mut points = match inner {
+                Some(inner) => vec![outer, point, inner],
+                None => vec![outer, point + segment.normal * inside],
+            };
+            points.sort_by(|a, b| segment.across(*b).total_cmp(&segment.across(*a)));
+            End { points, cap: None }
+        };
+        let ends = (end(before, outer_before), end(after, outer_after));
+
+        let miter_fits = denominator > 1e-6 && miter.length() <= self.stroke.miter_limit;
+        match self.stroke.join {
+            LineJoin::Miter if miter_fits => {
+                let tip = point - miter * inside;
+                let center = vertex(point, 0., 1.);
+                for (segment, outer, tip) in
+                    [(before, outer_before, tip), (after, tip, outer_after)]
+                {
+                    let across = |point| vertex(point, segment.across(point), 1.);
+                    self.mesh.triangle(center, across(outer), across(tip));
+                }
+            }
+            LineJoin::Round => {
+                let (from, to) = (outer_before - point, outer_after - point);
+                let mut sweep = from.cross(to).atan2(from.dot(to));
+                if from.cross(to).abs() < 1e-6 {
+                    // Turning back, around the end of `before`
+                    sweep = if from.perp().dot(before.direction) > 0. {
+                        PI
+                    } else {
+                        -PI
+                    };
+                }
+                self.arc(point, from, sweep);
+            }
+            _ => {
+                let chord = ((outer_before + outer_after) * 0.5 - point).length();
+                self.mesh.triangle(
+                    vertex(point, self.half - chord, 1.),
+                    vertex(outer_before, self.half, 1.),
+                    vertex(outer_after, self.half, 1.),
+                );
+            }
+        }
+
+        ends
+    }
+
+    /// Adds a fan around `center` from the offset `from` over `sweep` radians.
+    ///
+    /// The polygon is drawn around the circle, so the interpolated distance
+    /// is exact in the middle of every chord instead of cutting it off.
+    fn arc(&mut self, center: Vec2f, from: Vec2f, sweep: f32) {
+        let step = if self.half > TOLERANCE {
+            2. * (1. - TOLERANCE / self.half).acos()
+        } else {
+            PI
+        };
+        let steps = (sweep.abs() / step).ceil().max(2.) as usize;
+        let radius = 1. / (sweep / steps as f32 / 2.).cos();
+
+        let rotate = |angle: f32| {
+            let (sin, cos) = angle.sin_cos();
+            let (x, y) = (from.x * radius, from.y * radius);
+            center + Vec2f::new(x * cos - y * sin, x * sin + y * cos)
+        };
+        let middle = vertex(center, 0., 1.);
+        for i in 0..steps {
+            let a = rotate(sweep * i as f32 / steps as f32);
+            let b = rotate(sweep * (i + 1) as f32 / steps as f32);
+            self.mesh
+                .triangle(middle, vertex(a, self.half, 1.), vertex(b, self.half, 1.));
+        }
+    }
+}
+
+fn vertex(point: Vec2f, across: f32, edge: f32) -> MeshVertex {
+    MeshVertex {
+        x: point.x,
+        y: point.y,
+        across,
+        edge,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage::{self, assert_area};
+    use crate::renderer::backend::Backend;
+    use crate::renderer::Rgb;
+
+    /// Sums the coverage of a white stroke on black.
+    fn area(points: &[Vec2f], closed: bool, stroke: &Stroke) -> f32 {
+        coverage::area(|backend| backend.polyline(points, closed, stroke))
+    }
+
+    fn stroke(width: f32) -> Stroke {
+        Stroke::new(width, Rgb::from_hex(0xFFFFFF).into())
+    }
+
+    #[test]
+    fn caps() {
+        let line = [Vec2f::new(20.3, 30.), Vec2f::new(100.1, 90.)];
+        let length = (line[1] - line[0]).length();
+
+        let mut stroke = stroke(6.);
+        assert_area(area(&line, false, &stroke), length * 6.);
+
+        stroke.cap = LineCap::Square;
+        assert_area(area(&line, false, &stroke), length * 6. + 36.);
+
+        stroke.cap = LineCap::Round;
+        assert_area(area(&line, false, &stroke), length * 6. + PI * 9.);
+
+        // A dot
+        stroke.width = 20.;
+        assert_area(area(&line[..1], false, &stroke), PI * 100.);
+    }
+
+    #[test]
+    fn joins() {
+        let corner = [
+            Vec2f::new(10., 20.),
+            Vec2f::new(90., 20.),
+            Vec2f::new(90., 100.),
+        ];
+        let width: f32 = 8.;
+        let mut stroke = stroke(width);
+
+        assert_area(area(&corner, false, &stroke), 160. * width);
+
+        stroke.join = LineJoin::Bevel;
+        let bevel = 160. * width - width * width / 8.;
+        assert_area(area(&corner, false, &stroke), bevel);
+
+        // A right angle miter is sqrt(2) times as long as the width
+        stroke.join = LineJoin::Miter;
+        stroke.miter_limit = 1.4;
+        assert_area(area(&corner, false, &stroke), bevel);
+
+        stroke.join = LineJoin::Round;
+        let round = 160. * width - width * width / 4. + PI * width * width / 16.;
+        assert_area(area(&corner, false, &stroke), round);
+
+        // A closed square has four outer miters and no caps
+        stroke.join = LineJoin::Miter;
+        stroke.miter_limit = 4.;
+        let square = [
+            Vec2f::new(20., 20.),
+            Vec2f::new(100., 20.),
+            Vec2f::new(100., 100.),
+            Vec2f::new(20., 100.),
+        ];
+        assert_area(area(&square, true, &stroke), 88. * 88. - 72. * 72.);
+    }
+
+    #[test]
+    fn dashes() {
+        let corner = [
+            Vec2f::new(0., 0.),
+            Vec2f::new(10., 0.),
+            Vec2f::new(10., 10.),
+        ];
+
+        let split = super::dashes(&corner, false, &[4., 2.], 1.);
+        assert_eq!(
+            split,
+            [
+                vec![Vec2f::new(0., 0.), Vec2f::new(3., 0.)],
+                vec![Vec2f::new(5., 0.), Vec2f::new(9., 0.)],
+                vec![Vec2f::new(10., 1.), Vec2f::new(10., 5.)],
+                vec![Vec2f::new(10., 7.), Vec2f::new(10., 10.)],
+            ]
+        );
+        assert_eq!(dash_pattern(&[3.]), Some(vec![3., 3.]));
+        assert_eq!(dash_pattern(&[0., 0.]), None);
+
+        let mut stroke = stroke(4.);
+        stroke.dashes = vec![12., 8.];
+        let line = [Vec2f::new(10., 64.), Vec2f::new(110., 64.)];
+        assert_area(area(&line, false, &stroke), 60. * 4.);
+    }
+}
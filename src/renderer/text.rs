@@ -0,0 +1,153 @@
+use crate::renderer::decoration::DecorationMetrics;
+use crate::renderer::layout::{self, Align, Layout};
+use crate::renderer::shaping::{FontStyle, Shaper};
+
+use fnv::FnvHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+use crossfont::{Error as RasterizerError, FontKey, Metrics, Size};
+
+/// The fonts text is laid out with, shared by `TextRenderer` and
+/// `CpuBackend`.
+///
+/// Styles of the family are loaded the first time they are used and the
+/// metrics the first time text is laid out at a size.
+pub struct TextState {
+    pub(crate) shaper: Shaper,
+    family: String,
+    /// The size text is laid out at unless another one is given.
+    size: Size,
+    font_keys: HashMap<FontStyle, FontKey, BuildHasherDefault<FnvHasher>>,
+    /// Metrics of the regular font for every size text was laid out at.
+    pub(crate) metrics: HashMap<Size, Metrics, BuildHasherDefault<FnvHasher>>,
+}
+
+impl TextState {
+    pub fn new(family: &str, font_size: f32, dpr: f64) -> Result<Self, RasterizerError> {
+        let size = Size::new(font_size);
+
+        let mut shaper = Shaper::new(dpr)?;
+        let font_key = shaper.load_font(family, FontStyle::REGULAR, size)?;
+        let metrics = shaper.metrics(font_key, size)?;
+
+        let mut font_keys = HashMap::default();
+        font_keys.insert(FontStyle::REGULAR, font_key);
+        let mut sized_metrics = HashMap::default();
+        sized_metrics.insert(size, metrics);
+
+        Ok(Self {
+            shaper,
+            family: family.to_owned(),
+            size,
+            font_keys,
+            metrics: sized_metrics,
+        })
+    }
+
+    /// Updates the metrics for a new scale factor. Returns whether it
+    /// changed, in which case cached glyphs have to be rasterized again.
+    pub fn update_dpr(&mut self, dpr: f64) -> bool {
+        if self.shaper.dpr() == dpr {
+            return false;
+        }
+
+        self.shaper.update_dpr(dpr);
+        let font_key = self.font_key(FontStyle::REGULAR);
+        if let Ok(metrics) = self.shaper.metrics(font_key, self.size) {
+            self.metrics.clear();
+            self.metrics.insert(self.size, metrics);
+        }
+
+        true
+    }
+
+    /// Appends a font to the fallback chain for characters the font has no
+    /// glyph for.
+    pub fn add_fallback(&mut self, family: &str) -> Result<(), RasterizerError> {
+        self.shaper
+            .add_fallback(family, FontStyle::REGULAR, self.size)?;
+
+        Ok(())
+    }
+
+    /// The font of the family in `style`, loaded the first time it is used.
+    pub fn font_key(&mut self, style: FontStyle) -> FontKey {
+        if let Some(font_key) = self.font_keys.get(&style) {
+            return *font_key;
+        }
+
+        let font_key = self
+            .shaper
+            .load_font(&self.family, style, self.size)
+            .unwrap_or(self.font_keys[&FontStyle::REGULAR]);
+        self.font_keys.insert(style, font_key);
+
+        font_key
+    }
+
+    /// The metrics of the font at `size`, looked up the first time text is
+    /// laid out at it.
+    pub fn metrics(&mut self, size: Size) -> Metrics {
+        if let Some(metrics) = self.metrics.get(&size) {
+            return *metrics;
+        }
+
+        let font_key = self.font_key(FontStyle::REGULAR);
+        let metrics = self
+            .shaper
+            .metrics(font_key, size)
+            .unwrap_or(self.metrics[&self.size]);
+        self.metrics.insert(size, metrics);
+
+        metrics
+    }
+
+    pub fn size(&self) -> Size {
+        self.size
+    }
+
+    /// The size text is laid out at unless another one is given, in points.
+    pub fn font_size(&self) -> f32 {
+        self.size.as_f32_pts()
+    }
+
+    /// Changes the size text is laid out at by default.
+    pub fn set_font_size(&mut self, font_size: f32) {
+        let size = Size::new(font_size);
+        self.metrics(size);
+        self.size = size;
+    }
+
+    /// Distance of lines at the font size.
+    pub fn line_height(&self) -> i16 {
+        self.metrics[&self.size].line_height as i16
+    }
+
+    /// Wraps `string` at `font_size` points into lines no wider than
+    /// `max_width`, the lines are `Metrics::line_height` apart.
+    pub fn layout_sized(
+        &mut self,
+        string: &str,
+        font_size: f32,
+        max_width: f32,
+        align: Align,
+        style: FontStyle,
+    ) -> Layout {
+        let size = Size::new(font_size);
+        let font_key = self.font_key(style);
+        let metrics = self.metrics(size);
+
+        let mut layout = layout::layout(
+            &mut self.shaper,
+            string,
+            font_key,
+            size,
+            max_width,
+            align,
+            metrics.line_height as f32,
+        );
+        layout.decoration_metrics = DecorationMetrics::new(&metrics, size);
+        layout
+    }
+}